@@ -3,7 +3,7 @@ mod settings;
 mod staff;
 mod tablet;
 
-use settings::Settings;
+use settings::{Args, Settings};
 use staff::Staff;
 use std::env;
 use std::panic;
@@ -14,18 +14,25 @@ extern crate time;
 #[tokio::main]
 async fn main() {
     // Start staffs to serve customers
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() < 2 {
-        panic!("[ERR] Please specify number of staffs: cargo run --bin client [num], [num] should be a positive integer");
-    }
-    let nums = (&args[1]).parse().unwrap();
+    let usage: &str = "cargo run --bin client [num] [--profile development|test|production] [--config FILE] [--config-dir DIR] [--section.key VALUE]...";
+    let args: Args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => panic!("[SETTINGS] {}, usage: {}", err, usage),
+    };
+    let nums: usize = match args.command.as_deref().map(str::parse) {
+        Some(Ok(nums)) if nums > 0 => nums,
+        _ => panic!("[ERR] Please specify number of staffs: {}, [num] should be a positive integer", usage),
+    };
+    let config: Settings = match Settings::load(&args, &env::vars().collect()) {
+        Ok(config) => config,
+        Err(err) => panic!("[SETTINGS] {}", err),
+    };
     println!("Amount of staffs spawned: {}", nums);
 
     let pool = ThreadPool::with_name("staff-group".into(), nums);
     for i in 1..nums + 1 {
+        let config: Settings = config.clone();
         pool.execute(move || {
-            let config: Settings = Settings::new();
             let staff: Staff = Staff::new(i.to_string(), config);
             println!(
                 "------------------------- [SPAWN-STAFF-ID] {} -------------------------",
@@ -40,6 +40,20 @@ pub struct UpdateOrder {
     pub items: Vec<ItemPair>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Login {
+    pub username: String,
+    pub password: String,
+    pub device: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub token: String,
+    pub token_type: String,
+    pub expires_at: DateTime<Utc>,
+}
+
 impl PlaceOrder {
     pub fn disp(&self) -> String {
         let mut res = "".to_owned();
@@ -50,7 +64,7 @@ impl PlaceOrder {
         res.push_str(", items: [ ");
         for i in 0..self.items.len() {
             res.push_str(&self.items[i].name);
-            res.push_str(":");
+            res.push(':');
             res.push_str(&self.items[i].amount.to_string());
             if i != self.items.len() - 1 {
                 res.push_str(", ");
@@ -72,7 +86,7 @@ impl UpdateOrder {
         res.push_str(", items: [ ");
         for i in 0..self.items.len() {
             res.push_str(&self.items[i].name);
-            res.push_str(":");
+            res.push(':');
             res.push_str(&self.items[i].amount.to_string());
             if i != self.items.len() - 1 {
                 res.push_str(", ");
@@ -1,32 +1,39 @@
-use config::{Config, File};
+use config::{Config, ConfigError};
 use serde::Deserialize;
-use std::path::PathBuf;
+pub use settings_layers::{Args, Env, SettingsError};
+use settings_layers::Layers;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Client {
+    #[serde(rename = "base_url")]
     url: String,
     timeout: u64,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct API {
+#[derive(Debug, Clone, Deserialize)]
+pub struct Api {
     place_order: String,
     delete_order: String,
     update_order: String,
     status_order: String,
+    login: String,
+    refresh: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Auth {
     username: String,
     password: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
     pub client: Client,
-    pub api: API,
+    pub api: Api,
     pub auth: Auth,
+    // development, test or production, its file overriding config/default.toml
+    #[serde(default)]
+    pub profile: String,
 }
 
 impl Client {
@@ -38,7 +45,7 @@ impl Client {
     }
 }
 
-impl API {
+impl Api {
     pub fn get_place_order_api(&self) -> String {
         self.place_order.clone()
     }
@@ -51,6 +58,12 @@ impl API {
     pub fn get_status_order_api(&self) -> String {
         self.status_order.clone()
     }
+    pub fn get_login_api(&self) -> String {
+        self.login.clone()
+    }
+    pub fn get_refresh_api(&self) -> String {
+        self.refresh.clone()
+    }
 }
 
 impl Auth {
@@ -63,89 +76,42 @@ impl Auth {
 }
 
 impl Settings {
+    // Settings of the default profile without command line flags, as the tests run
+    #[cfg(test)]
     pub fn new() -> Self {
-        let mut config: Config = Config::default();
-        let (mut url, mut timeout) = ("".to_string(), 0);
-
-        let relative_path: PathBuf;
-        let mut absolute_path = std::env::current_dir().unwrap();
-        let mut path: &str = "";
-
-        //println!("absolute path: {:#?}", absolute_path);
-        if absolute_path.ends_with("client") {
-            relative_path = PathBuf::from("config\\production.toml");
-        } else {
-            relative_path = PathBuf::from("client\\config\\production.toml");
-        };
-        absolute_path.push(relative_path);
-
-        match absolute_path.to_str() {
-            Some(field) => path = field,
-            None => {}
-        };
-        match config.merge(File::with_name(path)) {
-            Ok(_) => {}
-            Err(err) => println!("[SETTINGS] Config Error: {}", err),
-        };
-        match config.get::<String>("client.base_url") {
-            Ok(field) => url = field.to_string(),
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        };
-        match config.get::<u64>("client.timeout") {
-            Ok(field) => timeout = field,
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        };
-
-        let (mut place_order_api, mut delete_order_api, mut update_order_api, mut status_order_api) = (
-            "".to_string(),
-            "".to_string(),
-            "".to_string(),
-            "".to_string(),
-        );
-        match config.get::<String>("api.place_order") {
-            Ok(field) => place_order_api = field.to_string(),
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        };
-        match config.get::<String>("api.delete_order") {
-            Ok(field) => delete_order_api = field.to_string(),
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        };
-        match config.get::<String>("api.update_order") {
-            Ok(field) => update_order_api = field.to_string(),
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        };
-        match config.get::<String>("api.status_order") {
-            Ok(field) => status_order_api = field.to_string(),
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        };
-
-        let (mut uname, mut pwd) = ("".to_string(), "".to_string());
-        match config.get::<String>("auth.username") {
-            Ok(field) => uname = field.to_string(),
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        };
-        match config.get::<String>("auth.password") {
-            Ok(field) => pwd = field.to_string(),
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        };
-
-        Settings {
-            client: Client {
-                url: url,
-                timeout: timeout,
-            },
-            api: API {
-                place_order: place_order_api,
-                delete_order: delete_order_api,
-                update_order: update_order_api,
-                status_order: status_order_api,
-            },
-            auth: Auth {
-                username: uname,
-                password: pwd,
-            },
+        match Settings::load(&Args::default(), &Env::new()) {
+            Ok(settings) => settings,
+            Err(err) => panic!("[SETTINGS] {}", err),
         }
     }
+
+    // Merge the layers of settings_layers::Layers over the defaults below, then check the result
+    pub fn load(args: &Args, env: &Env) -> Result<Settings, SettingsError> {
+        let (config, profile) = LAYERS.load(args, env, defaults)?;
+
+        LAYERS.required(&config, REQUIRED)?;
+        let mut settings: Settings = config.try_into().map_err(|err| SettingsError::Invalid(err.to_string()))?;
+        settings.profile = profile;
+        Ok(settings)
+    }
+}
+
+// Variables of their own, so that a client started next to the server never picks up its settings
+const LAYERS: Layers = Layers { prefix: "RESTAURANT_CLIENT", crate_dir: "client" };
+
+// Keys without a sensible default
+const REQUIRED: &[&str] = &["client.base_url", "auth.username", "auth.password"];
+
+fn defaults(config: &mut Config) -> Result<(), ConfigError> {
+    config
+        .set_default("client.timeout", 100)?
+        .set_default("api.place_order", "/api/place/order")?
+        .set_default("api.delete_order", "/api/delete/order")?
+        .set_default("api.update_order", "/api/update/order")?
+        .set_default("api.status_order", "/api/status/order")?
+        .set_default("api.login", "/api/auth/login")?
+        .set_default("api.refresh", "/api/auth/refresh")?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -154,13 +120,38 @@ mod tests {
     #[test]
     fn test_settings_new_given_config_provided_when_init_then_fields_obtained() {
         let config: Settings = Settings::new();
-        assert!(config.client.get_base_url().len() > 0);
+        assert!(!config.client.get_base_url().is_empty());
         assert!(config.client.get_timeout() > 0);
         assert_eq!("/api/place/order", config.api.get_place_order_api());
         assert_eq!("/api/delete/order", config.api.get_delete_order_api());
         assert_eq!("/api/update/order", config.api.get_update_order_api());
         assert_eq!("/api/status/order", config.api.get_status_order_api());
-        assert!(config.auth.get_username().len() > 0);
-        assert!(config.auth.get_password().len() > 0);
+        assert_eq!("/api/auth/login", config.api.get_login_api());
+        assert_eq!("/api/auth/refresh", config.api.get_refresh_api());
+        assert!(!config.auth.get_username().is_empty());
+        assert!(!config.auth.get_password().is_empty());
+        assert_eq!("production", config.profile);
+    }
+
+    #[test]
+    fn test_settings_args_parse_given_staffs_and_flags_when_parsed_then_layers_obtained() {
+        let parsed: Args = Args::parse(["4", "--profile", "test", "--client.timeout=5"].iter().map(|arg| arg.to_string())).unwrap();
+        assert_eq!(Some("4".to_string()), parsed.command);
+        assert_eq!(Some("test".to_string()), parsed.profile);
+        assert_eq!(vec![("client.timeout".to_string(), "5".to_string())], parsed.overrides);
+        assert!(matches!(Args::parse(vec!["--timeout".to_string(), "5".to_string()]), Err(SettingsError::Args(_))));
+
+        let config: Settings = Settings::load(&parsed, &Env::new()).unwrap();
+        assert_eq!((5, "test"), (config.client.get_timeout(), config.profile.as_str()));
+    }
+
+    #[test]
+    fn test_settings_load_given_missing_key_or_unknown_profile_when_loaded_then_typed_error() {
+        let args: Args = Args { overrides: vec![("client.base_url".to_string(), "".to_string())], ..Args::default() };
+        let err: SettingsError = Settings::load(&args, &Env::new()).unwrap_err();
+        assert_eq!(SettingsError::Missing("client.base_url".to_string(), "RESTAURANT_CLIENT_CLIENT__BASE_URL".to_string()), err);
+
+        let args: Args = Args { profile: Some("staging".to_string()), ..Args::default() };
+        assert_eq!(SettingsError::Profile("staging".to_string()), Settings::load(&args, &Env::new()).unwrap_err());
     }
 }
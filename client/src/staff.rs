@@ -1,4 +1,4 @@
-use crate::order_type::{DeleteOrder, ItemPair, PlaceOrder, ReqType, UpdateOrder};
+use crate::order_type::{DeleteOrder, ItemPair, Login, PlaceOrder, ReqType, Token, UpdateOrder};
 use crate::settings::Settings;
 use crate::tablet::Tablet;
 
@@ -7,24 +7,112 @@ use chrono::Utc;
 use rand::Rng;
 use reqwest::Client;
 use reqwest::Error;
+use reqwest::{RequestBuilder, Response, StatusCode};
 use std::convert::TryFrom;
+use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
 use std::vec::Vec;
 use tokio::runtime::Runtime;
 
+// secs before expiry from which the token gets refreshed
+const REFRESH_MARGIN: i64 = 60;
+
 pub struct Staff {
     table_id: String,
     config: Settings,
+    // bearer token of the tablet, logged in on the first request and reused afterwards
+    token: Mutex<Option<Token>>,
 }
 
 impl Staff {
     pub fn new(table_id: String, config: Settings) -> Staff {
         Staff {
-            table_id: table_id,
-            config: config,
+            table_id,
+            config,
+            token: Mutex::new(None),
         }
     }
+
+    fn get_device(&self) -> String {
+        format!("tablet-{}", self.table_id)
+    }
+
+    async fn login(&self) -> Result<Token, Error> {
+        let mut url: String = "".to_string();
+        url.push_str(&self.config.client.get_base_url());
+        url.push_str(&self.config.api.get_login_api());
+
+        let login: Login = Login {
+            username: self.config.auth.get_username(),
+            password: self.config.auth.get_password(),
+            device: self.get_device(),
+        };
+        println!("[STAFF-{}][LOGIN][REQUEST] DEVICE: {}", self.table_id, login.device);
+        let executor = Client::new();
+        let token: Token = executor
+            .post(url)
+            .timeout(Duration::from_secs(self.config.client.get_timeout()))
+            .json(&login)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        println!("[STAFF-{}][LOGIN][RESPONSE] EXPIRES AT: {}", self.table_id, token.expires_at);
+        Ok(token)
+    }
+
+    async fn refresh(&self, token: &Token) -> Result<Token, Error> {
+        let mut url: String = "".to_string();
+        url.push_str(&self.config.client.get_base_url());
+        url.push_str(&self.config.api.get_refresh_api());
+
+        let executor = Client::new();
+        let token: Token = executor
+            .post(url)
+            .bearer_auth(&token.token)
+            .timeout(Duration::from_secs(self.config.client.get_timeout()))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        println!("[STAFF-{}][REFRESH][RESPONSE] EXPIRES AT: {}", self.table_id, token.expires_at);
+        Ok(token)
+    }
+
+    // The token of the tablet, refreshed close to its expiry and logged in again when missing
+    async fn bearer(&self) -> Result<String, Error> {
+        let current: Option<Token> = self.token.lock().unwrap().clone();
+        let token: Token = match current {
+            Some(token) if (token.expires_at - Utc::now()).num_seconds() > REFRESH_MARGIN => return Ok(token.token),
+            Some(token) => match self.refresh(&token).await {
+                Ok(token) => token,
+                Err(e) => {
+                    println!("[STAFF-{}][REFRESH][ERROR] {}", self.table_id, e);
+                    self.login().await?
+                }
+            },
+            None => self.login().await?,
+        };
+        let bearer: String = token.token.clone();
+        *self.token.lock().unwrap() = Some(token);
+        Ok(bearer)
+    }
+
+    // Sends the request with the bearer token, a refused token is dropped so the next request logs in again
+    async fn send(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let resp = request
+            .bearer_auth(self.bearer().await?)
+            .timeout(Duration::from_secs(self.config.client.get_timeout()))
+            .send()
+            .await?;
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            self.token.lock().unwrap().take();
+        }
+        Ok(resp)
+    }
 }
 
 #[async_trait]
@@ -97,8 +185,8 @@ impl Tablet for Staff {
         let id = table_id.clone();
         let order: PlaceOrder = PlaceOrder {
             created_at: Utc::now(),
-            table_id: table_id,
-            items: items,
+            table_id,
+            items,
         };
 
         let mut url: String = "".to_string();
@@ -107,14 +195,7 @@ impl Tablet for Staff {
 
         println!("[STAFF-{}][PLACE][REQUEST] {}", id, order.disp());
         let executor = Client::new();
-        let resp = executor
-            .post(url)
-            .header("X-Auth-Username", self.config.auth.get_username())
-            .header("X-Auth-Password", self.config.auth.get_password())
-            .timeout(Duration::from_secs(self.config.client.get_timeout()))
-            .json(&order)
-            .send()
-            .await?;
+        let resp = self.send(executor.post(url).json(&order)).await?;
         let msg = resp.text().await?;
         println!("[STAFF-{}][PLACE][RESPONSE] {:?}", id, msg);
         Ok(())
@@ -124,8 +205,8 @@ impl Tablet for Staff {
         let id = table_id.clone();
         let order: DeleteOrder = DeleteOrder {
             deleted_at: Utc::now(),
-            table_id: table_id,
-            item: item,
+            table_id,
+            item,
         };
 
         let mut url: String = "".to_string();
@@ -134,14 +215,7 @@ impl Tablet for Staff {
 
         println!("[STAFF-{}][DELETE][REQUEST] {}", id, order.disp());
         let executor = Client::new();
-        let resp = executor
-            .delete(url)
-            .header("X-Auth-Username", self.config.auth.get_username())
-            .header("X-Auth-Password", self.config.auth.get_password())
-            .timeout(Duration::from_secs(self.config.client.get_timeout()))
-            .json(&order)
-            .send()
-            .await?;
+        let resp = self.send(executor.delete(url).json(&order)).await?;
         let msg = resp.text().await?;
         println!("[STAFF-{}][DELETE][RESPONSE] {:?}", id, msg);
         Ok(())
@@ -151,8 +225,8 @@ impl Tablet for Staff {
         let id = table_id.clone();
         let order: UpdateOrder = UpdateOrder {
             updated_at: Utc::now(),
-            table_id: table_id,
-            items: items,
+            table_id,
+            items,
         };
 
         let mut url: String = "".to_string();
@@ -161,14 +235,7 @@ impl Tablet for Staff {
 
         println!("[STAFF-{}][UPDATE][REQUEST] {}", id, order.disp());
         let executor = Client::new();
-        let resp = executor
-            .patch(url)
-            .header("X-Auth-Username", self.config.auth.get_username())
-            .header("X-Auth-Password", self.config.auth.get_password())
-            .timeout(Duration::from_secs(self.config.client.get_timeout()))
-            .json(&order)
-            .send()
-            .await?;
+        let resp = self.send(executor.patch(url).json(&order)).await?;
         let msg = resp.text().await?.to_string();
         println!("[STAFF-{}][UPDATE][RESPONSE] {:?}", id, msg);
         Ok(())
@@ -179,16 +246,12 @@ impl Tablet for Staff {
         let mut url: String = "".to_string();
         url.push_str(&self.config.client.get_base_url());
         url.push_str(&self.config.api.get_status_order_api());
-        url.push_str("/");
+        url.push('/');
         url.push_str(&table_id);
 
         println!("[STAFF-{}][STATUS_ALL][REQUEST] SENT! TABLE: {}", id, id);
         let executor = Client::new();
-        let resp = executor
-            .get(url)
-            .timeout(Duration::from_secs(self.config.client.get_timeout()))
-            .send()
-            .await?;
+        let resp = self.send(executor.get(url)).await?;
         let msg = resp.text().await?.to_string();
         println!("[STAFF-{}][STATUS_ALL][RESPONSE] {:?}", id, msg);
         Ok(())
@@ -198,9 +261,9 @@ impl Tablet for Staff {
         let mut url: String = "".to_string();
         url.push_str(&self.config.client.get_base_url());
         url.push_str(&self.config.api.get_status_order_api());
-        url.push_str("/");
+        url.push('/');
         url.push_str(&table_id);
-        url.push_str("/");
+        url.push('/');
         url.push_str(&item);
 
         println!(
@@ -208,11 +271,7 @@ impl Tablet for Staff {
             table_id, table_id, item
         );
         let executor = Client::new();
-        let resp = executor
-            .get(url)
-            .timeout(Duration::from_secs(self.config.client.get_timeout()))
-            .send()
-            .await?;
+        let resp = self.send(executor.get(url)).await?;
         let msg = resp.text().await?.to_string();
         println!("[STAFF-{}][STATUS_ITEM][RESPONSE] {:?}", table_id, msg);
         Ok(())
@@ -275,6 +334,14 @@ mod tests {
         assert_eq!("1", staff.get_table_id());
     }
 
+    #[test]
+    fn test_staff_get_device_given_table_id_provided_when_instantiated_then_device_named_after_table() {
+        let config: Settings = Settings::new();
+        let staff: Staff = Staff::new("1".to_string(), config);
+        assert_eq!("tablet-1", staff.get_device());
+        assert!(staff.token.lock().unwrap().is_none());
+    }
+
     #[test]
     fn test_get_item_given_rand_num_provided_when_executed_then_an_alphabet_obtained() {
         assert_eq!("A", get_item(0));
@@ -310,6 +377,6 @@ mod tests {
     fn test_generate_items_given_at_least_one_rand_num_provided_when_invoked_then_vec_len_greater_than_zero(
     ) {
         let res: Vec<ItemPair> = generate_items();
-        assert!(res.len() > 0);
+        assert!(!res.is_empty());
     }
 }
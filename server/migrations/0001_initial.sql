@@ -0,0 +1,91 @@
+-- Schema provisioned by Dbio::init before versioned migrations. The types and tables are
+-- created only when missing, so databases of earlier releases are adopted in place; the
+-- steps after them are not guarded and run once, as recorded in schema_version.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'tablestatus') THEN
+        CREATE TYPE TABLESTATUS AS ENUM ('Open', 'Close');
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'itemstatus') THEN
+        CREATE TYPE ITEMSTATUS AS ENUM ('New', 'Process', 'Done', 'Deleted');
+    END IF;
+END
+$$;
+
+CREATE TABLE IF NOT EXISTS TABLET (
+    opened_at TIMESTAMPTZ,
+    closed_at TIMESTAMPTZ,
+    table_id VARCHAR,
+    status TABLESTATUS
+);
+
+CREATE TABLE IF NOT EXISTS ITEMS (
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ,
+    table_id VARCHAR,
+    item VARCHAR,
+    amount INTEGER,
+    status ITEMSTATUS
+);
+
+CREATE TABLE IF NOT EXISTS ITEM_HISTORY (
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ,
+    table_id VARCHAR,
+    item VARCHAR,
+    amount INTEGER,
+    status ITEMSTATUS
+);
+
+-- every table session gets a surrogate id
+ALTER TABLE tablet ADD COLUMN session_id BIGSERIAL PRIMARY KEY;
+ALTER TABLE tablet
+    ALTER COLUMN opened_at SET NOT NULL,
+    ALTER COLUMN table_id SET NOT NULL,
+    ALTER COLUMN status SET NOT NULL;
+
+-- items/item_history reference their session; older releases stored created_at
+-- truncated to seconds, so sessions are matched at that precision
+ALTER TABLE items ADD COLUMN item_id BIGSERIAL PRIMARY KEY, ADD COLUMN session_id BIGINT;
+UPDATE items SET session_id = (SELECT MAX(t.session_id)
+                               FROM tablet t
+                               WHERE t.table_id = items.table_id AND
+                                     date_trunc('second', t.opened_at) = date_trunc('second', items.created_at));
+ALTER TABLE item_history ADD COLUMN history_id BIGSERIAL PRIMARY KEY, ADD COLUMN session_id BIGINT;
+UPDATE item_history SET session_id = (SELECT MAX(t.session_id)
+                                      FROM tablet t
+                                      WHERE t.table_id = item_history.table_id AND
+                                            date_trunc('second', t.opened_at) = date_trunc('second', item_history.created_at));
+-- rows without a session were never reachable through the api
+DELETE FROM items WHERE session_id IS NULL;
+DELETE FROM item_history WHERE session_id IS NULL;
+
+ALTER TABLE items
+    ALTER COLUMN session_id SET NOT NULL,
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET NOT NULL,
+    ALTER COLUMN table_id SET NOT NULL,
+    ALTER COLUMN item SET NOT NULL,
+    ALTER COLUMN amount SET NOT NULL,
+    ALTER COLUMN status SET NOT NULL,
+    ADD CONSTRAINT items_session_fk FOREIGN KEY (session_id) REFERENCES tablet (session_id) ON DELETE CASCADE;
+ALTER TABLE item_history
+    ALTER COLUMN session_id SET NOT NULL,
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET NOT NULL,
+    ALTER COLUMN table_id SET NOT NULL,
+    ALTER COLUMN item SET NOT NULL,
+    ALTER COLUMN amount SET NOT NULL,
+    ALTER COLUMN status SET NOT NULL,
+    ADD CONSTRAINT item_history_session_fk FOREIGN KEY (session_id) REFERENCES tablet (session_id) ON DELETE CASCADE;
+
+CREATE INDEX tablet_table_opened ON tablet (table_id, opened_at);
+-- an item is listed once per session, older releases could list it twice and the latest row wins
+DELETE FROM items a USING items b WHERE a.session_id = b.session_id AND a.item = b.item AND a.item_id < b.item_id;
+ALTER TABLE items ADD CONSTRAINT items_session_item UNIQUE (session_id, item);
+CREATE INDEX item_history_session ON item_history (session_id);
+-- a table left with several open sessions keeps the latest one open
+UPDATE tablet SET status = 'Close', closed_at = COALESCE(closed_at, opened_at)
+WHERE status = 'Open' AND session_id < (SELECT MAX(t.session_id) FROM tablet t WHERE t.table_id = tablet.table_id AND t.status = 'Open');
+-- at most one open session per table, whatever the interleaving of concurrent orders
+CREATE UNIQUE INDEX IF NOT EXISTS tablet_open_session ON tablet (table_id) WHERE status = 'Open';
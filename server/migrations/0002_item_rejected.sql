@@ -0,0 +1,2 @@
+-- items declined by the kitchen, run outside a transaction as PostgreSQL < 12 refuses ADD VALUE within one
+ALTER TYPE itemstatus ADD VALUE IF NOT EXISTS 'Rejected';
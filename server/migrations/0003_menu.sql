@@ -0,0 +1,13 @@
+-- dishes served by the restaurant, `cook_time` in seconds
+CREATE TABLE menu (
+    name VARCHAR PRIMARY KEY,
+    description VARCHAR NOT NULL DEFAULT '',
+    category VARCHAR NOT NULL DEFAULT '',
+    cook_time INTEGER NOT NULL CHECK (cook_time >= 0),
+    active BOOLEAN NOT NULL DEFAULT true
+);
+
+-- items 'A' to 'Z' ordered before the menu existed, keep in line with menu::default_menu
+INSERT INTO menu(name, description, category, cook_time, active)
+SELECT chr(64 + n), 'Dish ' || chr(64 + n), 'main', 5 + (n * 7) % 11, true
+FROM generate_series(1, 26) AS n;
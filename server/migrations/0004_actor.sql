@@ -0,0 +1,3 @@
+-- who opened each session and performed each item action, NULL for rows recorded before
+ALTER TABLE tablet ADD COLUMN staff VARCHAR, ADD COLUMN device VARCHAR;
+ALTER TABLE item_history ADD COLUMN staff VARCHAR, ADD COLUMN device VARCHAR;
@@ -0,0 +1,56 @@
+-- Statuses kept as text guarded by CHECK constraints, every table session keyed by a surrogate id
+-- taken in order of insertion
+CREATE TABLE tablet (
+    session_id INTEGER PRIMARY KEY,
+    opened_at TEXT NOT NULL,
+    closed_at TEXT,
+    table_id TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('Open', 'Close')),
+    staff TEXT,
+    device TEXT
+);
+
+CREATE TABLE items (
+    item_id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES tablet (session_id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    table_id TEXT NOT NULL,
+    item TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('New', 'Process', 'Done', 'Deleted', 'Rejected'))
+);
+
+-- `staff`/`device` of who performed each item action
+CREATE TABLE item_history (
+    history_id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES tablet (session_id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    table_id TEXT NOT NULL,
+    item TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('New', 'Process', 'Done', 'Deleted', 'Rejected')),
+    staff TEXT,
+    device TEXT
+);
+
+CREATE INDEX tablet_table_session ON tablet (table_id, session_id);
+CREATE UNIQUE INDEX items_session_item ON items (session_id, item);
+CREATE INDEX item_history_session ON item_history (session_id);
+-- at most one open session per table, whatever the interleaving of concurrent orders
+CREATE UNIQUE INDEX tablet_open_session ON tablet (table_id) WHERE status = 'Open';
+
+-- dishes served by the restaurant, `cook_time` in seconds
+CREATE TABLE menu (
+    name TEXT PRIMARY KEY,
+    description TEXT NOT NULL DEFAULT '',
+    category TEXT NOT NULL DEFAULT '',
+    cook_time INTEGER NOT NULL CHECK (cook_time >= 0),
+    active INTEGER NOT NULL DEFAULT 1
+);
+
+-- items 'A' to 'Z' of the first release, keep in line with menu::default_menu
+WITH RECURSIVE dish(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM dish WHERE n < 26)
+INSERT INTO menu(name, description, category, cook_time, active)
+SELECT char(64 + n), 'Dish ' || char(64 + n), 'main', 5 + (n * 7) % 11, 1 FROM dish;
@@ -0,0 +1,356 @@
+use crate::error::ServerError;
+use crate::order_type::{Actor, Login, Token};
+use crate::session::{Claims, Sessions};
+use crate::settings;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_std::task;
+use serde::{Deserialize, Serialize};
+use sha256::digest;
+use std::sync::Arc;
+use strum_macros::{Display, EnumString};
+use tide::{Middleware, Next, Request};
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // floor staff taking the orders
+    Staff,
+    Kitchen,
+    // voids served items, manages the menu and views reports
+    Manager,
+    // read-only screens, e.g. the order board of the dining room
+    Display,
+}
+
+impl Role {
+    pub const ALL: &'static [Role] = &[Role::Staff, Role::Kitchen, Role::Manager, Role::Display];
+}
+
+// Hash every unknown username is checked against, so that it takes as long to refuse as a wrong password
+const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$IboTDiDGemQWtAd22qxKUw$2boepLJN9VRiGD4NoJP+W0aKjyk3LLwg6aqWzyJ2Axg";
+
+#[derive(Clone)]
+struct Account {
+    name: String,
+    // SHA-256 digest of the username, as sent by the clients
+    username: String,
+    password_hash: String,
+    role: Role,
+}
+
+// Accounts of section [auth], the clients send SHA-256 digests of their username/password
+// and the password digest is checked against its salted argon2 hash
+#[derive(Clone)]
+pub struct Accounts {
+    accounts: Arc<Vec<Account>>,
+    sessions: Sessions,
+}
+
+// Who is behind an authorized request, along with the claims of its token if it came with one
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub name: String,
+    pub role: Role,
+    pub claims: Option<Claims>,
+}
+
+impl Identity {
+    pub fn actor(&self) -> Actor {
+        Actor {
+            staff: self.name.clone(),
+            device: self.claims.as_ref().map(|claims| claims.device.clone()),
+        }
+    }
+}
+
+impl Accounts {
+    pub fn new(config: &settings::Auth) -> Result<Accounts, ServerError> {
+        let mut accounts: Vec<Account> = Vec::new();
+        for account in config.get_accounts() {
+            let role: Role = account
+                .get_role()
+                .parse()
+                .map_err(|_| ServerError::Validation(format!("Unknown role: {} of account: {}", account.get_role(), account.get_username())))?;
+            if let Err(err) = PasswordHash::new(&account.get_password_hash()) {
+                return Err(ServerError::Validation(format!("Malformed password hash of account: {}, {}", account.get_username(), err)));
+            }
+            accounts.push(Account {
+                name: account.get_username(),
+                username: digest(account.get_username()),
+                password_hash: account.get_password_hash(),
+                role,
+            });
+        }
+        Ok(Accounts { accounts: Arc::new(accounts), sessions: Sessions::new(config) })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    // The account of the given name, as named in the `sub` of its tokens
+    fn account(&self, name: &str) -> Option<&Account> {
+        self.accounts.iter().find(|account| account.name == name)
+    }
+
+    // The account matching both digests, if any
+    fn verify(&self, username: &str, password: &str) -> Option<Account> {
+        let account: Option<&Account> = self.accounts.iter().find(|account| account.username == username);
+        let hash = PasswordHash::new(account.map_or(DUMMY_HASH, |account| &account.password_hash)).ok()?;
+        let verified: bool = Argon2::default().verify_password(password.as_bytes(), &hash).is_ok();
+        account.filter(|_| verified).cloned()
+    }
+
+    // `verify` on the blocking thread pool, the hashing would stall an async worker
+    async fn authenticate(&self, username: &str, password: &str) -> Option<Account> {
+        let (accounts, username, password) = (self.clone(), username.to_string(), password.to_string());
+        task::spawn_blocking(move || accounts.verify(&username, &password)).await
+    }
+
+    // A bearer token for the device, so that its later requests skip the password check
+    pub async fn login(&self, login: &Login) -> Result<Token, ServerError> {
+        if login.device.trim().is_empty() {
+            return Err(ServerError::Validation("Login Failed! device should not be blank".to_string()));
+        }
+        match self.authenticate(&login.username, &login.password).await {
+            Some(account) => self.sessions.issue(&account.name, account.role, &login.device),
+            None => Err(ServerError::Unauthorized("Login Failed! Wrong username or password".to_string())),
+        }
+    }
+
+    // A new token for the same account and device, the current one is revoked
+    pub fn refresh(&self, claims: &Claims) -> Result<Token, ServerError> {
+        let account = self
+            .account(&claims.sub)
+            .ok_or_else(|| ServerError::Unauthorized(format!("Refresh Failed! No account: {}", claims.sub)))?;
+        self.sessions.revoke(claims);
+        self.sessions.issue(&account.name, account.role, &claims.device)
+    }
+
+    pub fn logout(&self, claims: &Claims) {
+        self.sessions.revoke(claims);
+    }
+
+    // Bearer token first, then the X-Auth-Username/X-Auth-Password headers. The role of a token is the
+    // one its account has now, so that removing or demoting an account takes effect before `exp`
+    async fn identify<State>(&self, req: &Request<State>) -> Result<Identity, ServerError> {
+        if let Some(authorization) = req.header("Authorization") {
+            let token: &str = authorization
+                .as_str()
+                .strip_prefix("Bearer ")
+                .ok_or_else(|| ServerError::Unauthorized("Only bearer tokens are accepted".to_string()))?;
+            let claims: Claims = self.sessions.verify(token)?;
+            let account: &Account = self
+                .account(&claims.sub)
+                .ok_or_else(|| ServerError::Unauthorized(format!("No account: {}", claims.sub)))?;
+            return Ok(Identity { name: account.name.clone(), role: account.role, claims: Some(claims) });
+        }
+        match (req.header("X-Auth-Username"), req.header("X-Auth-Password")) {
+            (Some(name), Some(pwd)) => self
+                .authenticate(name.as_str(), pwd.as_str())
+                .await
+                .map(|account| Identity { name: account.name.clone(), role: account.role, claims: None })
+                .ok_or_else(|| ServerError::Unauthorized("Wrong username or password".to_string())),
+            _ => Err(ServerError::Unauthorized("No credentials".to_string())),
+        }
+    }
+
+    // "name@device" of a valid bearer token of an existing account, without going through the password
+    // check. The X-Auth headers are left out, their username is not verified at this point
+    pub fn principal<State>(&self, req: &Request<State>) -> Option<String> {
+        let token: &str = req.header("Authorization")?.as_str().strip_prefix("Bearer ")?;
+        let claims: Claims = self.sessions.verify(token).ok()?;
+        self.account(&claims.sub).map(|account| format!("{}@{}", account.name, claims.device))
+    }
+
+    // Middleware letting through the requests of the given roles only
+    pub fn allow(&self, roles: &'static [Role]) -> Authorize {
+        Authorize { accounts: self.clone(), roles }
+    }
+}
+
+// Checks the bearer token or X-Auth-Username/X-Auth-Password headers of a route against the roles
+// allowed on it, answering 401 without valid credentials and 403 for the other roles. The `Identity`
+// of the caller is left in the request extensions for the handlers
+pub struct Authorize {
+    accounts: Accounts,
+    roles: &'static [Role],
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Authorize {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        match self.accounts.identify(&req).await {
+            Ok(identity) if self.roles.contains(&identity.role) => {
+                req.set_ext(identity);
+                Ok(next.run(req).await)
+            }
+            Ok(identity) => ServerError::Forbidden(format!("Forbidden! {} accounts are not allowed to {} {}", identity.role, req.method(), req.url().path())).to_response(),
+            Err(err) => ServerError::Unauthorized(format!("Un-authorized request to {} {}: {}", req.method(), req.url().path(), err)).to_response(),
+        }
+    }
+}
+
+// Hash for the `password_hash` field of an account, salted at random
+pub fn hash_password(password: &str) -> Result<String, ServerError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(digest(password).as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| ServerError::Validation(format!("Password hashing failed: {}", err)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::{Args, Env, Settings};
+
+    fn accounts() -> Accounts {
+        Accounts {
+            accounts: Arc::new(vec![
+                Account { name: "staff".to_string(), username: digest("staff"), password_hash: hash_password("secret").unwrap(), role: Role::Staff },
+                Account { name: "cook".to_string(), username: digest("cook"), password_hash: hash_password("secret").unwrap(), role: Role::Kitchen },
+            ]),
+            sessions: Sessions::new(&Settings::new().auth),
+        }
+    }
+
+    fn login(username: &str, password: &str, device: &str) -> Login {
+        Login { username: digest(username), password: digest(password), device: device.to_string() }
+    }
+
+    #[test]
+    fn test_auth_accounts_new_given_config_provided_when_init_then_accounts_loaded() {
+        let args: Args = Args { profile: Some("test".to_string()), ..Args::default() };
+        let accounts: Accounts = Accounts::new(&Settings::load(&args, &Env::new()).unwrap().auth).unwrap();
+        assert_eq!(Some(Role::Manager), accounts.verify(&digest("manager"), &digest("paidy")).map(|account| account.role));
+    }
+
+    #[test]
+    fn test_auth_accounts_new_given_production_profile_when_init_then_no_account_shipped() {
+        let args: Args = Args { profile: Some("production".to_string()), ..Args::default() };
+        assert!(Accounts::new(&Settings::load(&args, &Env::new()).unwrap().auth).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_auth_verify_given_matching_digests_when_verified_then_role_returned() {
+        let accounts: Accounts = accounts();
+        assert_eq!(Some(Role::Staff), accounts.verify(&digest("staff"), &digest("secret")).map(|account| account.role));
+        assert_eq!(Some(Role::Kitchen), accounts.verify(&digest("cook"), &digest("secret")).map(|account| account.role));
+    }
+
+    #[test]
+    fn test_auth_verify_given_wrong_password_or_unknown_user_when_verified_then_none_returned() {
+        let accounts: Accounts = accounts();
+        assert!(accounts.verify(&digest("staff"), &digest("paidy")).is_none());
+        assert!(accounts.verify(&digest("staff"), "secret").is_none());
+        assert!(accounts.verify(&digest("guest"), &digest("secret")).is_none());
+    }
+
+    #[test]
+    fn test_auth_hash_password_given_same_password_when_hashed_twice_then_salts_differ() {
+        let first: String = hash_password("secret").unwrap();
+        let second: String = hash_password("secret").unwrap();
+        assert_ne!(first, second);
+        assert!(first.starts_with("$argon2id$"));
+    }
+
+    #[test]
+    fn test_auth_authorize_given_route_for_staff_when_requested_then_401_403_or_200_answered() {
+        let mut app = tide::new();
+        app.at("/").with(accounts().allow(&[Role::Staff])).get(|_| async { Ok("served") });
+        let call = |credentials: Option<(&str, &str)>| {
+            let mut req = tide::http::Request::new(tide::http::Method::Get, "http://localhost/");
+            if let Some((username, password)) = credentials {
+                req.insert_header("X-Auth-Username", digest(username));
+                req.insert_header("X-Auth-Password", digest(password));
+            }
+            let res: tide::http::Response = async_std::task::block_on(app.respond(req)).unwrap();
+            res.status()
+        };
+
+        assert_eq!(tide::StatusCode::Unauthorized, call(None));
+        assert_eq!(tide::StatusCode::Unauthorized, call(Some(("staff", "paidy"))));
+        assert_eq!(tide::StatusCode::Forbidden, call(Some(("cook", "secret"))));
+        assert_eq!(tide::StatusCode::Ok, call(Some(("staff", "secret"))));
+    }
+
+    #[test]
+    fn test_auth_login_given_account_and_device_when_logged_in_then_token_names_both() {
+        let accounts: Accounts = accounts();
+        let token: Token = task::block_on(accounts.login(&login("staff", "secret", "tablet-1"))).unwrap();
+        let claims: Claims = accounts.sessions.verify(&token.token).unwrap();
+        assert_eq!("staff", claims.sub);
+        assert_eq!(Role::Staff, claims.role);
+        assert_eq!("tablet-1", claims.device);
+
+        assert!(matches!(task::block_on(accounts.login(&login("staff", "paidy", "tablet-1"))), Err(ServerError::Unauthorized(_))));
+        assert!(matches!(task::block_on(accounts.login(&login("guest", "secret", "tablet-1"))), Err(ServerError::Unauthorized(_))));
+        assert!(matches!(task::block_on(accounts.login(&login("staff", "secret", " "))), Err(ServerError::Validation(_))));
+    }
+
+    #[test]
+    fn test_auth_refresh_and_logout_given_token_when_invoked_then_previous_token_revoked() {
+        let accounts: Accounts = accounts();
+        let first: Token = task::block_on(accounts.login(&login("staff", "secret", "tablet-1"))).unwrap();
+        let second: Token = accounts.refresh(&accounts.sessions.verify(&first.token).unwrap()).unwrap();
+        assert!(accounts.sessions.verify(&first.token).is_err());
+
+        let claims: Claims = accounts.sessions.verify(&second.token).unwrap();
+        assert_eq!("tablet-1", claims.device);
+        accounts.logout(&claims);
+        assert!(accounts.sessions.verify(&second.token).is_err());
+    }
+
+    #[test]
+    fn test_auth_authorize_given_bearer_token_when_requested_then_identity_passed_on() {
+        let accounts: Accounts = accounts();
+        let mut app = tide::new();
+        app.at("/")
+            .with(accounts.allow(&[Role::Staff]))
+            .get(|req: Request<()>| async move { Ok(req.ext::<Identity>().unwrap().name.clone()) });
+        let call = |authorization: &str| {
+            let mut req = tide::http::Request::new(tide::http::Method::Get, "http://localhost/");
+            req.insert_header("Authorization", authorization);
+            let mut res: tide::http::Response = async_std::task::block_on(app.respond(req)).unwrap();
+            (res.status(), async_std::task::block_on(res.body_string()).unwrap())
+        };
+        let staff: Token = task::block_on(accounts.login(&login("staff", "secret", "tablet-1"))).unwrap();
+        let cook: Token = task::block_on(accounts.login(&login("cook", "secret", "pass-1"))).unwrap();
+
+        assert_eq!((tide::StatusCode::Ok, "staff".to_string()), call(&format!("Bearer {}", staff.token)));
+        assert_eq!(tide::StatusCode::Forbidden, call(&format!("Bearer {}", cook.token)).0);
+        assert_eq!(tide::StatusCode::Unauthorized, call(&format!("Basic {}", staff.token)).0);
+        accounts.logout(&accounts.sessions.verify(&staff.token).unwrap());
+        assert_eq!(tide::StatusCode::Unauthorized, call(&format!("Bearer {}", staff.token)).0);
+    }
+
+    #[test]
+    fn test_auth_authorize_given_account_removed_or_demoted_when_token_used_then_current_role_applied() {
+        let accounts: Accounts = accounts();
+        let token: Token = task::block_on(accounts.login(&login("staff", "secret", "tablet-1"))).unwrap();
+        let call = |accounts: Accounts| {
+            let mut app = tide::new();
+            app.at("/").with(accounts.allow(&[Role::Staff])).get(|_| async { Ok("served") });
+            let mut req = tide::http::Request::new(tide::http::Method::Get, "http://localhost/");
+            req.insert_header("Authorization", format!("Bearer {}", token.token));
+            let res: tide::http::Response = task::block_on(app.respond(req)).unwrap();
+            res.status()
+        };
+        let with = |role: Option<Role>| Accounts {
+            accounts: Arc::new(
+                role.map(|role| Account { name: "staff".to_string(), username: digest("staff"), password_hash: DUMMY_HASH.to_string(), role })
+                    .into_iter()
+                    .collect(),
+            ),
+            sessions: accounts.sessions.clone(),
+        };
+
+        assert_eq!(tide::StatusCode::Ok, call(with(Some(Role::Staff))));
+        assert_eq!(tide::StatusCode::Forbidden, call(with(Some(Role::Display))));
+        assert_eq!(tide::StatusCode::Unauthorized, call(with(None)));
+    }
+}
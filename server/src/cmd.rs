@@ -1,500 +1,713 @@
-use crate::db::DB;
-use crate::order_type::{DeleteOrder, ItemPair, ItemStatus, PlaceOrder, TableStatus, UpdateOrder};
-use crate::settings::Settings;
+use crate::db::{self, PoolStats, DB};
+use crate::error::ServerError;
+use crate::kitchen::Kitchen;
+use crate::menu;
+use crate::migration;
+use crate::order_type::{
+    Actor, DeleteOrder, History, HistoryEntry, Item, ItemPair, ItemStatus, MenuItem, Order, PlaceOrder,
+    Report, StatusCount, TableSession, TableStatus, UpdateOrder,
+};
+use crate::settings::{self, Settings};
 use chrono::{DateTime, Utc};
-use postgres::{Client, Error, NoTls};
-use rand::Rng;
-use std::thread;
-use std::time::{Duration, Instant};
-
+use native_tls::TlsConnector;
+use postgres::config::SslMode;
+use postgres::error::SqlState;
+use postgres::{Config, Error, GenericClient};
+use postgres_native_tls::MakeTlsConnector;
+use r2d2::{Pool, PooledConnection};
+use r2d2_postgres::PostgresConnectionManager;
+use std::fs;
+use std::time::Duration;
+use tide::log::info;
+
+type Manager = PostgresConnectionManager<MakeTlsConnector>;
+
+#[derive(Clone)]
 pub struct Dbio {
-    name: String,
+    pool: Pool<Manager>,
+    kitchen: Kitchen,
 }
 
 impl Dbio {
-    pub fn new() -> Dbio {
-        let mut db_url: String = "".to_string();
-        let config: Settings = Settings::new();
-        db_url.push_str(&config.database.get_prefix());
-        db_url.push_str(":");
-        db_url.push_str(&config.database.get_password());
-        db_url.push_str("@");
-        db_url.push_str(&config.database.get_ip());
-        db_url.push_str(":");
-        db_url.push_str(&config.database.get_port());
-        db_url.push_str("/");
-        db_url.push_str(&config.database.get_db_name());
-        Dbio { name: db_url }
+    pub fn new(config: &Settings, kitchen: Kitchen) -> Result<Dbio, ServerError> {
+        let manager = Manager::new(connection(&config.database)?, tls(&config.database)?);
+        // connections are opened lazily, init() reports an unreachable database
+        let pool = Pool::builder()
+            .max_size(config.database.get_pool_max_size())
+            .min_idle(Some(config.database.get_pool_min_idle()))
+            .connection_timeout(Duration::from_secs(config.database.get_pool_connection_timeout()))
+            .idle_timeout(Some(Duration::from_secs(config.database.get_pool_idle_timeout())))
+            .build_unchecked(manager);
+
+        Ok(Dbio { pool, kitchen })
+    }
+
+    fn client(&self) -> Result<PooledConnection<Manager>, ServerError> {
+        Ok(self.pool.get()?)
+    }
+}
+
+// Like libpq, only verify-full checks the certificate and host name of the server, prefer/require only encrypt
+fn tls(config: &settings::Database) -> Result<MakeTlsConnector, ServerError> {
+    let verify: bool = config.get_sslmode() == "verify-full";
+    let connector: TlsConnector = TlsConnector::builder()
+        .danger_accept_invalid_certs(!verify)
+        .danger_accept_invalid_hostnames(!verify)
+        .build()
+        .map_err(|err| ServerError::Storage(format!("TLS connector Error: {}", err)))?;
+    Ok(MakeTlsConnector::new(connector))
+}
+
+// Each field of `[database]` is handed to the driver on its own, so no character of the password needs escaping
+fn connection(config: &settings::Database) -> Result<Config, ServerError> {
+    let port: u16 = config
+        .get_port()
+        .parse()
+        .map_err(|_| ServerError::Validation(format!("Invalid database port: {}", config.get_port())))?;
+    let ssl_mode: SslMode = match config.get_sslmode().as_str() {
+        "disable" => SslMode::Disable,
+        "prefer" => SslMode::Prefer,
+        "require" | "verify-full" => SslMode::Require,
+        other => return Err(ServerError::Validation(format!("Unknown sslmode: {}, expected disable, prefer, require or verify-full", other))),
+    };
+
+    let mut pg_config: Config = Config::new();
+    pg_config
+        .user(&config.get_user())
+        .host(&config.get_ip())
+        .port(port)
+        .dbname(&config.get_db_name())
+        .ssl_mode(ssl_mode)
+        .application_name(&config.get_application_name());
+    if config.get_connect_timeout() > 0 {
+        pg_config.connect_timeout(Duration::from_secs(config.get_connect_timeout()));
+    }
+    if let Some(password) = password(config)? {
+        pg_config.password(password);
     }
+    Ok(pg_config)
+}
 
-    pub fn get_db_path(&self) -> &str {
-        &self.name
+// `password_file` wins over `password_env`, which wins over the plain `password`, left empty for trust or peer auth
+fn password(config: &settings::Database) -> Result<Option<String>, ServerError> {
+    let path: String = config.get_password_file();
+    if !path.is_empty() {
+        let secret: String = fs::read_to_string(&path).map_err(|err| ServerError::Validation(format!("Password file {}: {}", path, err)))?;
+        return Ok(Some(secret.trim_end_matches(['\n', '\r']).to_string()));
+    }
+    let name: String = config.get_password_env();
+    if !name.is_empty() {
+        let secret: String = config.get_password_from_env().ok_or_else(|| ServerError::Validation(format!("Password variable {} is not set", name)))?;
+        return Ok(Some(secret));
     }
+    Ok(Some(config.get_password()).filter(|password| !password.is_empty()))
 }
 
 impl DB for Dbio {
-    fn init(&self) -> Result<(), Error> {
-        let mut client = Client::connect(self.get_db_path(), NoTls)?;
-
-        match client.query_one(
-            "SELECT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'tablestatus')",
-            &[],
-        ) {
-            Ok(row) => {
-                let exists: bool = row.get(0);
-                if exists == false {
-                    client.batch_execute(
-                        "CREATE TYPE TABLESTATUS AS ENUM (
-                            'Open',
-                            'Close'
-                        )",
-                    )?;
-                }
+    fn init(&self) -> Result<(), ServerError> {
+        self.migrate().map(|_| ())
+    }
+
+    fn migrate(&self) -> Result<Vec<String>, ServerError> {
+        let mut client = self.client()?;
+        let applied = migration::migrate(&mut client)?;
+        info!("[MIGRATE] Schema at version {}", migration::current_version(&mut *client)?);
+
+        Ok(applied
+            .iter()
+            .map(|migration| format!("{:04}_{}", migration.version, migration.name))
+            .collect())
+    }
+
+    fn pending_migrations(&self) -> Result<Vec<String>, ServerError> {
+        let mut client = self.client()?;
+
+        Ok(migration::pending(&mut *client)?
+            .iter()
+            .map(|migration| format!("{:04}_{}", migration.version, migration.name))
+            .collect())
+    }
+
+    fn place(&self, order: PlaceOrder, by: &Actor) -> Result<Order, ServerError> {
+        let mut client = self.client()?;
+        let mut tx = client.transaction()?;
+
+        menu::check_order(&order.items, |name| Ok(on_menu(&mut tx, name)?))?;
+        if let Some((_, session)) = latest_session(&mut tx, &order.table_id, true)? {
+            if session.status == TableStatus::Open {
+                return Err(ServerError::Conflict(format!("Duplicated! There has been an order of table_id: {}, being served in the queue", session.table_id)));
             }
-            _ => {}
         }
 
-        match client.query_one(
-            "SELECT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'itemstatus')",
-            &[],
-        ) {
-            Ok(row) => {
-                let exists: bool = row.get(0);
-                if exists == false {
-                    client.batch_execute(
-                        "CREATE TYPE ITEMSTATUS AS ENUM (
-                            'New',
-                            'Process',
-                            'Done',
-                            'Deleted'
-                        )",
-                    )?;
-                }
+        // insert new order into table 'tablet', a concurrent place on the same table hits index 'tablet_open_session'
+        let session_id: i64 = match tx.query_one("INSERT INTO tablet(opened_at, table_id, status, staff, device) VALUES ($1, $2, $3, $4, $5) RETURNING session_id",
+                                                 &[&order.created_at, &order.table_id, &TableStatus::Open, &by.staff, &by.device]) {
+            Ok(row) => row.get("session_id"),
+            Err(err) if err.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+                return Err(ServerError::Conflict(format!("Duplicated! There has been an order of table_id: {}, being served in the queue", order.table_id)));
             }
-            _ => {}
+            Err(err) => return Err(err.into()),
+        };
+        // insert new items into table items/item_history
+        let mut items: Vec<Item> = Vec::new();
+        for elem in &order.items {
+            queue_item(&mut tx, session_id, order.created_at, order.created_at, &order.table_id, elem, by)?;
+            items.push(Item { name: elem.name.clone(), amount: elem.amount, status: ItemStatus::New });
+        }
+        tx.commit()?;
+
+        for elem in order.items {
+            self.kitchen.enqueue(session_id, &order.table_id, &elem.name);
         }
 
-        client.batch_execute(
-            "
-            CREATE TABLE IF NOT EXISTS TABLET (
-                opened_at TIMESTAMPTZ,
-                closed_at TIMESTAMPTZ,
-                table_id VARCHAR,
-                status TABLESTATUS
-            );
-
-            CREATE TABLE IF NOT EXISTS ITEMS (
-                created_at TIMESTAMPTZ,
-                updated_at TIMESTAMPTZ,
-                table_id VARCHAR,
-                item VARCHAR,
-                amount INTEGER,
-                status ITEMSTATUS
-            );
-
-            CREATE TABLE IF NOT EXISTS ITEM_HISTORY (
-                created_at TIMESTAMPTZ,
-                updated_at TIMESTAMPTZ,
-                table_id VARCHAR,
-                item VARCHAR,
-                amount INTEGER,
-                status ITEMSTATUS
-            );
-            ",
-        )?;
-
-        Ok(())
+        Ok(Order {
+            queried_at: Utc::now(),
+            table: TableSession {
+                table_id: order.table_id,
+                opened_at: order.created_at,
+                closed_at: None,
+                status: TableStatus::Open,
+                staff: Some(by.staff.clone()),
+                device: by.device.clone(),
+            },
+            items,
+        })
     }
-  
-    fn place(&self, order: PlaceOrder) -> Result<String, Error> {
-        let mut client = Client::connect(self.get_db_path(), NoTls)?;
-        let mut res: String = "".to_string();
-
-        match client.query_one("SELECT table_id, status
-                                FROM tablet WHERE table_id = $1 AND opened_at = (SELECT MAX(opened_at)
-                                                                                 FROM tablet
-                                                                                 WHERE table_id = $1) FOR UPDATE", &[&order.table_id]) {
-            Ok(row) => {
-                let table_id: String = row.get(0);
-                let status: TableStatus = row.get(1);
-
-                match status {
-                    TableStatus::Open => res = format!("Duplicated! There has been an order of table_id: {}, being served in the queue", table_id),
-                    TableStatus::Close => {
-                        res = format!("New Order! opened_at: {}, table_id: {}", order.created_at, order.table_id);
-                        // insert new order into table 'tablet'
-                        client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES ($1, $2, $3)", &[&order.created_at, &order.table_id, &TableStatus::Open])?;
-                        // insert new items into table items/item_history
-                        let mut rng = rand::thread_rng();
-                        for elem in order.items {
-                            let (ts, table_id, item, amount, cook_time) = (order.created_at, order.table_id.to_string(), elem.name, elem.amount, rng.gen_range(5..16));
-                            client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES ($1, $1, $2, $3, $4, 'Process'::itemstatus)",
-                                            &[&ts, &table_id, &item, &amount])?;
-                            client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES ($1, $1, $2, $3, $4, 'New'::itemstatus)",
-                                            &[&ts, &table_id, &item, &amount])?;
-                            client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES ($1, $1, $2, $3, $4, 'Process'::itemstatus)",
-                                            &[&ts, &table_id, &item, &amount])?;
-                            // spawn a task handling item preparation
-                            let elm = ItemPair{ name: item, amount: amount };
-                            thread::spawn(move || cook_order_item(ts.to_string(), table_id, elm, cook_time));
-                        }                       
-                    }
+
+    fn update(&self, order: UpdateOrder, by: &Actor) -> Result<Order, ServerError> {
+        let mut client = self.client()?;
+        let mut tx = client.transaction()?;
+
+        menu::check_order(&order.items, |name| Ok(on_menu(&mut tx, name)?))?;
+        let (session_id, session) = match latest_session(&mut tx, &order.table_id, true)? {
+            Some(session) => session,
+            None => {
+                return Err(ServerError::NotFound(format!("Update Order Failed! Non-existent table_id: {}", order.table_id)))
+            }
+        };
+        if session.status == TableStatus::Close {
+            return Err(ServerError::InvalidState(format!("Update Order Failed! table_id: {} was done. Please launch a new order", session.table_id)));
+        }
+
+        let mut queued: Vec<ItemPair> = Vec::new();
+        for elem in order.items {
+            let row = tx.query_opt("SELECT status FROM items WHERE session_id = $1 AND item = $2 FOR UPDATE", &[&session_id, &elem.name])?;
+            match row.map(|row| row.get::<_, ItemStatus>("status")) {
+                // only items still in 'New' state are allowed to be updated
+                Some(ItemStatus::New) => {
+                    tx.execute("UPDATE items SET updated_at = $1, amount = $2 WHERE session_id = $3 AND item = $4",
+                               &[&order.updated_at, &elem.amount, &session_id, &elem.name])?;
+                    tx.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device) VALUES ($1, $2, $3, $4, $5, $6, 'New'::itemstatus, $7, $8)",
+                               &[&session_id, &session.opened_at, &order.updated_at, &session.table_id, &elem.name, &elem.amount, &by.staff, &by.device])?;
                 }
-            },
-            Err(_err) => {
-                res = format!("New Order! opened_at_at: {}, table_id: {}", order.created_at, order.table_id);
-                // insert new order into table 'tablet'
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES ($1, $2, $3)", &[&order.created_at, &order.table_id, &TableStatus::Open])?;
-                // insert new items into table items/item_history
-                let mut rng = rand::thread_rng();
-                for elem in order.items {
-                    let (ts, table_id, item, amount, cook_time) = (order.created_at, order.table_id.to_string(), elem.name, elem.amount, rng.gen_range(5..16));
-                    client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES ($1, $1, $2, $3, $4, 'Process'::itemstatus)",
-                                    &[&ts, &table_id, &item, &amount])?;
-                    client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES ($1, $1, $2, $3, $4, 'New'::itemstatus)",
-                                    &[&ts, &table_id, &item, &amount])?;
-                    client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES ($1, $1, $2, $3, $4, 'Process'::itemstatus)",
-                                    &[&ts, &table_id, &item, &amount])?;
-                    // spawn a task handling item preparation
-                    let elm = ItemPair{ name: item, amount: amount };
-                    thread::spawn(move || cook_order_item(ts.to_string(), table_id, elm, cook_time));
+                Some(_) => {}
+                None => {
+                    queue_item(&mut tx, session_id, session.opened_at, order.updated_at, &session.table_id, &elem, by)?;
+                    queued.push(elem);
                 }
             }
         }
 
-        Ok(res.into())
+        let items = session_items(&mut tx, session_id, None, true)?;
+        tx.commit()?;
+
+        for elem in queued {
+            self.kitchen.enqueue(session_id, &session.table_id, &elem.name);
+        }
+        Ok(Order { queried_at: Utc::now(), table: session, items })
     }
-  
-    fn update(&self, order: UpdateOrder) -> Result<String, Error> {
-        let mut client = Client::connect(self.get_db_path(), NoTls).unwrap();
-        let mut res: String = "".to_string();
-
-        match client.query_one("SELECT opened_at, table_id, status
-                                FROM tablet
-                                WHERE table_id = $1 AND opened_at = (SELECT MAX(opened_at)
-                                                                     FROM tablet
-                                                                     WHERE table_id = $1) FOR UPDATE", &[&order.table_id]) {
-                Ok(row) => {
-                    let opened_at: DateTime<Utc> = row.get("opened_at");
-                    let table_id: String = row.get("table_id");
-                    let status: TableStatus = row.get("status");
-                    
-                    match status {
-                        TableStatus::Open => {
-                            for elem in order.items {
-                                let id = table_id.clone();
-                                let updated_at = order.updated_at.to_string();
-                                thread::spawn(move || update_order_item(opened_at.to_string(), updated_at, id, elem));
-                            }
-                            res = format!("Update Order Successed! table_id: {}", table_id);
-                        },
-                        TableStatus::Close => res = format!("Update Order Failed! table_id: {} was done. Please launch a new order", table_id)
-                    }
-                },
-                Err(_err) => {
-                    res = format!("Update Order Failed! Non-existent table_id: {}", order.table_id);
-                }
-            };
 
-        Ok(res.into())
+    fn delete(&self, order: DeleteOrder, by: &Actor) -> Result<Item, ServerError> {
+        let mut client = self.client()?;
+        let mut tx = client.transaction()?;
+
+        // lock the session first, the same order place/update and the kitchen take
+        let session = latest_session(&mut tx, &order.table_id, true)?;
+        let row = match session {
+            Some((session_id, _)) => tx.query_opt("SELECT session_id, created_at, table_id, item, amount, status
+                                                FROM items
+                                                WHERE session_id = $1 AND item = $2 FOR UPDATE", &[&session_id, &order.item])?,
+            None => None,
+        };
+        let row = match row {
+            Some(row) => row,
+            None => {
+                return Err(ServerError::NotFound(format!("Delete Order Failed! No item: {} of table_id: {}", order.item, order.table_id)))
+            }
+        };
+
+        let session_id: i64 = row.get("session_id");
+        let created_at: DateTime<Utc> = row.get("created_at");
+        let table_id: String = row.get("table_id");
+        let item: String = row.get("item");
+        let amount: i32 = row.get("amount");
+        let status: ItemStatus = row.get("status");
+
+        match status {
+            ItemStatus::New => {
+                tx.execute("DELETE FROM items WHERE session_id = $1 AND item = $2", &[&session_id, &item])?;
+                tx.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device) VALUES ($1, $2, $3, $4, $5, $6, 'Deleted'::itemstatus, $7, $8)",
+                           &[&session_id, &created_at, &order.deleted_at, &table_id, &item, &amount, &by.staff, &by.device])?;
+                update_table_status(&mut tx, session_id)?;
+                tx.commit()?;
+                Ok(Item { name: item, amount, status: ItemStatus::Deleted })
+            }
+            ItemStatus::Process => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} is cooking", item, table_id))),
+            ItemStatus::Done => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was done", item, table_id))),
+            ItemStatus::Deleted => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was deleted", item, table_id))),
+            ItemStatus::Rejected => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was rejected by the kitchen", item, table_id))),
+        }
     }
 
-    fn delete(&self, order: DeleteOrder) -> Result<String, Error> {
-        let mut client = Client::connect(self.get_db_path(), NoTls).unwrap();
-        let mut res: String = "".to_string();
-        match client.query_one("SELECT created_at, table_id, item, amount, status
-                                FROM items
-                                WHERE table_id = $1 AND item = $2 and created_at = ( SELECT MAX(opened_at)
-                                                                                     FROM tablet
-                                                                                     WHERE table_id = $1 ) FOR UPDATE", &[&order.table_id, &order.item]){
-                Ok(row) => {
-                    let created_at: DateTime<Utc> = row.get("created_at");
-                    let table_id: String = row.get("table_id");
-                    let item: String = row.get("item");
-                    let amount: i32 = row.get("amount");
-                    let status: ItemStatus = row.get("status");
-
-                    match status {
-                        ItemStatus::New => {
-                            match client.execute("DELETE FROM items
-                                                  WHERE table_id = $1 AND 
-                                                        item = $2 AND 
-                                                        status = 'New'::itemstatus AND 
-                                                        created_at = (SELECT MAX(opened_at)
-                                                                      FROM tablet
-                                                                      WHERE table_id = $1)", &[&order.table_id, &order.item]) {
-                                Ok(_n) => {
-                                    client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status)
-                                                    VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, 'Deleted'::itemstatus)", 
-                                                    &[&created_at.to_string(), &order.deleted_at.to_string(), &table_id, &item, &amount]).unwrap();
-                                    res = format!("Delete Order Successed! item: {} of table_id: {} deleted", order.item, order.table_id);
-                                    update_table_status(client, table_id, created_at.to_string())?;
-                                },
-                                Err(_err) => {
-                                    res = format!("Delete Order Failed! No item: {} of table_id: {}", order.item, order.table_id);
-                                }
-                            }
-                        },
-                        ItemStatus::Process => res = format!("Delete Order Failed! The item: {} of table_id: {} is cooking", item, table_id),
-                        ItemStatus::Done => res = format!("Delete Order Failed! The item: {} of table_id: {} was done", item, table_id),
-                        ItemStatus::Deleted => res = format!("Delete Order Failed! The item: {} of table_id: {} was deleted", item, table_id),
-                    }
-                },
-                Err(_err) => {
-                    res = format!("Delete Order Failed! No item: {} of table_id: {}", order.item, order.table_id);
+    fn void_item(&self, order: DeleteOrder, by: &Actor) -> Result<Item, ServerError> {
+        let mut client = self.client()?;
+        let mut tx = client.transaction()?;
+
+        // lock the session first, the same order place/update/delete take
+        let row = match latest_session(&mut tx, &order.table_id, true)? {
+            Some((session_id, _)) => tx.query_opt("SELECT session_id, created_at, amount, status
+                                                FROM items
+                                                WHERE session_id = $1 AND item = $2 FOR UPDATE", &[&session_id, &order.item])?,
+            None => None,
+        };
+        let row = match row {
+            Some(row) => row,
+            None => {
+                return Err(ServerError::NotFound(format!("Void Order Failed! No item: {} of table_id: {}", order.item, order.table_id)))
+            }
+        };
+
+        let session_id: i64 = row.get("session_id");
+        let created_at: DateTime<Utc> = row.get("created_at");
+        let amount: i32 = row.get("amount");
+        match row.get::<_, ItemStatus>("status") {
+            ItemStatus::Done => {
+                tx.execute("DELETE FROM items WHERE session_id = $1 AND item = $2", &[&session_id, &order.item])?;
+                tx.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device) VALUES ($1, $2, $3, $4, $5, $6, 'Deleted'::itemstatus, $7, $8)",
+                           &[&session_id, &created_at, &order.deleted_at, &order.table_id, &order.item, &amount, &by.staff, &by.device])?;
+                tx.commit()?;
+                Ok(Item { name: order.item, amount, status: ItemStatus::Deleted })
+            }
+            status => Err(ServerError::InvalidState(format!("Void Order Failed! The item: {} of table_id: {} is {}, only served items are voided", order.item, order.table_id, status))),
+        }
+    }
+
+    fn query_by_tableid(&self, table_id: String) -> Result<Order, ServerError> {
+        let mut client = self.client()?;
+
+        match latest_session(&mut *client, &table_id, false)? {
+            Some((session_id, session)) => {
+                let items = session_items(&mut *client, session_id, None, false)?;
+                Ok(Order { queried_at: Utc::now(), table: session, items })
+            }
+            None => Err(ServerError::NotFound(format!("No Order of table id: {}", table_id))),
+        }
+    }
+
+    fn query_by_tableid_and_item(&self, table_id: String, item: String) -> Result<Order, ServerError> {
+        let mut client = self.client()?;
+
+        match latest_session(&mut *client, &table_id, false)? {
+            Some((session_id, session)) => {
+                let items = session_items(&mut *client, session_id, Some(&item), false)?;
+                if items.is_empty() {
+                    Err(ServerError::NotFound(format!("No item: {} of table id: {}", item, table_id)))
+                } else {
+                    Ok(Order { queried_at: Utc::now(), table: session, items })
                 }
-            };
+            }
+            None => Err(ServerError::NotFound(format!("No Order of table id: {}", table_id))),
+        }
+    }
+
+    fn advance_item(&self, table_id: &str, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError> {
+        let mut client = self.client()?;
+        let mut tx = client.transaction()?;
 
-        Ok(res.into())
+        // lock the session first, the same order place/update/delete take
+        let advanced: bool = match latest_session(&mut tx, table_id, true)? {
+            Some((session_id, _)) => advance_item(&mut tx, session_id, item, to, by)?,
+            None => false,
+        };
+        tx.commit()?;
+        Ok(advanced)
     }
 
-    fn query_by_tableid(&self, table_id: String) -> Result<String, Error> {
-        let mut client = Client::connect(self.get_db_path(), NoTls).unwrap();
-        let mut res = "".to_owned();
-        let ts: DateTime<Utc> = Utc::now();
-        let mut empty: bool = true;
-        res.push_str("{ queried_at: ");
-        res.push_str(&ts.to_string());
-        res.push_str(", table_id: ");
-        res.push_str(&table_id);
-        res.push_str(", items: [ ");
-        for row in client.query("SELECT item, amount, status
-                                 FROM items
-                                 WHERE table_id = $1 AND created_at = (SELECT MAX(opened_at)
-                                                                       FROM tablet
-                                                                       WHERE table_id = $2) FOR UPDATE", &[&table_id, &table_id]).unwrap() {
-            empty = false;
-            let item: String = row.get(0);
-            let amount: i32 = row.get(1);
-            let status: ItemStatus = row.get(2);
-
-            res.push_str("{ item: ");
-            res.push_str(&item);
-            res.push_str(", amount: ");
-            res.push_str(&amount.to_string());
-            res.push_str(", status: ");
-            res.push_str(&status.to_string());
-            res.push_str(" },");
+    fn advance_session_item(&self, session_id: i64, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError> {
+        let mut client = self.client()?;
+        let mut tx = client.transaction()?;
+
+        let advanced: bool = match tx.query_opt("SELECT session_id FROM tablet WHERE session_id = $1 FOR UPDATE", &[&session_id])? {
+            Some(_) => advance_item(&mut tx, session_id, item, to, by)?,
+            None => false,
+        };
+        tx.commit()?;
+        Ok(advanced)
+    }
+
+    fn open_orders(&self) -> Result<Vec<(i64, Order)>, ServerError> {
+        let mut client = self.client()?;
+
+        let mut orders: Vec<(i64, Order)> = Vec::new();
+        for row in client.query("SELECT session_id, opened_at, closed_at, table_id, status, staff, device FROM tablet WHERE status = $1 ORDER BY opened_at", &[&TableStatus::Open])? {
+            let session_id: i64 = row.get("session_id");
+            let table = session_row(&row);
+            let items = session_items(&mut *client, session_id, None, false)?;
+            orders.push((session_id, Order { queried_at: Utc::now(), table, items }));
+        }
+        Ok(orders)
+    }
+
+    fn history(&self, table_id: &str, item: Option<&str>) -> Result<History, ServerError> {
+        let mut client = self.client()?;
+
+        let rows = client.query("SELECT created_at, updated_at, item, amount, status, staff, device
+                                 FROM item_history
+                                 WHERE table_id = $1 AND ($2::VARCHAR IS NULL OR item = $2)
+                                 ORDER BY history_id", &[&table_id, &item])?;
+        if rows.is_empty() {
+            return Err(ServerError::NotFound(format!("No history of table id: {}", table_id)));
         }
+        let entries = rows
+            .iter()
+            .map(|row| HistoryEntry {
+                opened_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                item: row.get("item"),
+                amount: row.get("amount"),
+                status: row.get("status"),
+                staff: row.get("staff"),
+                device: row.get("device"),
+            })
+            .collect();
+        Ok(History { queried_at: Utc::now(), table_id: table_id.to_string(), entries })
+    }
+
+    fn menu(&self) -> Result<Vec<MenuItem>, ServerError> {
+        let mut client = self.client()?;
+
+        let rows = client.query("SELECT name, description, category, cook_time, active FROM menu ORDER BY name", &[])?;
+        Ok(rows.iter().map(menu_row).collect())
+    }
+
+    fn menu_item(&self, name: &str) -> Result<MenuItem, ServerError> {
+        let mut client = self.client()?;
 
-        if empty {
-            res = format!("No Order of table id: {}", table_id);
-        } else {
-            res.push_str("]");
-            res.push_str(" }");
+        match client.query_opt("SELECT name, description, category, cook_time, active FROM menu WHERE name = $1", &[&name])? {
+            Some(row) => Ok(menu_row(&row)),
+            None => Err(ServerError::NotFound(format!("No menu item: {}", name))),
         }
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<MenuItem, ServerError> {
+        menu::check_entry(&item)?;
+        let mut client = self.client()?;
 
-        Ok(res.into())
+        match client.execute("INSERT INTO menu(name, description, category, cook_time, active) VALUES ($1, $2, $3, $4, $5)",
+                             &[&item.name, &item.description, &item.category, &item.cook_time, &item.active]) {
+            Ok(_) => Ok(item),
+            Err(err) if err.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+                Err(ServerError::Conflict(format!("Duplicated! The menu has an item: {} already", item.name)))
+            }
+            Err(err) => Err(err.into()),
+        }
     }
 
-    fn query_by_tableid_and_item(&self, table_id: String, item: String) -> Result<String, Error> {
-        let mut client = Client::connect(self.get_db_path(), NoTls).unwrap();
-        let mut res = "".to_owned();
-        let ts: DateTime<Utc> = Utc::now();
-        let mut empty: bool = true;
-        res.push_str("{ queried_at: ");
-        res.push_str(&ts.to_string());
-        for row in client.query("SELECT table_id, item, amount, status
-                                 FROM items
-                                 WHERE table_id = $1 AND item = $2 AND created_at = (SELECT MAX(opened_at)
-                                                                                     FROM tablet
-                                                                                     WHERE table_id = $1) FOR UPDATE", &[&table_id, &item]).unwrap() {
-            empty = false;
-            let table_id: String = row.get(0);
-            let item: String = row.get(1);
-            let amount: i32 = row.get(2);
-            let status: ItemStatus = row.get(3);
-
-            res.push_str(", table_id: ");
-            res.push_str(&table_id);
-            res.push_str(", item: ");
-            res.push_str(&item);
-            res.push_str(", amount: ");
-            res.push_str(&amount.to_string());
-            res.push_str(", item_status: ");
-            res.push_str(&status.to_string());
+    fn update_menu_item(&self, item: MenuItem) -> Result<MenuItem, ServerError> {
+        menu::check_entry(&item)?;
+        let mut client = self.client()?;
+
+        let updated = client.execute("UPDATE menu SET description = $2, category = $3, cook_time = $4, active = $5 WHERE name = $1",
+                                     &[&item.name, &item.description, &item.category, &item.cook_time, &item.active])?;
+        if updated == 0 {
+            return Err(ServerError::NotFound(format!("No menu item: {}", item.name)));
         }
+        Ok(item)
+    }
+
+    fn delete_menu_item(&self, name: &str) -> Result<MenuItem, ServerError> {
+        let mut client = self.client()?;
 
-        if empty {
-            res = format!("No Order of table id: {}", table_id);
-        } else {
-            res.push_str(" }");
+        match client.query_opt("DELETE FROM menu WHERE name = $1 RETURNING name, description, category, cook_time, active", &[&name])? {
+            Some(row) => Ok(menu_row(&row)),
+            None => Err(ServerError::NotFound(format!("No menu item: {}", name))),
         }
+    }
 
-        Ok(res.into())
+    fn report(&self) -> Result<Report, ServerError> {
+        let mut client = self.client()?;
+
+        let open_tables: i64 = client.query_one("SELECT COUNT(*) FROM tablet WHERE status = $1", &[&TableStatus::Open])?.get(0);
+        let items = client
+            .query("SELECT status, COUNT(*) AS items, SUM(amount)::BIGINT AS amount FROM items GROUP BY status ORDER BY status::VARCHAR", &[])?
+            .iter()
+            .map(|row| StatusCount {
+                status: row.get("status"),
+                items: row.get("items"),
+                amount: row.get("amount"),
+            })
+            .collect();
+        Ok(Report { reported_at: Utc::now(), open_tables, items })
     }
-    
-    fn check_table_status(&self) -> Result<bool, Error> {
-        let mut client = Client::connect(self.get_db_path(), NoTls).unwrap();
+
+    fn cook_times(&self) -> Result<Vec<f64>, ServerError> {
+        let mut client = self.client()?;
+
+        let rows = client
+            .query("SELECT session_id::VARCHAR || '/' || item AS session_item, status, updated_at FROM item_history ORDER BY history_id", &[])?
+            .iter()
+            .map(|row| (row.get("session_item"), row.get("status"), row.get("updated_at")))
+            .collect();
+        Ok(db::cook_times(rows))
+    }
+
+    fn check_table_status(&self) -> Result<bool, ServerError> {
+        let mut client = self.client()?;
         let mut is_empty: bool = true;
 
-        for row in client.query("SELECT * FROM tablet WHERE status = $1 FOR UPDATE", &[&TableStatus::Open])? {
+        for row in client.query("SELECT * FROM tablet WHERE status = $1", &[&TableStatus::Open])? {
             is_empty = false;
             let table_id: String = row.get("table_id");
             let status: TableStatus = row.get("status");
-            println!("[CHECK_TABLE_STATUS] table_id: {} is still being served, status: {}", table_id, status);
+            info!("[CHECK_TABLE_STATUS] table_id: {} is still being served, status: {}", table_id, status);
         }
 
         Ok(is_empty)
     }
-    
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        let state = self.pool.state();
+        Some(PoolStats {
+            max_size: self.pool.max_size(),
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+        })
+    }
+}
+
+// The latest session of a table along with its session id, its row locked with `lock` for the
+// writes, while the queries read it without waiting behind them
+fn latest_session<C: GenericClient>(client: &mut C, table_id: &str, lock: bool) -> Result<Option<(i64, TableSession)>, Error> {
+    let query: String = format!("SELECT session_id, opened_at, closed_at, table_id, status, staff, device
+                                 FROM tablet
+                                 WHERE session_id = (SELECT MAX(session_id)
+                                                     FROM tablet
+                                                     WHERE table_id = $1){}", for_update(lock));
+    let row = client.query_opt(query.as_str(), &[&table_id])?;
+
+    Ok(row.map(|row| (row.get("session_id"), session_row(&row))))
 }
 
-fn update_table_status(mut client: Client, table_id: String, ts: String) -> Result<(), Error> {
-    let mut empty: bool = true;
+fn for_update(lock: bool) -> &'static str {
+    if lock {
+        " FOR UPDATE"
+    } else {
+        ""
+    }
+}
+
+fn session_row(row: &postgres::Row) -> TableSession {
+    TableSession {
+        opened_at: row.get("opened_at"),
+        closed_at: row.get("closed_at"),
+        table_id: row.get("table_id"),
+        status: row.get("status"),
+        staff: row.get("staff"),
+        device: row.get("device"),
+    }
+}
+
+fn session_items<C: GenericClient>(client: &mut C, session_id: i64, item: Option<&str>, lock: bool) -> Result<Vec<Item>, Error> {
+    let query: String = format!("SELECT item, amount, status
+                                 FROM items
+                                 WHERE session_id = $1 AND ($2::VARCHAR IS NULL OR item = $2)
+                                 ORDER BY item_id{}", for_update(lock));
+    let rows = client.query(query.as_str(), &[&session_id, &item])?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Item {
+            name: row.get("item"),
+            amount: row.get("amount"),
+            status: row.get("status"),
+        })
+        .collect())
+}
+
+// Move an item of a session already locked by the caller, answering whether it reached `to`
+fn advance_item<C: GenericClient>(client: &mut C, session_id: i64, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, Error> {
+    let row = client.query_opt("SELECT status FROM items WHERE session_id = $1 AND item = $2 FOR UPDATE", &[&session_id, &item])?;
+    match row.map(|row| row.get::<_, ItemStatus>("status")) {
+        Some(status) if status == to => return Ok(true),
+        Some(status) if status.can_advance(to) => {}
+        _ => return Ok(false),
+    }
+
+    client.execute("UPDATE items SET updated_at = now(), status = $1 WHERE session_id = $2 AND item = $3", &[&to, &session_id, &item])?;
+    client.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device)
+                    SELECT session_id, created_at, updated_at, table_id, item, amount, status, $3, $4
+                    FROM items WHERE session_id = $1 AND item = $2", &[&session_id, &item, &by.staff, &by.device])?;
+    update_table_status(client, session_id)?;
+    Ok(true)
+}
+
+fn update_table_status<C: GenericClient>(client: &mut C, session_id: i64) -> Result<(), Error> {
     let mut open = false;
 
-    for row in client.query(
-        "SELECT status FROM items WHERE table_id = $1 AND created_at = (SELECT MAX(opened_at)
-                                                                        FROM tablet
-                                                                        WHERE table_id = $1) FOR UPDATE",
-        &[&table_id],
-    )? {
-        empty = false;
+    for row in client.query("SELECT status FROM items WHERE session_id = $1 FOR UPDATE", &[&session_id])? {
         let status: ItemStatus = row.get(0);
         match status {
             ItemStatus::New => open = true,
             ItemStatus::Process => open = true,
             ItemStatus::Done => {}
             ItemStatus::Deleted => {}
+            ItemStatus::Rejected => {}
         }
     }
 
-    if empty {
-        client.execute("UPDATE tablet 
-                        SET closed_at = now(),
-                            status = 'Close'::tablestatus
-                        WHERE table_id = $1 AND opened_at = (SELECT MAX(opened_at)
-                                                             FROM tablet
-                                                             WHERE table_id = $1)",
-            &[&table_id],
-        )?;
-        ()
-    }
-
     if open {
-        client.execute(
-            "UPDATE tablet
-             SET status = 'Open'::tablestatus
-             WHERE table_id = $1 and opened_at = (SELECT MAX(opened_at)
-                                                  FROM tablet
-                                                  WHERE table_id = $1)",
-            &[&table_id],
-        )?;
+        client.execute("UPDATE tablet SET status = 'Open'::tablestatus WHERE session_id = $1", &[&session_id])?;
     } else {
-        client.execute(
-            "UPDATE tablet
-             SET closed_at = now(),
-             status = 'Close'::tablestatus
-             WHERE table_id = $1 and opened_at = (SELECT MAX(opened_at)
-                                                  FROM tablet
-                                                  WHERE table_id = $1)",
-            &[&table_id],
-        )?;
+        client.execute("UPDATE tablet SET closed_at = now(), status = 'Close'::tablestatus WHERE session_id = $1", &[&session_id])?;
     }
 
     Ok(())
 }
 
-fn update_order_item(opened_at: String, updated_at: String, table_id: String, elem: ItemPair) {
-    let command: Dbio = Dbio::new();
-    let mut client = Client::connect(command.get_db_path(), NoTls).unwrap();
-    let mut res: String = "".to_string();
-    match client.query_one("SELECT created_at, table_id, item, amount, status
-                            FROM items
-                            WHERE table_id = $1 AND item = $2 AND created_at = (SELECT MAX(opened_at)
-                                                                                FROM tablet
-                                                                                WHERE table_id = $1) FOR UPDATE", &[&table_id, &elem.name]) {
-        Ok(row) => {
-            let status: ItemStatus = row.get("status");
-            let amount: i32 = row.get("amount");
-
-            match status {
-                ItemStatus::New => {
-                    client.execute("UPDATE items
-                                    SET updated_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'),
-                                    amount = $2
-                                    WHERE table_id = $3 AND item = $4 AND created_at = (SELECT MAX(opened_at)
-                                                                                        FROM tablet
-                                                                                        WHERE table_id = $3))", &[&updated_at, &elem.amount, &table_id, &elem.name]).unwrap();
-                    client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, ammount, status)
-                                    VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, $6)", &[&opened_at, &updated_at, &table_id, &elem.name, &elem.amount, &ItemStatus::New]).unwrap();
-                    res = format!("Updat Item Successed! The item: {} of table_id: {} amount: {} -> {}", elem.name, table_id, amount, elem.amount);
-                },
-                ItemStatus::Process => res = format!("Update Item Failed! The item: {} of table_id :{} is cooking", elem.name, table_id),
-                ItemStatus::Done => res = format!("Update Item Failed! The item: {} of table_id: {} was done", elem.name, table_id), 
-                _ => {}
-            }
-        },
-        Err(_err) => {
-            let mut rng = rand::thread_rng();
-            let cook_time: u64 = rng.gen_range(5..16);
-            res = format!("Update Item Successed! New item: {} added to the table_id: {}", elem.name, table_id);
-            // spawn a task handling item preparation
-            std::thread::spawn(move || {
-                let mut duration: Duration;
-                // Start preparing food 
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, 'Process'::itemstatus)", &[&opened_at, &updated_at, &table_id, &elem.name, &elem.amount]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, 'New'::itemstatus)", &[&opened_at, &updated_at, &table_id, &elem.name, &elem.amount]).unwrap();               
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, 'Process'::itemstatus)", &[&opened_at, &updated_at, &table_id, &elem.name, &elem.amount]).unwrap();               
-                let start = Instant::now(); 
-                while start.elapsed().as_secs() < cook_time {}
-                let done_at: String = Utc::now().to_string();
-                let id: String = table_id;
-                update_item_status(opened_at, done_at, id, elem, ItemStatus::Done);
-            });
-        }
+fn menu_row(row: &postgres::Row) -> MenuItem {
+    MenuItem {
+        name: row.get("name"),
+        description: row.get("description"),
+        category: row.get("category"),
+        cook_time: row.get("cook_time"),
+        active: row.get("active"),
     }
-
-    // println!("{}", res);
 }
 
-fn update_item_status(created_at: String, updated_at: String, table_id: String, elem: ItemPair, to: ItemStatus) {
-    let command: Dbio = Dbio::new();
-    let mut client = Client::connect(command.get_db_path(), NoTls).unwrap();
-    match client.execute("UPDATE items
-                          SET updated_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'),
-                              status = $2
-                          WHERE table_id = $3 AND item = $4 AND created_at = (SELECT MAX(opened_at) FROM tablet WHERE table_id = $3)", &[&updated_at, &to, &table_id, &elem.name]) {
-        Ok(_n) => {
-            // println!("[UPDATE_ITEM_STATUS] {} rows modified", n);
-            client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status)
-                            VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, $6)", &[&created_at, &updated_at, &table_id, &elem.name, &elem.amount, &to]).unwrap();
-        },
-        Err(err) => println!("[UPDATE_ITEM_STATUS] Cook Error: {}", err)
-    };
-    match update_table_status(client, table_id, created_at) {
-        Ok(()) => {},
-        Err(_) => {}
-    };
+fn on_menu<C: GenericClient>(client: &mut C, name: &str) -> Result<bool, Error> {
+    let row = client.query_one("SELECT EXISTS (SELECT 1 FROM menu WHERE name = $1 AND active)", &[&name])?;
+    Ok(row.get(0))
 }
 
-fn cook_order_item(ts: String, table_id: String, elem: ItemPair, cook_time: u64) {
-    // println!("[COOK][START] table_id: {} item: {} cook_time: {} secs", table_id, elem.name, cook_time);
-    let start = Instant::now();
-    while start.elapsed().as_secs() < cook_time {
-        // println!("wait...");
-    }
-    let done_at: String = Utc::now().to_string();
-    update_item_status(ts, done_at, table_id, elem, ItemStatus::Done);
+// insert a new item as 'New', queued in the kitchen once the transaction commits
+fn queue_item<C: GenericClient>(client: &mut C, session_id: i64, created_at: DateTime<Utc>, updated_at: DateTime<Utc>, table_id: &str, elem: &ItemPair, by: &Actor) -> Result<(), Error> {
+    client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES ($1, $2, $3, $4, $5, $6, 'New'::itemstatus)",
+                   &[&session_id, &created_at, &updated_at, &table_id, &elem.name, &elem.amount])?;
+    client.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device) VALUES ($1, $2, $3, $4, $5, $6, 'New'::itemstatus, $7, $8)",
+                   &[&session_id, &created_at, &updated_at, &table_id, &elem.name, &elem.amount, &by.staff, &by.device])?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::settings::Args;
     #[test]
     fn test_dbio_new_given_config_provided_when_init_then_inst_generated() {
-        let dbio: Dbio = Dbio::new();
-        assert!(dbio.get_db_path().len() > 0);
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        let stats = dbio.pool_stats().unwrap();
+        assert_eq!(Settings::new().database.get_pool_max_size(), stats.max_size);
+    }
+
+    fn database(overrides: &[(&str, &str)]) -> settings::Database {
+        database_in(overrides, &settings::Env::new())
+    }
+
+    fn database_in(overrides: &[(&str, &str)], env: &settings::Env) -> settings::Database {
+        let overrides: Vec<(String, String)> = overrides.iter().map(|(key, value)| (format!("database.{}", key), value.to_string())).collect();
+        let args: Args = Args { profile: Some("test".to_string()), overrides, ..Args::default() };
+        Settings::load(&args, env).unwrap().database
+    }
+
+    // The suite shares a single database, run it with --test-threads=1
+    #[cfg(feature = "postgres-tests")]
+    fn storage_dbio() -> Dbio {
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::manual()).unwrap();
+        dbio.init().unwrap();
+        dbio.client().unwrap().execute("DELETE FROM tablet", &[]).unwrap();
+        dbio
+    }
+
+    #[cfg(feature = "postgres-tests")]
+    fn storage() -> crate::db::Storage {
+        std::sync::Arc::new(storage_dbio())
+    }
+
+    #[cfg(feature = "postgres-tests")]
+    crate::db::storage_suite!(storage);
+
+    #[test]
+    fn test_dbio_connection_given_password_with_url_characters_when_built_then_fields_kept_apart() {
+        let pg_config: Config = connection(&database(&[("password", "p@ss/w:rd?#"), ("application_name", "restaurant-test")])).unwrap();
+        assert_eq!(Some("postgres"), pg_config.get_user());
+        assert_eq!(Some("p@ss/w:rd?#".as_bytes()), pg_config.get_password());
+        assert_eq!(Some("restaurant"), pg_config.get_dbname());
+        assert_eq!(&[5432], pg_config.get_ports());
+        assert_eq!(SslMode::Prefer, pg_config.get_ssl_mode());
+        assert_eq!(Some("restaurant-test"), pg_config.get_application_name());
+        assert_eq!(Some(&Duration::from_secs(5)), pg_config.get_connect_timeout());
+
+        let pg_config: Config = connection(&database(&[("password", ""), ("sslmode", "disable"), ("connect_timeout", "0")])).unwrap();
+        assert!(pg_config.get_password().is_none());
+        assert_eq!(SslMode::Disable, pg_config.get_ssl_mode());
+        assert!(pg_config.get_connect_timeout().is_none());
+    }
+
+    #[test]
+    fn test_dbio_connection_given_password_file_and_env_when_built_then_file_over_env_over_plain() {
+        let path = std::env::temp_dir().join(format!("restaurant-db-password-{}", uuid::Uuid::new_v4()));
+        fs::write(&path, "from/file@\n").unwrap();
+        let env: settings::Env = [("DB_PASSWORD".to_string(), "from:env".to_string())].iter().cloned().collect();
+
+        let file: settings::Database = database_in(&[("password_file", path.to_str().unwrap()), ("password_env", "DB_PASSWORD")], &env);
+        assert_eq!(Some("from/file@".as_bytes()), connection(&file).unwrap().get_password());
+        let var: settings::Database = database_in(&[("password_env", "DB_PASSWORD")], &env);
+        assert_eq!(Some("from:env".as_bytes()), connection(&var).unwrap().get_password());
+        assert_eq!(Some("test".as_bytes()), connection(&database_in(&[], &env)).unwrap().get_password());
+
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(connection(&file), Err(ServerError::Validation(_))));
+    }
+
+    #[test]
+    fn test_dbio_connection_given_password_env_missing_from_env_when_built_then_validation_error() {
+        let env: settings::Env = [("OTHER_PASSWORD".to_string(), "from:env".to_string())].iter().cloned().collect();
+        let var: settings::Database = database_in(&[("password_env", "DB_PASSWORD")], &env);
+        assert!(matches!(connection(&var), Err(ServerError::Validation(_))));
     }
 
     #[test]
+    fn test_dbio_connection_given_invalid_port_or_sslmode_when_built_then_validation_error() {
+        assert!(matches!(connection(&database(&[("port", "postgres")])), Err(ServerError::Validation(_))));
+        assert!(matches!(connection(&database(&[("sslmode", "verify-ca")])), Err(ServerError::Validation(_))));
+        assert!(matches!(connection(&database(&[("sslmode", "allow")])), Err(ServerError::Validation(_))));
+    }
+
+    #[test]
+    fn test_dbio_connection_given_sslmode_require_or_verify_full_when_built_then_tls_required() {
+        for mode in &["require", "verify-full"] {
+            let config: settings::Database = database(&[("sslmode", *mode)]);
+            assert_eq!(SslMode::Require, connection(&config).unwrap().get_ssl_mode());
+            assert!(tls(&config).is_ok());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "postgres-tests")]
+    fn test_dbio_init_given_sslmode_require_when_connected_then_session_encrypted() {
+        let mut config: Settings = Settings::new();
+        config.database = database(&[("sslmode", "require")]);
+        let dbio: Dbio = Dbio::new(&config, Kitchen::new()).unwrap();
+        let mut client = dbio.client().unwrap();
+        let ssl: bool = client.query_one("SELECT ssl FROM pg_stat_ssl WHERE pid = pg_backend_pid()", &[]).unwrap().get(0);
+        assert!(ssl);
+    }
+
+    #[test]
+    #[cfg(feature = "postgres-tests")]
     fn test_dbio_init_given_db_schema_setup_when_init_then_all_table_exist() {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        let mut client = dbio.client().unwrap();
         match dbio.init() {
             Ok(()) => {
                 match client.query_one(
@@ -531,75 +744,27 @@ mod test {
             Err(e) => panic!("[TEST::DBIO_INIT] Should not panic: {}", e),
         };
     }
-    
-    #[test]
-    fn test_dbio_check_table_status_given_no_rows_in_tablet_when_checked_then_true_returned() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                match dbio.check_table_status() {
-                    Ok(val) => assert_eq!(true, val),
-                    Err(e) => panic!("[TEST::DBIO_CHECK_TABLE_STATUS] Error: {}", e)
-                }
-            },
-            Err(e) => panic!("[TEST::DBIO_CHECK_TABLE_STATUS] Should not panic: {}", e)
-        };
-    }
-            #[test]
-    fn test_dbio_check_table_status_given_all_table_statuses_are_close_when_checked_then_true_returned() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                let opened_at: DateTime<Utc> = Utc::now();
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES($1, '1', $2)", &[&opened_at, &TableStatus::Close]).unwrap();
-                match dbio.check_table_status() {
-                    Ok(val) => assert_eq!(true, val),
-                    Err(e) => panic!("[TEST::DBIO_CHECK_TABLE_STATUS] Error: {}", e)
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-            },
-            Err(e) => panic!("[TEST::DBIO_CHECK_TABLE_STATUS] Should not panic: {}", e)
-        };
-    }
 
     #[test]
-    fn test_dbio_check_table_status_given_certain_table_statuses_are_open_when_checked_then_false_returned() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                let opened_at: DateTime<Utc> = Utc::now();
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                match client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES($1, '1', 'Open')", &[&opened_at]) {
-                    Ok(_) => {
-                        match dbio.check_table_status() {
-                            Ok(val) => assert_eq!(false, val),
-                            Err(e) => panic!("[TEST::DBIO_CHECK_TABLE_STATUS] Error: {}", e)
-                        }
-                    },
-                    Err(e) => panic!("[TEST::DBIO_CHECK_TABLE_STATUS] Error: {}", e)
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-            },
-            Err(e) => panic!("[TEST::DBIO_CHECK_TABLE_STATUS] Should not panic: {}", e)
-        };
+    #[cfg(feature = "postgres-tests")]
+    fn test_dbio_pending_migrations_given_db_migrated_when_checked_then_none_pending() {
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        dbio.init().unwrap();
+        assert!(dbio.pending_migrations().unwrap().is_empty());
+
+        let mut client = dbio.client().unwrap();
+        let mut tx = client.transaction().unwrap();
+        tx.execute("DELETE FROM schema_version WHERE version = $1", &[&migration::MIGRATIONS.last().unwrap().version]).unwrap();
+        let pending = migration::pending(&mut tx).unwrap();
+        assert_eq!(vec![migration::MIGRATIONS.last().unwrap().name], pending.iter().map(|migration| migration.name).collect::<Vec<&str>>());
     }
 
     #[test]
+    #[cfg(feature = "postgres-tests")]
     fn test_dbio_update_table_status_given_no_items_left_when_executed_then_tablestatus_gets_close()
     {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        let mut client = dbio.client().unwrap();
         match dbio.init() {
             Ok(()) => {
                 let opened_at: DateTime<Utc> = Utc::now();
@@ -608,16 +773,17 @@ mod test {
                 client.execute("DELETE FROM items", &[]).unwrap();
                 client.execute("DELETE FROM item_history", &[]).unwrap();
                 client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)", &[&opened_at.to_string()]).unwrap();
-                match update_table_status(client, "1".to_string(), opened_at.to_string()) {
+                let session_id: i64 = client.query_one("SELECT MAX(session_id) FROM tablet", &[]).unwrap().get(0);
+                match update_table_status(&mut *client, session_id) {
                     Ok(()) => {
-                        let mut cli: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+                        let mut cli = dbio.client().unwrap();
                         match cli.query_one("SELECT opened_at, closed_at, table_id, status
                                              FROM tablet
                                              WHERE table_id = '1' AND opened_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS')", &[&opened_at.to_string()]) {
                             Ok(row) => {
                                 let closed_at: DateTime<Utc> = row.get(1);
                                 let status: TableStatus = row.get(3);
-                                assert!(closed_at.to_string().len() > 0, "Field 'clsoed_at' should not be null");
+                                assert!(!closed_at.to_string().is_empty(), "Field 'clsoed_at' should not be null");
                                 assert_eq!(TableStatus::Close, status, "tablestatus should be 'Close'");
                             },
                             Err(e) => {
@@ -634,10 +800,11 @@ mod test {
     }
 
     #[test]
+    #[cfg(feature = "postgres-tests")]
     fn test_dbio_update_table_status_given_all_itemstatus_done_when_executed_then_tablestatus_gets_close(
     ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        let mut client = dbio.client().unwrap();
         match dbio.init() {
             Ok(()) => {
                 let opened_at: DateTime<Utc> = Utc::now();
@@ -646,18 +813,19 @@ mod test {
                 client.execute("DELETE FROM items", &[]).unwrap();
                 client.execute("DELETE FROM item_history", &[]).unwrap();
                 client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES($1, '1', 'Open'::tablestatus)", &[&opened_at]).unwrap();
-                client.execute("INSERT into items(created_at, updated_at, table_id, item, amount, status) VALUES($1, $1, '1', 'A', 1, 'Done'::itemstatus)", &[&opened_at]).unwrap();
-                client.execute("INSERT into items(created_at, updated_at, table_id, item, amount, status) VALUES($1, $1, '1', 'B', 1, 'Done'::itemstatus)", &[&opened_at]).unwrap();
-                match update_table_status(client, "1".to_string(), opened_at.to_string()) {
+                client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES((SELECT MAX(session_id) FROM tablet), $1, $1, '1', 'A', 1, 'Done'::itemstatus)", &[&opened_at]).unwrap();
+                client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES((SELECT MAX(session_id) FROM tablet), $1, $1, '1', 'B', 1, 'Done'::itemstatus)", &[&opened_at]).unwrap();
+                let session_id: i64 = client.query_one("SELECT MAX(session_id) FROM tablet", &[]).unwrap().get(0);
+                match update_table_status(&mut *client, session_id) {
                     Ok(()) => {
-                        let mut cli: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+                        let mut cli = dbio.client().unwrap();
                         match cli.query_one("SELECT opened_at, closed_at, table_id, status
                                              FROM tablet
                                              WHERE table_id = '1' AND opened_at = $1", &[&opened_at]) {
                             Ok(row) => {
                                 let closed_at: DateTime<Utc> = row.get(1);
                                 let status: TableStatus = row.get(3);
-                                assert!(closed_at.to_string().len() > 0, "Field 'clsoed_at' should not be null");
+                                assert!(!closed_at.to_string().is_empty(), "Field 'clsoed_at' should not be null");
                                 assert_eq!(TableStatus::Close, status, "tablestatus should be 'Close'");
                             },
                             Err(e) => {
@@ -675,10 +843,11 @@ mod test {
     }
 
     #[test]
+    #[cfg(feature = "postgres-tests")]
     fn test_dbio_update_table_status_given_all_itemstatus_deleted_when_executed_then_tablestatus_gets_close(
     ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        let mut client = dbio.client().unwrap();
         match dbio.init() {
             Ok(()) => {
                 let opened_at: DateTime<Utc> = Utc::now();
@@ -687,18 +856,19 @@ mod test {
                 client.execute("DELETE FROM items", &[]).unwrap();
                 client.execute("DELETE FROM item_history", &[]).unwrap();
                 client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT into items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'A', 1, 'Deleted'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT into items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'B', 1, 'Deleted'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                match update_table_status(client, "1".to_string(), opened_at.to_string()) {
+                client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES((SELECT MAX(session_id) FROM tablet), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'A', 1, 'Deleted'::itemstatus)", &[&opened_at.to_string()]).unwrap();
+                client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES((SELECT MAX(session_id) FROM tablet), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'B', 1, 'Deleted'::itemstatus)", &[&opened_at.to_string()]).unwrap();
+                let session_id: i64 = client.query_one("SELECT MAX(session_id) FROM tablet", &[]).unwrap().get(0);
+                match update_table_status(&mut *client, session_id) {
                     Ok(()) => {
-                        let mut cli: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+                        let mut cli = dbio.client().unwrap();
                         match cli.query_one("SELECT opened_at, closed_at, table_id, status
                                              FROM tablet
                                              WHERE table_id = '1' AND opened_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS')", &[&opened_at.to_string()]) {
                             Ok(row) => {
                                 let closed_at: DateTime<Utc> = row.get(1);
                                 let status: TableStatus = row.get(3);
-                                assert!(closed_at.to_string().len() > 0, "Field 'clsoed_at' should not be null");
+                                assert!(!closed_at.to_string().is_empty(), "Field 'clsoed_at' should not be null");
                                 assert_eq!(TableStatus::Close, status, "tablestatus should be 'Close'");
                             },
                             Err(e) => {
@@ -716,10 +886,11 @@ mod test {
     }
 
     #[test]
+    #[cfg(feature = "postgres-tests")]
     fn test_dbio_update_table_status_given_certain_itemstatus_new_when_executed_then_tablestatus_gets_open(
     ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        let mut client = dbio.client().unwrap();
         match dbio.init() {
             Ok(()) => {
                 let opened_at: DateTime<Utc> = Utc::now();
@@ -728,11 +899,12 @@ mod test {
                 client.execute("DELETE FROM items", &[]).unwrap();
                 client.execute("DELETE FROM item_history", &[]).unwrap();
                 client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT into items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'A', 1, 'New'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT into items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'B', 1, 'Done'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                match update_table_status(client, "1".to_string(), opened_at.to_string()) {
+                client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES((SELECT MAX(session_id) FROM tablet), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'A', 1, 'New'::itemstatus)", &[&opened_at.to_string()]).unwrap();
+                client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES((SELECT MAX(session_id) FROM tablet), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'B', 1, 'Done'::itemstatus)", &[&opened_at.to_string()]).unwrap();
+                let session_id: i64 = client.query_one("SELECT MAX(session_id) FROM tablet", &[]).unwrap().get(0);
+                match update_table_status(&mut *client, session_id) {
                     Ok(()) => {
-                        let mut cli: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+                        let mut cli = dbio.client().unwrap();
                         match cli.query_one("SELECT opened_at, closed_at, table_id, status
                                              FROM tablet
                                              WHERE table_id = '1' AND opened_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS')", &[&opened_at.to_string()]) {
@@ -755,10 +927,11 @@ mod test {
     }
 
     #[test]
+    #[cfg(feature = "postgres-tests")]
     fn test_dbio_update_table_status_given_certain_itemstatus_process_when_executed_then_tablestatus_gets_open(
     ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+        let dbio: Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        let mut client = dbio.client().unwrap();
         match dbio.init() {
             Ok(()) => {
                 let opened_at: DateTime<Utc> = Utc::now();
@@ -767,11 +940,12 @@ mod test {
                 client.execute("DELETE FROM items", &[]).unwrap();
                 client.execute("DELETE FROM item_history", &[]).unwrap();
                 client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT into items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'A', 1, 'Process'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT into items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'B', 1, 'Done'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                match update_table_status(client, "1".to_string(), opened_at.to_string()) {
+                client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES((SELECT MAX(session_id) FROM tablet), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'A', 1, 'Process'::itemstatus)", &[&opened_at.to_string()]).unwrap();
+                client.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES((SELECT MAX(session_id) FROM tablet), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), to_timestamp($1, 'yyyy-mm-dd hh24:mi:ss'), '1', 'B', 1, 'Done'::itemstatus)", &[&opened_at.to_string()]).unwrap();
+                let session_id: i64 = client.query_one("SELECT MAX(session_id) FROM tablet", &[]).unwrap().get(0);
+                match update_table_status(&mut *client, session_id) {
                     Ok(()) => {
-                        let mut cli: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+                        let mut cli = dbio.client().unwrap();
                         match cli.query_one("SELECT opened_at, closed_at, table_id, status
                                              FROM tablet
                                              WHERE table_id = '1' AND opened_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS')", &[&opened_at.to_string()]) {
@@ -794,547 +968,52 @@ mod test {
     }
 
     #[test]
-    fn test_dbio_update_item_status_given_an_item_created_when_updated_then_status_new() {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean both tablet/items/item_history tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                
-                let now: String = Utc::now().to_string();
-                let created_at = now.clone();
-                let order: UpdateOrder = UpdateOrder {
-                    updated_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "A".to_string(), amount: 1}]
-                };
-                let table_id = order.table_id.clone();
-                let updated_at: String = order.updated_at.to_string();
-                let elem = ItemPair {
-                    name: order.items[0].name.clone(),
-                    amount: order.items[0].amount
-                };
-                let item = elem.name.clone();
-
-                client.execute("INSERT INTO tablet(opened_at, table_id, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), $2, $3)", &[&now, &order.table_id, &TableStatus::Open]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, $6)", &[&now, &updated_at, &order.table_id, &order.items[0].name, &order.items[0].amount, &ItemStatus::New]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, $6)", &[&now, &updated_at, &order.table_id, &order.items[0].name, &order.items[0].amount, &ItemStatus::New]).unwrap();
-                
-                update_item_status(now, updated_at, order.table_id, elem, ItemStatus::New);
-
-                match client.query_one("SELECT updated_at, status 
-                                        FROM items
-                                        WHERE created_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS') AND table_id = $2 AND item = $3", &[&created_at, &table_id, &item]) {
-                    Ok(row) => {
-                        let updated_at: DateTime<Utc> = row.get(0);
-                        let status: ItemStatus = row.get(1);
-                        assert!(updated_at.to_string().len() > 0);
-                        assert_eq!(ItemStatus::New, status);
-                    },
-                    Err(e) => panic!("[TEST::DBIO_UPDATE_ITEM_STATUS] Err: {}", e),
-                }
-
-                client.batch_execute(
-                    "DELETE FROM tablet;
-                     DELETE FROM items;
-                     DELETE FROM item_history;").unwrap();
-            }
-            Err(e) => panic!(
-                "[TEST::DBIO_UPDATE_ITEM_STATUS] Should not panic: {}",
-                e
-            ),
-        };
-    }
-    
-    #[test]
-    fn test_dbio_update_item_status_given_an_item_process_when_updated_then_status_process() {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean both tablet/items/item_history tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                
-                let now: String = Utc::now().to_string();
-                let created_at = now.clone();
-                let order: UpdateOrder = UpdateOrder {
-                    updated_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "A".to_string(), amount: 1}]
-                };
-                let table_id = order.table_id.clone();
-                let updated_at: String = order.updated_at.to_string();
-                let elem = ItemPair {
-                    name: order.items[0].name.clone(),
-                    amount: order.items[0].amount
-                };
-                let item = elem.name.clone();
-
-                client.execute("INSERT INTO tablet(opened_at, table_id, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), $2, $3)", &[&now, &order.table_id, &TableStatus::Open]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, $6)", &[&now, &updated_at, &order.table_id, &order.items[0].name, &order.items[0].amount, &ItemStatus::New]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, $6)", &[&now, &updated_at, &order.table_id, &order.items[0].name, &order.items[0].amount, &ItemStatus::New]).unwrap();
-                
-                update_item_status(now, updated_at, order.table_id, elem, ItemStatus::Process);
-
-                match client.query_one("SELECT updated_at, status 
-                                        FROM items
-                                        WHERE created_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS') AND table_id = $2 AND item = $3", &[&created_at, &table_id, &item]) {
-                    Ok(row) => {
-                        let updated_at: DateTime<Utc> = row.get(0);
-                        let status: ItemStatus = row.get(1);
-                        assert!(updated_at.to_string().len() > 0);
-                        assert_eq!(ItemStatus::Process, status);
-                    },
-                    Err(e) => panic!("[TEST::DBIO_UPDATE_ITEM_STATUS] Err: {}", e),
-                }
-
-                client.batch_execute(
-                    "DELETE FROM tablet;
-                     DELETE FROM items;
-                     DELETE FROM item_history;").unwrap();
-            }
-            Err(e) => panic!(
-                "[TEST::DBIO_UPDATE_ITEM_STATUS] Should not panic: {}",
-                e
-            ),
-        };       
-    }
-    
-    #[test]
-    fn test_dbio_update_item_status_given_an_item_served_when_updated_then_status_done() {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean both tablet/items/item_history tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                
-                let now: String = Utc::now().to_string();
-                let created_at = now.clone();
-                let order: UpdateOrder = UpdateOrder {
-                    updated_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "A".to_string(), amount: 1}]
-                };
-                let table_id = order.table_id.clone();
-                let updated_at: String = order.updated_at.to_string();
-                let elem = ItemPair {
-                    name: order.items[0].name.clone(),
-                    amount: order.items[0].amount
-                };
-                let item = elem.name.clone();
-
-                client.execute("INSERT INTO tablet(opened_at, table_id, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), $2, $3)", &[&now, &order.table_id, &TableStatus::Open]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, $6)", &[&now, &updated_at, &order.table_id, &order.items[0].name, &order.items[0].amount, &ItemStatus::New]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status)
-                                VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), $3, $4, $5, $6)", &[&now, &updated_at, &order.table_id, &order.items[0].name, &order.items[0].amount, &ItemStatus::New]).unwrap();
-                
-                update_item_status(now, updated_at, order.table_id, elem, ItemStatus::Done);
-
-                match client.query_one("SELECT updated_at, status 
-                                        FROM items
-                                        WHERE created_at = to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS') AND table_id = $2 AND item = $3", &[&created_at, &table_id, &item]) {
-                    Ok(row) => {
-                        let updated_at: DateTime<Utc> = row.get(0);
-                        let status: ItemStatus = row.get(1);
-                        assert!(updated_at.to_string().len() > 0);
-                        assert_eq!(ItemStatus::Done, status);
-                    },
-                    Err(e) => panic!("[TEST::DBIO_UPDATE_ITEM_STATUS] Err: {}", e),
-                }
-
-                client.batch_execute(
-                    "DELETE FROM tablet;
-                     DELETE FROM items;
-                     DELETE FROM item_history;").unwrap();
-            }
-            Err(e) => panic!(
-                "[TEST::DBIO_UPDATE_ITEM_STATUS] Should not panic: {}",
-                e
-            ),
-        };       
-    }
-
-    #[test]
-    fn test_dbio_query_by_tableid_and_item_given_no_row_exists_when_select_then_result_contains_no_string_literal(
-    ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean both tablet/items/item_history tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                match dbio.query_by_tableid_and_item("1".to_string(), "A".to_string()) {
-                    Ok(res) => assert!(res.contains("No")),
-                    Err(e) => panic!("[TEST::DBIO_QUERY_BY_TABLEID_AND_ITEM] Error: {}", e),
-                }
-            }
-            Err(e) => panic!(
-                "[TEST::DBIO_QUERY_BY_TABLEID_AND_ITEM] Should not panic: {}",
-                e
-            ),
-        };
-    }
-
-    #[test]
-    fn test_dbio_query_by_tableid_and_item_given_one_row_exists_when_select_then_result_contains_item_string_literal(
-    ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean tablet/items/item_history tables
-                let opened_at = Utc::now();
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                client
-                    .execute(
-                        "INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)",
-                        &[&opened_at.to_string()],
-                    )
-                    .unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, 'New'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, 'New'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                match dbio.query_by_tableid_and_item("1".to_string(), "A".to_string()) {
-                    Ok(res) => assert!(res.contains("item")),
-                    Err(e) => panic!("[TEST::DBIO_QUERY_BY_TABLEID_AND_ITEM] Error: {}", e),
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-            }
-            Err(e) => panic!(
-                "[TEST::DBIO_QUERY_BY_TABLEID_AND_ITEM] Should not panic: {}",
-                e
-            ),
-        };
-    }
-    #[test]
-    fn test_dbio_query_by_tableid_given_no_tableid_exists_when_select_then_result_contains_no_string_literal(
-    ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean both tablet/items/item_history tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                match dbio.query_by_tableid("1".to_string()) {
-                    Ok(res) => assert!(res.contains("No")),
-                    Err(e) => panic!("[TEST::DBIO_QUERY_BY_TABLEID] Error: {}", e),
-                }
-            }
-            Err(e) => panic!("[TEST::DBIO_QUERY_BY_TABLEID] Should not panic: {}", e),
-        };
-    }
-
-    #[test]
-    fn test_dbio_query_by_tableid_given_tableid_exists_when_select_then_result_contains_tableid_string_literal(
-    ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                let opened_at = Utc::now();
-                // Clean both tablet/items tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                client
-                    .execute(
-                        "INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)",
-                        &[&opened_at.to_string()],
-                    )
-                    .unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, 'New'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, 'New'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                match dbio.query_by_tableid("1".to_string()) {
-                    Ok(res) => assert!(res.contains("table_id")),
-                    Err(e) => panic!("[TEST::DBIO_QUERY_BY_TABLEID] Error: {}", e),
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-            }
-            Err(e) => panic!("[TEST::DBIO_QUERY_BY_TABLEID] Should not panic: {}", e),
-        };
-    }
-
-    #[test]
-    fn test_dbio_delete_given_no_items_when_delete_then_result_contains_failed_string_literal() {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean both tablet/items tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                let order: DeleteOrder = DeleteOrder {
-                    deleted_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    item: "A".to_string(),
-                };
-                match dbio.delete(order) {
-                    Ok(res) => assert!(res.contains("Failed")),
-                    Err(e) => panic!("[TEST::DBIO_DELETE] Error: {}", e),
-                }
-            }
-            Err(e) => panic!("[TEST::DBIO_DELETE] Should not panic: {}", e),
-        };
-    }
-
-    #[test]
-    fn test_dbio_delete_given_items_exist_and_status_process_when_delete_then_result_contains_failed_string_literal(
-    ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean both tablet/items tables
-                let opened_at: DateTime<Utc> = Utc::now();
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, 'Process'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                let order: DeleteOrder = DeleteOrder {
-                    deleted_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    item: "A".to_string(),
-                };
-                match dbio.delete(order) {
-                    Ok(res) => assert!(res.contains("Failed")),
-                    Err(e) => panic!("[TEST::DBIO_DELETE] Error: {}", e),
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-            }
-            Err(e) => panic!("[TEST::DBIO_DELETE] Should not panic: {}", e),
-        };
-    }
-
-    #[test]
-    fn test_dbio_delete_given_items_exist_and_status_done_when_delete_then_result_contains_failed_string_literal(
-    ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                let opened_at: DateTime<Utc> = Utc::now();
-                // Clean both tablet/items tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, 'Done'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                let order: DeleteOrder = DeleteOrder {
-                    deleted_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    item: "A".to_string(),
-                };
-                match dbio.delete(order) {
-                    Ok(res) => assert!(res.contains("Failed")),
-                    Err(e) => panic!("[TEST::DBIO_DELETE] Error: {}", e),
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-            }
-            Err(e) => panic!("[TEST::DBIO_DELETE] Should not panic: {}", e),
-        };
-    }
-
-    #[test]
-    fn test_dbio_delete_given_items_exist_and_status_new_when_delete_then_result_contains_successed_string_literal(
-    ) {
-        let dbio: Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                let opened_at: DateTime<Utc> = Utc::now();
-                // Clean both tablet/items tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'Open'::tablestatus)", &[&opened_at.to_string()]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, 'New'::itemstatus)", &[&opened_at.to_string()]).unwrap();
-                let order: DeleteOrder = DeleteOrder {
-                    deleted_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    item: "A".to_string(),
-                };
-                match dbio.delete(order) {
-                    Ok(res) => assert!(res.contains("Successed")),
-                    Err(e) => panic!("[TEST::DBIO_DELETE] Error: {}", e),
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-            }
-            Err(e) => panic!("[TEST::DBIO_DELETE] Should not panic: {}", e),
-        };
-    }
-    
-    #[test]
-    fn test_dbio_update_given_no_row_exists_when_update_then_result_contains_non_existent_string_literal() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                // Clean both tablet/items tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                let order: UpdateOrder = UpdateOrder {
-                    updated_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "A".to_string(), amount: 1}]
-                };
-                match dbio.update(order) {
-                    Ok(res) => assert!(res.contains("Non-existent")),
-                    Err(e) => panic!("[TEST::DBIO_UPDATE] Error: {}", e)
-                }
-            },
-            Err(e) => panic!("[TEST::DBIO_UPDATE] Should not panic: {}", e)
-        };
-    }
-
-    #[test]
-    fn test_dbio_update_given_table_status_close_when_update_then_result_contains_failed_string_literal() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                let opened_at: String = Utc::now().to_string();
-                let created_at: String = opened_at.clone();
-                let update_at: String = created_at.clone();
-                // Clean both tablet/items tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', $2)", &[&opened_at, &TableStatus::Close]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, $3)", &[&created_at, &update_at, &ItemStatus::Done]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, $3)", &[&created_at, &update_at, &ItemStatus::Done]).unwrap();
-                let order: UpdateOrder = UpdateOrder {
-                    updated_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "A".to_string(), amount: 8}]
-                };
-                match dbio.update(order) {
-                    Ok(res) => assert!(res.contains("Failed")),
-                    Err(e) => panic!("[TEST::DBIO_UPDATE] Error: {}", e)
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-            },
-            Err(e) => panic!("[TEST::DBIO_UPDATE] Should not panic: {}", e)
-        };
-    }
-
-    #[test]
-    fn test_dbio_update_given_table_status_open_when_update_then_result_contains_successed_string_literal() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                let opened_at: String = Utc::now().to_string();
-                let created_at: String = opened_at.clone();
-                let update_at: String = created_at.clone();
-                // Clean both tablet/items tables
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', $2)", &[&opened_at, &TableStatus::Open]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, $3)", &[&created_at, &update_at, &ItemStatus::New]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($2, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, $3)", &[&created_at, &update_at, &ItemStatus::New]).unwrap();
-                let order: UpdateOrder = UpdateOrder {
-                    updated_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "A".to_string(), amount: 8}]
-                };
-                match dbio.update(order) {
-                    Ok(res) => assert!(res.contains("Successed")),
-                    Err(e) => panic!("[TEST::DBIO_UPDATE] Error: {}", e)
-                }
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-            },
-            Err(e) => panic!("[TEST::DBIO_UPDATE] Should not panic: {}", e)
-        };
+    #[cfg(feature = "postgres-tests")]
+    fn test_dbio_query_by_tableid_given_session_locked_by_a_writer_when_queried_then_answered_without_waiting() {
+        let dbio: Dbio = storage_dbio();
+        dbio.place(PlaceOrder { created_at: Utc::now(), table_id: "1".to_string(), items: vec![ItemPair{name: "A".to_string(), amount: 1}] }, &Actor::system()).unwrap();
+        let mut writer = dbio.client().unwrap();
+        let mut tx = writer.transaction().unwrap();
+        tx.execute("SELECT session_id FROM tablet WHERE table_id = '1' FOR UPDATE", &[]).unwrap();
+        tx.execute("SELECT item_id FROM items FOR UPDATE", &[]).unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let reader: Dbio = dbio.clone();
+        std::thread::spawn(move || sender.send((reader.query_by_tableid("1".to_string()), reader.open_orders())).unwrap());
+        let (order, open) = receiver.recv_timeout(Duration::from_secs(3)).expect("the queries waited behind the writer");
+        assert_eq!(ItemStatus::New, order.unwrap().items[0].status);
+        assert_eq!(1, open.unwrap().len());
+        tx.rollback().unwrap();
     }
 
     #[test]
-    fn test_dbio_place_given_no_previous_row_when_place_then_result_contains_new_string_literal() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
+    #[cfg(feature = "postgres-tests")]
+    fn test_dbio_place_given_concurrent_orders_of_same_table_when_place_then_only_one_session_opened() {
+        let dbio:Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        let mut client = dbio.client().unwrap();
         match dbio.init() {
             Ok(()) => {
                 client.execute("DELETE FROM tablet", &[]).unwrap();
                 client.execute("DELETE FROM items", &[]).unwrap();
                 client.execute("DELETE FROM item_history", &[]).unwrap();
 
-                let order: PlaceOrder = PlaceOrder {
-                    created_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "A".to_string(), amount: 8}]
-                };
-
-                match dbio.place(order) {
-                    Ok(res) => assert!(res.contains("New")),
-                    Err(e) => panic!("[TEST::DBIO_PLACE] Error: {}", e)
-                }
-            },
-            Err(e) => panic!("[TEST::DBIO_PLACE] Should not panic: {}", e)
-        }          
-    }
+                let handles: Vec<_> = (0..8).map(|_| {
+                    let db: Dbio = dbio.clone();
+                    std::thread::spawn(move || db.place(PlaceOrder {
+                        created_at: Utc::now(),
+                        table_id: "1".to_string(),
+                        items: vec![ItemPair{name: "A".to_string(), amount: 1}]
+                    }, &Actor::system()))
+                }).collect();
+                let results: Vec<Result<Order, ServerError>> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+                assert_eq!(1, results.iter().filter(|res| res.is_ok()).count());
+                assert!(results.iter().filter_map(|res| res.as_ref().err()).all(|e| matches!(e, ServerError::Conflict(_))));
+                let row = client.query_one("SELECT COUNT(*) FROM tablet WHERE table_id = '1'", &[]).unwrap();
+                assert_eq!(1, row.get::<_, i64>(0));
+                let row = client.query_one("SELECT COUNT(*) FROM item_history WHERE table_id = '1'", &[]).unwrap();
+                assert_eq!(1, row.get::<_, i64>(0));
 
-    #[test]
-    fn test_dbio_place_given_previous_row_exists_and_table_status_open_when_place_result_contains_duplicated_string_literal() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-                
-                let opened_at: String = Utc::now().to_string();
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', $2)", &[&opened_at, &TableStatus::Open]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, $2)", &[&opened_at, &ItemStatus::New]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, $2)", &[&opened_at, &ItemStatus::New]).unwrap();
-                
-                let order: PlaceOrder = PlaceOrder {
-                    created_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "B".to_string(), amount: 8}]
-                };
-                    
-                match dbio.place(order) {
-                    Ok(res) => assert!(res.contains("Duplicated")),
-                    Err(e) => panic!("[TEST::DBIO_PLACE] Error: {}", e)
-                }
-                
                 client.execute("DELETE FROM tablet", &[]).unwrap();
                 client.execute("DELETE FROM items", &[]).unwrap();
                 client.execute("DELETE FROM item_history", &[]).unwrap();
@@ -1344,37 +1023,15 @@ mod test {
     }
 
     #[test]
-    fn test_dbio_place_given_previous_row_exists_and_table_status_close_when_place_result_contains_new_string_literal() {
-        let dbio:Dbio = Dbio::new();
-        let mut client: Client = Client::connect(dbio.get_db_path(), NoTls).unwrap();
-        match dbio.init() {
-            Ok(()) => {
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-
-                let opened_at: String = Utc::now().to_string();
-                client.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', $2)", &[&opened_at, &TableStatus::Close]).unwrap();
-                client.execute("INSERT INTO items(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, $2)", &[&opened_at, &ItemStatus::Done]).unwrap();
-                client.execute("INSERT INTO item_history(created_at, updated_at, table_id, item, amount, status) VALUES(to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), to_timestamp($1, 'YYYY-MM-DD HH24:MI:SS'), '1', 'A', 2, $2)", &[&opened_at, &ItemStatus::Done]).unwrap();
-            
-                let order: PlaceOrder = PlaceOrder {
-                    created_at: Utc::now(),
-                    table_id: "1".to_string(),
-                    items: vec![ItemPair{name: "B".to_string(), amount: 8}]
-                };
-
-                match dbio.place(order) {
-                    Ok(res) => assert!(res.contains("New")),
-                    Err(e) => panic!("[TEST::DBIO_PLACE] Error: {}", e)
-                }
-            
-                client.execute("DELETE FROM tablet", &[]).unwrap();
-                client.execute("DELETE FROM items", &[]).unwrap();
-                client.execute("DELETE FROM item_history", &[]).unwrap();
-            },
-            Err(e) => panic!("[TEST::DBIO_PLACE] Should not panic: {}", e)
-        };
+    #[cfg(feature = "postgres-tests")]
+    fn test_dbio_migrate_given_schema_up_to_date_when_migrated_then_nothing_applied() {
+        let dbio:Dbio = Dbio::new(&Settings::new(), Kitchen::new()).unwrap();
+        dbio.init().unwrap();
+        match dbio.migrate() {
+            Ok(applied) => assert!(applied.is_empty()),
+            Err(e) => panic!("[TEST::DBIO_MIGRATE] Should not panic: {}", e)
+        }
+        let mut client = dbio.client().unwrap();
+        assert_eq!(migration::MIGRATIONS.len() as i32, migration::current_version(&mut *client).unwrap());
     }
-    
 }
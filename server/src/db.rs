@@ -1,12 +1,481 @@
-use crate::order_type::{DeleteOrder, PlaceOrder, UpdateOrder};
-use postgres::Error;
+use crate::cmd::Dbio;
+use crate::error::ServerError;
+use crate::kitchen::Kitchen;
+use crate::memory::MemDb;
+use crate::order_type::{Actor, DeleteOrder, History, Item, ItemStatus, MenuItem, Order, PlaceOrder, Report, UpdateOrder};
+use crate::settings::Settings;
+use crate::sqlite::SqliteDb;
+use async_std::task;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Debug, Serialize)]
+pub struct PoolStats {
+    pub max_size: u32,
+    pub connections: u32,
+    pub idle_connections: u32,
+}
 
 pub trait DB {
-    fn init(&self) -> Result<(), Error>;
-    fn place(&self, order: PlaceOrder) -> Result<String, Error>;
-    fn update(&self, order: UpdateOrder) -> Result<String, Error>;
-    fn delete(&self, order: DeleteOrder) -> Result<String, Error>;
-    fn query_by_tableid(&self, table_id: String) -> Result<String, Error>;
-    fn query_by_tableid_and_item(&self, table_id: String, item: String) -> Result<String, Error>;
-    fn check_table_status(&self) -> Result<bool, Error>;
+    fn init(&self) -> Result<(), ServerError>;
+    // Bring the schema up to date, returning the migrations applied
+    fn migrate(&self) -> Result<Vec<String>, ServerError> {
+        self.init().map(|_| Vec::new())
+    }
+    // Migrations the schema still lacks, an error when the store cannot be reached
+    fn pending_migrations(&self) -> Result<Vec<String>, ServerError>;
+    // Writes record `by` along with the session they open or the history rows they add
+    fn place(&self, order: PlaceOrder, by: &Actor) -> Result<Order, ServerError>;
+    fn update(&self, order: UpdateOrder, by: &Actor) -> Result<Order, ServerError>;
+    fn delete(&self, order: DeleteOrder, by: &Actor) -> Result<Item, ServerError>;
+    // Take back an item that was served, on behalf of a manager
+    fn void_item(&self, order: DeleteOrder, by: &Actor) -> Result<Item, ServerError>;
+    fn query_by_tableid(&self, table_id: String) -> Result<Order, ServerError>;
+    fn query_by_tableid_and_item(&self, table_id: String, item: String) -> Result<Order, ServerError>;
+    fn check_table_status(&self) -> Result<bool, ServerError>;
+    // Every session still being served, with all its items, along with its session id
+    fn open_orders(&self) -> Result<Vec<(i64, Order)>, ServerError>;
+    // Move an item of the latest session of a table along the kitchen transitions,
+    // false once it left them (e.g. deleted while queued)
+    fn advance_item(&self, table_id: &str, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError>;
+    // Same as advance_item, for an item of the given session only, whichever session of the table is the latest
+    fn advance_session_item(&self, session_id: i64, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError>;
+    // The item_history rows of every session of a table, optionally of a single item
+    fn history(&self, table_id: &str, item: Option<&str>) -> Result<History, ServerError>;
+    // Dishes of the menu, new stores start from menu::default_menu
+    fn menu(&self) -> Result<Vec<MenuItem>, ServerError>;
+    fn menu_item(&self, name: &str) -> Result<MenuItem, ServerError>;
+    fn add_menu_item(&self, item: MenuItem) -> Result<MenuItem, ServerError>;
+    fn update_menu_item(&self, item: MenuItem) -> Result<MenuItem, ServerError>;
+    fn delete_menu_item(&self, name: &str) -> Result<MenuItem, ServerError>;
+    // Number of open tables and of items in each status
+    fn report(&self) -> Result<Report, ServerError>;
+    // Secs each served item took from being placed to 'Done', over the whole item_history
+    fn cook_times(&self) -> Result<Vec<f64>, ServerError>;
+    // Usage of the connection pool, for backends keeping one
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+}
+
+pub type Storage = Arc<dyn DB + Send + Sync>;
+
+// Cook times out of the item_history rows in the order they were recorded, each keyed by its session
+// and item. The clock starts at the first 'New' row and stops at 'Done', or is dropped on 'Deleted'
+// and 'Rejected', so that an item ordered again after being served gets timed on its own
+pub fn cook_times(rows: Vec<(String, ItemStatus, DateTime<Utc>)>) -> Vec<f64> {
+    let mut placed: HashMap<String, DateTime<Utc>> = HashMap::new();
+    let mut times: Vec<f64> = Vec::new();
+    for (key, status, at) in rows {
+        match status {
+            ItemStatus::New => {
+                placed.entry(key).or_insert(at);
+            }
+            ItemStatus::Process => {}
+            ItemStatus::Done => {
+                if let Some(start) = placed.remove(&key) {
+                    times.push((at - start).num_milliseconds() as f64 / 1000.0);
+                }
+            }
+            ItemStatus::Deleted | ItemStatus::Rejected => {
+                placed.remove(&key);
+            }
+        }
+    }
+    times
+}
+
+// Run a storage call on the blocking thread pool, the backends wait on the database or a lock
+pub async fn blocking<T, F>(db: &Storage, call: F) -> T
+where
+    F: FnOnce(&Storage) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let db: Storage = db.clone();
+    task::spawn_blocking(move || call(&db)).await
+}
+
+// Pick the storage backend configured in section [database]
+pub fn open(config: &Settings, kitchen: Kitchen) -> Result<Storage, ServerError> {
+    match config.database.get_backend().as_str() {
+        "postgres" => Ok(Arc::new(Dbio::new(config, kitchen)?)),
+        "sqlite" => Ok(Arc::new(SqliteDb::open(&config.database.get_sqlite_path(), kitchen)?)),
+        "memory" => Ok(Arc::new(MemDb::new(kitchen))),
+        backend => Err(ServerError::Storage(format!("Unknown database backend: {}", backend))),
+    }
+}
+
+// One #[test] per case of db::suite, each on a store fresh out of `$storage()`
+#[cfg(test)]
+macro_rules! storage_suite {
+    ($storage:ident) => {
+        $crate::db::storage_suite!(@cases $storage;
+            test_check_table_status_given_no_rows_in_tablet_when_checked_then_true_returned,
+            test_check_table_status_given_all_table_statuses_are_close_when_checked_then_true_returned,
+            test_check_table_status_given_certain_table_statuses_are_open_when_checked_then_false_returned,
+            test_advance_item_given_an_item_new_when_advanced_to_done_then_status_new,
+            test_advance_item_given_an_item_new_when_advanced_to_process_then_status_process,
+            test_advance_item_given_an_item_process_when_advanced_to_done_then_status_done,
+            test_advance_item_given_items_rejected_when_advanced_then_tablestatus_gets_close,
+            test_advance_item_given_item_off_kitchen_path_when_advanced_then_false_returned,
+            test_advance_session_item_given_item_deleted_and_placed_again_when_old_session_advanced_then_new_item_untouched,
+            test_query_by_tableid_and_item_given_no_row_exists_when_select_then_not_found_returned,
+            test_query_by_tableid_and_item_given_one_row_exists_when_select_then_item_returned,
+            test_query_by_tableid_given_no_tableid_exists_when_select_then_not_found_returned,
+            test_query_by_tableid_given_tableid_exists_when_select_then_order_of_tableid_returned,
+            test_delete_given_no_items_when_delete_then_not_found_returned,
+            test_delete_given_items_exist_and_status_process_when_delete_then_invalid_state_returned,
+            test_delete_given_items_exist_and_status_done_when_delete_then_invalid_state_returned,
+            test_delete_given_items_exist_and_status_new_when_delete_then_deleted_item_returned,
+            test_delete_given_placed_item_still_queued_when_delete_then_deleted_item_returned,
+            test_update_given_no_row_exists_when_update_then_not_found_returned,
+            test_update_given_table_status_close_when_update_then_invalid_state_returned,
+            test_update_given_table_status_open_when_update_then_new_items_updated_and_added,
+            test_update_given_items_off_the_menu_when_update_then_validation_returned,
+            test_place_given_no_previous_row_when_place_then_open_order_returned,
+            test_place_given_previous_row_exists_and_table_status_open_when_place_then_conflict_returned,
+            test_place_given_previous_row_exists_and_table_status_close_when_place_then_open_order_returned,
+            test_place_given_created_at_older_than_previous_session_when_place_then_latest_session_by_insertion,
+            test_place_given_items_off_the_menu_when_place_then_validation_returned,
+            test_menu_given_default_menu_when_items_added_updated_and_deleted_then_menu_follows,
+            test_report_given_open_table_when_reported_then_items_counted_by_status,
+            test_void_item_given_done_item_when_voided_then_item_deleted_and_reported,
+            test_history_given_items_placed_and_deleted_by_staff_when_queried_then_actor_of_each_row_returned,
+            test_cook_times_given_items_served_and_deleted_when_queried_then_served_ones_timed);
+    };
+    (@cases $storage:ident; $($case:ident),*) => {
+        mod suite {
+            $(
+                #[test]
+                fn $case() {
+                    $crate::db::suite::$case(super::$storage());
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use storage_suite;
+
+// Behaviour every backend shares, seeded through the DB trait only. Each backend runs it with
+// storage_suite!, given a function returning an empty, initialized store
+#[cfg(test)]
+pub mod suite {
+    use super::*;
+    use crate::order_type::{ItemPair, TableStatus};
+    use chrono::SubsecRound;
+
+    fn place(db: &Storage, items: &[(&str, i32)]) -> Result<Order, ServerError> {
+        let items: Vec<ItemPair> = items.iter().map(|(name, amount)| ItemPair { name: name.to_string(), amount: *amount }).collect();
+        db.place(PlaceOrder { created_at: Utc::now(), table_id: "1".to_string(), items }, &Actor::system())
+    }
+
+    fn delete(item: &str) -> DeleteOrder {
+        DeleteOrder { deleted_at: Utc::now(), table_id: "1".to_string(), item: item.to_string() }
+    }
+
+    fn serve(db: &Storage, item: &str) {
+        for to in &[ItemStatus::Process, ItemStatus::Done] {
+            assert!(db.advance_item("1", item, *to, &Actor::system()).unwrap());
+        }
+    }
+
+    fn status_of(db: &Storage, item: &str) -> ItemStatus {
+        db.query_by_tableid_and_item("1".to_string(), item.to_string()).unwrap().items[0].status
+    }
+
+    fn amount_of(order: &Order, item: &str) -> i32 {
+        order.items.iter().find(|row| row.name == item).unwrap().amount
+    }
+
+    pub fn test_check_table_status_given_no_rows_in_tablet_when_checked_then_true_returned(db: Storage) {
+        assert!(db.check_table_status().unwrap());
+    }
+
+    pub fn test_check_table_status_given_all_table_statuses_are_close_when_checked_then_true_returned(db: Storage) {
+        place(&db, &[("A", 1)]).unwrap();
+        serve(&db, "A");
+        assert!(db.check_table_status().unwrap());
+    }
+
+    pub fn test_check_table_status_given_certain_table_statuses_are_open_when_checked_then_false_returned(db: Storage) {
+        place(&db, &[("A", 1)]).unwrap();
+        assert!(!db.check_table_status().unwrap());
+    }
+
+    pub fn test_advance_item_given_an_item_new_when_advanced_to_done_then_status_new(db: Storage) {
+        place(&db, &[("A", 1)]).unwrap();
+        assert!(!db.advance_item("1", "A", ItemStatus::Done, &Actor::system()).unwrap());
+        assert_eq!(ItemStatus::New, status_of(&db, "A"));
+    }
+
+    pub fn test_advance_item_given_an_item_new_when_advanced_to_process_then_status_process(db: Storage) {
+        place(&db, &[("A", 1)]).unwrap();
+        assert!(db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap());
+        assert_eq!(ItemStatus::Process, status_of(&db, "A"));
+    }
+
+    pub fn test_advance_item_given_an_item_process_when_advanced_to_done_then_status_done(db: Storage) {
+        place(&db, &[("A", 1), ("B", 1)]).unwrap();
+        serve(&db, "A");
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!(ItemStatus::Done, order.items[0].status);
+        assert_eq!(TableStatus::Open, order.table.status);
+        serve(&db, "B");
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!(TableStatus::Close, order.table.status);
+        assert!(order.table.closed_at.is_some());
+    }
+
+    pub fn test_advance_item_given_items_rejected_when_advanced_then_tablestatus_gets_close(db: Storage) {
+        place(&db, &[("A", 1)]).unwrap();
+        assert!(db.advance_item("1", "A", ItemStatus::Rejected, &Actor::system()).unwrap());
+        assert!(!db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap());
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!(ItemStatus::Rejected, order.items[0].status);
+        assert_eq!(TableStatus::Close, order.table.status);
+    }
+
+    pub fn test_advance_item_given_item_off_kitchen_path_when_advanced_then_false_returned(db: Storage) {
+        place(&db, &[("A", 1), ("B", 1)]).unwrap();
+        serve(&db, "B");
+        assert!(!db.advance_item("1", "B", ItemStatus::Process, &Actor::system()).unwrap());
+        assert!(!db.advance_item("1", "C", ItemStatus::Process, &Actor::system()).unwrap());
+        assert!(!db.advance_item("2", "A", ItemStatus::Process, &Actor::system()).unwrap());
+        assert_eq!(ItemStatus::New, status_of(&db, "A"));
+    }
+
+    pub fn test_advance_session_item_given_item_deleted_and_placed_again_when_old_session_advanced_then_new_item_untouched(db: Storage) {
+        place(&db, &[("A", 1)]).unwrap();
+        let (old_session, _) = db.open_orders().unwrap().remove(0);
+        db.delete(delete("A"), &Actor::system()).unwrap();
+        place(&db, &[("A", 1)]).unwrap();
+        assert!(!db.advance_session_item(old_session, "A", ItemStatus::Process, &Actor::system()).unwrap());
+        assert_eq!(ItemStatus::New, status_of(&db, "A"));
+        let (new_session, _) = db.open_orders().unwrap().remove(0);
+        assert_ne!(old_session, new_session);
+        assert!(db.advance_session_item(new_session, "A", ItemStatus::Process, &Actor::system()).unwrap());
+    }
+
+    pub fn test_query_by_tableid_and_item_given_no_row_exists_when_select_then_not_found_returned(db: Storage) {
+        assert!(matches!(db.query_by_tableid_and_item("1".to_string(), "A".to_string()), Err(ServerError::NotFound(_))));
+    }
+
+    pub fn test_query_by_tableid_and_item_given_one_row_exists_when_select_then_item_returned(db: Storage) {
+        place(&db, &[("A", 2), ("B", 1)]).unwrap();
+        let order: Order = db.query_by_tableid_and_item("1".to_string(), "A".to_string()).unwrap();
+        assert_eq!(1, order.items.len());
+        assert_eq!(("A", 2), (order.items[0].name.as_str(), order.items[0].amount));
+        assert!(matches!(db.query_by_tableid_and_item("1".to_string(), "C".to_string()), Err(ServerError::NotFound(_))));
+    }
+
+    pub fn test_query_by_tableid_given_no_tableid_exists_when_select_then_not_found_returned(db: Storage) {
+        assert!(matches!(db.query_by_tableid("1".to_string()), Err(ServerError::NotFound(_))));
+    }
+
+    pub fn test_query_by_tableid_given_tableid_exists_when_select_then_order_of_tableid_returned(db: Storage) {
+        place(&db, &[("A", 2)]).unwrap();
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!("1", order.table.table_id);
+        assert_eq!(TableStatus::Open, order.table.status);
+    }
+
+    pub fn test_delete_given_no_items_when_delete_then_not_found_returned(db: Storage) {
+        assert!(matches!(db.delete(delete("A"), &Actor::system()), Err(ServerError::NotFound(_))));
+    }
+
+    pub fn test_delete_given_items_exist_and_status_process_when_delete_then_invalid_state_returned(db: Storage) {
+        place(&db, &[("A", 2)]).unwrap();
+        assert!(db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap());
+        assert!(matches!(db.delete(delete("A"), &Actor::system()), Err(ServerError::InvalidState(_))));
+    }
+
+    pub fn test_delete_given_items_exist_and_status_done_when_delete_then_invalid_state_returned(db: Storage) {
+        place(&db, &[("A", 2), ("B", 1)]).unwrap();
+        serve(&db, "A");
+        assert!(matches!(db.delete(delete("A"), &Actor::system()), Err(ServerError::InvalidState(_))));
+    }
+
+    pub fn test_delete_given_items_exist_and_status_new_when_delete_then_deleted_item_returned(db: Storage) {
+        place(&db, &[("A", 2)]).unwrap();
+        assert_eq!(ItemStatus::Deleted, db.delete(delete("A"), &Actor::system()).unwrap().status);
+        assert!(matches!(db.delete(delete("A"), &Actor::system()), Err(ServerError::NotFound(_))));
+        assert!(matches!(db.delete(delete("D"), &Actor::system()), Err(ServerError::NotFound(_))));
+        assert_eq!(ItemStatus::Deleted, db.history("1", Some("A")).unwrap().entries.last().unwrap().status);
+    }
+
+    pub fn test_delete_given_placed_item_still_queued_when_delete_then_deleted_item_returned(db: Storage) {
+        let order: Order = place(&db, &[("A", 1), ("B", 1)]).unwrap();
+        assert!(order.items.iter().all(|item| item.status == ItemStatus::New));
+        assert_eq!(ItemStatus::Deleted, db.delete(delete("A"), &Actor::system()).unwrap().status);
+        assert!(!db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap());
+        assert!(db.advance_item("1", "B", ItemStatus::Process, &Actor::system()).unwrap());
+    }
+
+    pub fn test_update_given_no_row_exists_when_update_then_not_found_returned(db: Storage) {
+        let order: UpdateOrder = UpdateOrder { updated_at: Utc::now(), table_id: "1".to_string(), items: vec![ItemPair { name: "A".to_string(), amount: 1 }] };
+        assert!(matches!(db.update(order, &Actor::system()), Err(ServerError::NotFound(_))));
+    }
+
+    pub fn test_update_given_table_status_close_when_update_then_invalid_state_returned(db: Storage) {
+        place(&db, &[("A", 2)]).unwrap();
+        serve(&db, "A");
+        let order: UpdateOrder = UpdateOrder { updated_at: Utc::now(), table_id: "1".to_string(), items: vec![ItemPair { name: "A".to_string(), amount: 8 }] };
+        assert!(matches!(db.update(order, &Actor::system()), Err(ServerError::InvalidState(_))));
+    }
+
+    pub fn test_update_given_table_status_open_when_update_then_new_items_updated_and_added(db: Storage) {
+        place(&db, &[("A", 2), ("B", 2)]).unwrap();
+        assert!(db.advance_item("1", "B", ItemStatus::Process, &Actor::system()).unwrap());
+        let items: Vec<ItemPair> = ["A", "B", "C"].iter().map(|name| ItemPair { name: name.to_string(), amount: 8 }).collect();
+        let order: Order = db.update(UpdateOrder { updated_at: Utc::now(), table_id: "1".to_string(), items }, &Actor::system()).unwrap();
+        assert_eq!(TableStatus::Open, order.table.status);
+        assert_eq!(3, order.items.len());
+        assert_eq!((8, 2, 8), (amount_of(&order, "A"), amount_of(&order, "B"), amount_of(&order, "C")));
+        assert_eq!(ItemStatus::New, status_of(&db, "C"));
+    }
+
+    pub fn test_update_given_items_off_the_menu_when_update_then_validation_returned(db: Storage) {
+        let _ = db.delete_menu_item("Ramen");
+        db.add_menu_item(MenuItem { name: "Ramen".to_string(), description: "Pork bone broth".to_string(), category: "noodles".to_string(), cook_time: 8, active: false }).unwrap();
+        place(&db, &[("A", 2)]).unwrap();
+        let items: Vec<ItemPair> = ["A", "Ramen"].iter().map(|name| ItemPair { name: name.to_string(), amount: 8 }).collect();
+        assert!(matches!(db.update(UpdateOrder { updated_at: Utc::now(), table_id: "1".to_string(), items }, &Actor::system()), Err(ServerError::Validation(_))));
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!((1, 2), (order.items.len(), order.items[0].amount));
+        db.delete_menu_item("Ramen").unwrap();
+    }
+
+    pub fn test_place_given_no_previous_row_when_place_then_open_order_returned(db: Storage) {
+        let order: Order = place(&db, &[("A", 8)]).unwrap();
+        assert_eq!(TableStatus::Open, order.table.status);
+        assert_eq!(ItemStatus::New, order.items[0].status);
+        assert_eq!(1, db.history("1", None).unwrap().entries.len());
+    }
+
+    pub fn test_place_given_previous_row_exists_and_table_status_open_when_place_then_conflict_returned(db: Storage) {
+        place(&db, &[("A", 2)]).unwrap();
+        assert!(matches!(place(&db, &[("B", 8)]), Err(ServerError::Conflict(_))));
+    }
+
+    pub fn test_place_given_previous_row_exists_and_table_status_close_when_place_then_open_order_returned(db: Storage) {
+        place(&db, &[("A", 2)]).unwrap();
+        serve(&db, "A");
+        let order: Order = place(&db, &[("B", 8)]).unwrap();
+        assert_eq!(TableStatus::Open, order.table.status);
+        assert_eq!(vec!["B"], order.items.iter().map(|item| item.name.as_str()).collect::<Vec<&str>>());
+    }
+
+    pub fn test_place_given_created_at_older_than_previous_session_when_place_then_latest_session_by_insertion(db: Storage) {
+        // whole seconds, which every backend keeps as they are
+        let opened_at: DateTime<Utc> = place(&db, &[("A", 1)]).unwrap().table.opened_at.trunc_subsecs(0);
+        serve(&db, "A");
+        for (created_at, item) in &[(opened_at - chrono::Duration::hours(1), "B"), (opened_at, "C")] {
+            let items: Vec<ItemPair> = vec![ItemPair { name: item.to_string(), amount: 1 }];
+            db.place(PlaceOrder { created_at: *created_at, table_id: "1".to_string(), items }, &Actor::system()).unwrap();
+            assert!(!db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap());
+            assert!(db.advance_item("1", item, ItemStatus::Rejected, &Actor::system()).unwrap());
+            let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+            assert_eq!((*created_at, TableStatus::Close), (order.table.opened_at, order.table.status));
+            assert_eq!(vec![*item], order.items.iter().map(|item| item.name.as_str()).collect::<Vec<&str>>());
+        }
+    }
+
+    pub fn test_place_given_items_off_the_menu_when_place_then_validation_returned(db: Storage) {
+        let _ = db.delete_menu_item("Ramen");
+        let dish: MenuItem = MenuItem {
+            name: "Ramen".to_string(),
+            description: "Pork bone broth".to_string(),
+            category: "noodles".to_string(),
+            cook_time: 8,
+            active: false,
+        };
+        db.add_menu_item(dish.clone()).unwrap();
+        assert!(matches!(db.add_menu_item(dish.clone()), Err(ServerError::Conflict(_))));
+
+        assert!(matches!(place(&db, &[("A", 1), ("Ramen", 1)]), Err(ServerError::Validation(_))));
+        assert!(matches!(db.query_by_tableid("1".to_string()), Err(ServerError::NotFound(_))));
+
+        db.update_menu_item(MenuItem { active: true, ..dish }).unwrap();
+        assert_eq!(2, place(&db, &[("A", 1), ("Ramen", 1)]).unwrap().items.len());
+        assert!(db.menu_item("Ramen").unwrap().active);
+        assert_eq!("Ramen", db.delete_menu_item("Ramen").unwrap().name);
+        assert!(matches!(db.menu_item("Ramen"), Err(ServerError::NotFound(_))));
+    }
+
+    pub fn test_menu_given_default_menu_when_items_added_updated_and_deleted_then_menu_follows(db: Storage) {
+        let _ = db.delete_menu_item("Ramen");
+        let dishes: usize = db.menu().unwrap().len();
+        let dish: MenuItem = MenuItem {
+            name: "Ramen".to_string(),
+            description: "Pork bone broth".to_string(),
+            category: "noodles".to_string(),
+            cook_time: 8,
+            active: true,
+        };
+        assert_eq!(dish, db.add_menu_item(dish.clone()).unwrap());
+        assert_eq!(dishes + 1, db.menu().unwrap().len());
+        assert_eq!(12, db.update_menu_item(MenuItem { cook_time: 12, ..dish.clone() }).unwrap().cook_time);
+        assert_eq!(12, db.menu_item("Ramen").unwrap().cook_time);
+        assert_eq!("Ramen", db.delete_menu_item("Ramen").unwrap().name);
+        assert!(matches!(db.menu_item("Ramen"), Err(ServerError::NotFound(_))));
+        assert!(matches!(db.update_menu_item(dish), Err(ServerError::NotFound(_))));
+        assert_eq!(dishes, db.menu().unwrap().len());
+    }
+
+    pub fn test_report_given_open_table_when_reported_then_items_counted_by_status(db: Storage) {
+        place(&db, &[("A", 2), ("B", 2), ("C", 2)]).unwrap();
+        serve(&db, "C");
+        let report: Report = db.report().unwrap();
+        let count = |status: ItemStatus| report.items.iter().find(|count| count.status == status).map(|count| (count.items, count.amount));
+        assert_eq!(1, report.open_tables);
+        assert_eq!(2, report.items.len());
+        assert_eq!((Some((2, 4)), Some((1, 2))), (count(ItemStatus::New), count(ItemStatus::Done)));
+    }
+
+    pub fn test_void_item_given_done_item_when_voided_then_item_deleted_and_reported(db: Storage) {
+        place(&db, &[("A", 1), ("B", 2)]).unwrap();
+        assert!(matches!(db.void_item(delete("A"), &Actor::system()), Err(ServerError::InvalidState(_))));
+        assert!(db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap());
+        assert!(matches!(db.void_item(delete("A"), &Actor::system()), Err(ServerError::InvalidState(_))));
+        assert!(db.advance_item("1", "A", ItemStatus::Done, &Actor::system()).unwrap());
+        assert_eq!(ItemStatus::Deleted, db.void_item(delete("A"), &Actor::system()).unwrap().status);
+        assert!(matches!(db.void_item(delete("A"), &Actor::system()), Err(ServerError::NotFound(_))));
+
+        let report: Report = db.report().unwrap();
+        assert_eq!(1, report.open_tables);
+        assert_eq!(1, report.items.len());
+        assert_eq!((ItemStatus::New, 1, 2), (report.items[0].status, report.items[0].items, report.items[0].amount));
+    }
+
+    pub fn test_history_given_items_placed_and_deleted_by_staff_when_queried_then_actor_of_each_row_returned(db: Storage) {
+        let waiter: Actor = Actor { staff: "restaurant".to_string(), device: Some("tablet-1".to_string()) };
+        let items: Vec<ItemPair> = vec![ItemPair { name: "A".to_string(), amount: 1 }, ItemPair { name: "B".to_string(), amount: 2 }];
+        db.place(PlaceOrder { created_at: Utc::now(), table_id: "1".to_string(), items }, &waiter).unwrap();
+        assert_eq!(Some("tablet-1".to_string()), db.query_by_tableid("1".to_string()).unwrap().table.device);
+        db.delete(delete("B"), &Actor { staff: "manager".to_string(), device: None }).unwrap();
+        assert!(db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap());
+
+        assert_eq!(4, db.history("1", None).unwrap().entries.len());
+        let history: History = db.history("1", Some("A")).unwrap();
+        assert_eq!((ItemStatus::Process, Some("system".to_string())), (history.entries[1].status, history.entries[1].staff.clone()));
+        let history: History = db.history("1", Some("B")).unwrap();
+        assert_eq!((ItemStatus::New, Some("restaurant".to_string())), (history.entries[0].status, history.entries[0].staff.clone()));
+        assert_eq!((ItemStatus::Deleted, Some("manager".to_string()), None), (history.entries[1].status, history.entries[1].staff.clone(), history.entries[1].device.clone()));
+        assert!(matches!(db.history("2", None), Err(ServerError::NotFound(_))));
+    }
+
+    pub fn test_cook_times_given_items_served_and_deleted_when_queried_then_served_ones_timed(db: Storage) {
+        place(&db, &[("A", 1), ("B", 1)]).unwrap();
+        assert!(db.cook_times().unwrap().is_empty());
+        serve(&db, "A");
+        db.delete(delete("B"), &Actor::system()).unwrap();
+
+        let times: Vec<f64> = db.cook_times().unwrap();
+        assert_eq!(1, times.len());
+        assert!(times[0] >= 0.0);
+    }
 }
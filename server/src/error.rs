@@ -0,0 +1,185 @@
+use crate::order_type::{ErrorResponse, Violation};
+use std::fmt;
+use tide::log::error;
+use tide::{Body, Response, StatusCode};
+
+#[derive(Debug)]
+pub enum ServerError {
+    NotFound(String),
+    Conflict(String),
+    Unauthorized(String),
+    Forbidden(String),
+    InvalidState(String),
+    Validation(String),
+    Violations(Vec<Violation>),
+    Storage(String),
+    // secs until the client's bucket holds a token again
+    RateLimited(u64),
+    // secs until the drain deadline, after which the server is gone or serving again
+    ShuttingDown(u64),
+}
+
+impl ServerError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServerError::NotFound(_) => "not_found",
+            ServerError::Conflict(_) => "conflict",
+            ServerError::Unauthorized(_) => "unauthorized",
+            ServerError::Forbidden(_) => "forbidden",
+            ServerError::InvalidState(_) => "invalid_state",
+            ServerError::Validation(_) => "validation",
+            ServerError::Violations(_) => "validation",
+            ServerError::Storage(_) => "storage",
+            ServerError::RateLimited(_) => "rate_limited",
+            ServerError::ShuttingDown(_) => "shutting_down",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ServerError::NotFound(_) => StatusCode::NotFound,
+            ServerError::Conflict(_) => StatusCode::Conflict,
+            ServerError::Unauthorized(_) => StatusCode::Unauthorized,
+            ServerError::Forbidden(_) => StatusCode::Forbidden,
+            ServerError::InvalidState(_) => StatusCode::UnprocessableEntity,
+            ServerError::Validation(_) => StatusCode::UnprocessableEntity,
+            ServerError::Violations(_) => StatusCode::UnprocessableEntity,
+            ServerError::Storage(_) => StatusCode::ServiceUnavailable,
+            ServerError::RateLimited(_) => StatusCode::TooManyRequests,
+            ServerError::ShuttingDown(_) => StatusCode::ServiceUnavailable,
+        }
+    }
+
+    // Text sent to the client: the cause of a storage error names tables and constraints, so it is only logged
+    pub fn public_message(&self) -> String {
+        match self {
+            ServerError::Storage(_) => "Storage Unavailable! Try again later".to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn to_response(&self) -> tide::Result {
+        if let ServerError::Storage(msg) = self {
+            error!("[STORAGE] {}", msg);
+        }
+        let body: Body = Body::from_json(&ErrorResponse {
+            code: self.code().to_string(),
+            message: self.public_message(),
+            violations: match self {
+                ServerError::Violations(violations) => violations.clone(),
+                _ => Vec::new(),
+            },
+        })?;
+        let mut res: Response = Response::builder(self.status()).body(body).build();
+        if let ServerError::RateLimited(secs) | ServerError::ShuttingDown(secs) = self {
+            res.insert_header("Retry-After", secs.to_string());
+        }
+        Ok(res)
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::NotFound(msg)
+            | ServerError::Conflict(msg)
+            | ServerError::Unauthorized(msg)
+            | ServerError::Forbidden(msg)
+            | ServerError::InvalidState(msg)
+            | ServerError::Validation(msg)
+            | ServerError::Storage(msg) => write!(f, "{}", msg),
+            ServerError::Violations(violations) => {
+                let fields: Vec<String> = violations
+                    .iter()
+                    .map(|violation| format!("{} {}", violation.field, violation.message))
+                    .collect();
+                write!(f, "Invalid request: {}", fields.join("; "))
+            }
+            ServerError::RateLimited(secs) => write!(f, "Too Many Requests! Retry after {} secs", secs),
+            ServerError::ShuttingDown(_) => write!(f, "Server is Closing. No More Services"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+impl From<postgres::Error> for ServerError {
+    fn from(err: postgres::Error) -> Self {
+        ServerError::Storage(format!("Database Error: {}", err))
+    }
+}
+
+impl From<r2d2::Error> for ServerError {
+    fn from(err: r2d2::Error) -> Self {
+        ServerError::Storage(format!("Connection Pool Error: {}", err))
+    }
+}
+
+impl From<rusqlite::Error> for ServerError {
+    fn from(err: rusqlite::Error) -> Self {
+        ServerError::Storage(format!("Database Error: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_server_error_status_given_each_variant_when_mapped_then_http_status_obtained() {
+        assert_eq!(StatusCode::NotFound, ServerError::NotFound("".to_string()).status());
+        assert_eq!(StatusCode::Conflict, ServerError::Conflict("".to_string()).status());
+        assert_eq!(StatusCode::Unauthorized, ServerError::Unauthorized("".to_string()).status());
+        assert_eq!(StatusCode::Forbidden, ServerError::Forbidden("".to_string()).status());
+        assert_eq!(StatusCode::UnprocessableEntity, ServerError::InvalidState("".to_string()).status());
+        assert_eq!(StatusCode::UnprocessableEntity, ServerError::Validation("".to_string()).status());
+        assert_eq!(StatusCode::UnprocessableEntity, ServerError::Violations(Vec::new()).status());
+        assert_eq!(StatusCode::ServiceUnavailable, ServerError::Storage("".to_string()).status());
+        assert_eq!(StatusCode::TooManyRequests, ServerError::RateLimited(1).status());
+        assert_eq!(StatusCode::ServiceUnavailable, ServerError::ShuttingDown(60).status());
+    }
+
+    #[test]
+    fn test_server_error_to_response_given_rate_limited_or_shutting_down_when_converted_then_retry_after_set() {
+        let res: Response = ServerError::RateLimited(3).to_response().unwrap();
+        assert_eq!(StatusCode::TooManyRequests, res.status());
+        assert_eq!("3", res.header("Retry-After").unwrap().as_str());
+
+        let res: Response = ServerError::ShuttingDown(42).to_response().unwrap();
+        assert_eq!(StatusCode::ServiceUnavailable, res.status());
+        assert_eq!("42", res.header("Retry-After").unwrap().as_str());
+    }
+
+    #[test]
+    fn test_server_error_to_response_given_conflict_when_converted_then_code_in_body() {
+        let mut res: Response = ServerError::Conflict("Duplicated!".to_string()).to_response().unwrap();
+        assert_eq!(StatusCode::Conflict, res.status());
+        let body: ErrorResponse = async_std::task::block_on(res.take_body().into_json()).unwrap();
+        assert_eq!("conflict", body.code);
+        assert_eq!("Duplicated!", body.message);
+    }
+
+    #[test]
+    fn test_server_error_to_response_given_violations_when_converted_then_each_listed_in_body() {
+        let violations: Vec<Violation> = vec![
+            Violation { field: "table_id".to_string(), message: "should not be blank".to_string() },
+            Violation { field: "items[0].amount".to_string(), message: "should be at least 1".to_string() },
+        ];
+        let mut res: Response = ServerError::Violations(violations.clone()).to_response().unwrap();
+        assert_eq!(StatusCode::UnprocessableEntity, res.status());
+        let body: ErrorResponse = async_std::task::block_on(res.take_body().into_json()).unwrap();
+        assert_eq!("validation", body.code);
+        assert_eq!("Invalid request: table_id should not be blank; items[0].amount should be at least 1", body.message);
+        assert_eq!(violations, body.violations);
+    }
+
+    #[test]
+    fn test_server_error_to_response_given_storage_when_converted_then_cause_kept_out_of_body() {
+        let cause: &str = "Database Error: duplicate key value violates unique constraint \"tablet_open_session\"";
+        let mut res: Response = ServerError::Storage(cause.to_string()).to_response().unwrap();
+        assert_eq!(StatusCode::ServiceUnavailable, res.status());
+        let body: ErrorResponse = async_std::task::block_on(res.take_body().into_json()).unwrap();
+        assert_eq!("storage", body.code);
+        assert!(!body.message.contains("tablet_open_session"));
+        assert_eq!(cause, ServerError::Storage(cause.to_string()).to_string());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::db::Storage;
+use crate::kitchen::{Kitchen, KitchenStats};
+use crate::lifecycle::Stage;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tide::log::warn;
+
+// Answer of `/healthz`, given as long as the process serves requests
+#[derive(Debug, Serialize)]
+pub struct Liveness {
+    pub status: &'static str,
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+// Answer of `/readyz`, "ready" only once every check passed
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: &'static str,
+    pub checked_at: DateTime<Utc>,
+    pub checks: Vec<Check>,
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.checks.iter().all(|check| check.ok)
+    }
+}
+
+pub fn liveness(started_at: DateTime<Utc>) -> Liveness {
+    Liveness { status: "ok", started_at, uptime_secs: (Utc::now() - started_at).num_seconds() }
+}
+
+// Whether the instance should be given traffic: the store answers with its schema up to date, the
+// cooking slots are running and the server is serving, rather than starting or draining its tables
+pub fn readiness(db: &Storage, kitchen: &Kitchen, stage: Stage) -> Readiness {
+    let mut checks: Vec<Check> = Vec::new();
+    match db.pending_migrations() {
+        Ok(pending) => {
+            checks.push(Check { name: "database", ok: true, detail: "reachable".to_string() });
+            checks.push(if pending.is_empty() {
+                Check { name: "migrations", ok: true, detail: "schema up to date".to_string() }
+            } else {
+                Check { name: "migrations", ok: false, detail: format!("pending: {}", pending.join(", ")) }
+            });
+        }
+        Err(err) => {
+            warn!("[HEALTH] Database check failed: {}", err);
+            checks.push(Check { name: "database", ok: false, detail: err.public_message() });
+            checks.push(Check { name: "migrations", ok: false, detail: "unknown, the database is unreachable".to_string() });
+        }
+    }
+
+    let stats: KitchenStats = kitchen.stats();
+    checks.push(if !stats.simulate {
+        Check { name: "kitchen", ok: true, detail: "simulation off, items are cooked through the kitchen api".to_string() }
+    } else if stats.slots == 0 {
+        Check { name: "kitchen", ok: false, detail: "no cooking slot started".to_string() }
+    } else {
+        Check { name: "kitchen", ok: stats.running == stats.slots, detail: format!("{}/{} cooking slots running", stats.running, stats.slots) }
+    });
+
+    let detail: &str = match stage {
+        Stage::Starting => "starting, recovering the items of a previous run",
+        Stage::Serving => "serving",
+        Stage::Draining => "draining, waiting for tables to be fully served",
+        Stage::Stopped => "stopped",
+    };
+    checks.push(Check { name: "lifecycle", ok: stage == Stage::Serving, detail: detail.to_string() });
+
+    let mut readiness: Readiness = Readiness { status: "ready", checked_at: Utc::now(), checks };
+    if !readiness.is_ready() {
+        readiness.status = "unavailable";
+    }
+    readiness
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::MemDb;
+    use chrono::Duration;
+    use std::sync::Arc;
+
+    fn check<'a>(readiness: &'a Readiness, name: &str) -> &'a Check {
+        readiness.checks.iter().find(|check| check.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_health_liveness_given_start_time_when_checked_then_uptime_counted() {
+        let liveness: Liveness = liveness(Utc::now() - Duration::seconds(90));
+        assert_eq!("ok", liveness.status);
+        assert!(liveness.uptime_secs >= 90);
+    }
+
+    #[test]
+    fn test_health_readiness_given_store_and_kitchen_up_when_checked_then_ready() {
+        let kitchen: Kitchen = Kitchen::new();
+        let db: Storage = Arc::new(MemDb::new(kitchen.clone()));
+        assert!(!readiness(&db, &kitchen, Stage::Serving).is_ready());
+        assert_eq!("no cooking slot started", check(&readiness(&db, &kitchen, Stage::Serving), "kitchen").detail);
+
+        kitchen.start(db.clone(), 2, 1..=1);
+        let readiness: Readiness = readiness(&db, &kitchen, Stage::Serving);
+        assert!(readiness.is_ready());
+        assert_eq!("ready", readiness.status);
+        assert_eq!("2/2 cooking slots running", check(&readiness, "kitchen").detail);
+    }
+
+    #[test]
+    fn test_health_readiness_given_draining_when_checked_then_unavailable() {
+        let kitchen: Kitchen = Kitchen::manual();
+        let db: Storage = Arc::new(MemDb::new(kitchen.clone()));
+        assert!(readiness(&db, &kitchen, Stage::Serving).is_ready());
+
+        let readiness: Readiness = readiness(&db, &kitchen, Stage::Draining);
+        assert!(!readiness.is_ready());
+        assert_eq!("unavailable", readiness.status);
+        assert!(!check(&readiness, "lifecycle").ok);
+        assert!(check(&readiness, "database").ok && check(&readiness, "migrations").ok && check(&readiness, "kitchen").ok);
+    }
+}
@@ -0,0 +1,396 @@
+use crate::db::{blocking, Storage};
+use crate::error::ServerError;
+use crate::order_type::{Actor, ItemStatus, KitchenOrder, Order};
+use async_std::channel::{self, Receiver, Sender};
+use async_std::task;
+use rand::Rng;
+use serde::Serialize;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use strum_macros::{Display, EnumString};
+use tide::log::{error, info};
+
+// An item of a table session, so that a job left behind by a deleted item never cooks the one of a later session
+pub struct CookJob {
+    pub session_id: i64,
+    pub table_id: String,
+    pub item: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KitchenStats {
+    pub simulate: bool,
+    pub queued: usize,
+    // cooking slots started and still waiting for or cooking items
+    pub slots: usize,
+    pub running: usize,
+}
+
+// What happens at startup to the items a previous run left in 'New'/'Process'
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Recovery {
+    // queue them again, items being cooked start over
+    Requeue,
+    // decline them, so that their tables get closed
+    Reject,
+}
+
+// Queue of items waiting for a free cooking slot, shared by the storage backends and the slots.
+// Without simulation the staff drive every item through the kitchen api and nothing is queued
+#[derive(Clone)]
+pub struct Kitchen {
+    simulate: bool,
+    sender: Sender<CookJob>,
+    receiver: Receiver<CookJob>,
+    slots: Arc<AtomicUsize>,
+    running: Arc<AtomicUsize>,
+}
+
+impl Kitchen {
+    pub fn new() -> Kitchen {
+        let (sender, receiver) = channel::unbounded();
+        Kitchen { simulate: true, sender, receiver, slots: Arc::new(AtomicUsize::new(0)), running: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    pub fn manual() -> Kitchen {
+        Kitchen { simulate: false, ..Kitchen::new() }
+    }
+
+    pub fn enqueue(&self, session_id: i64, table_id: &str, item: &str) {
+        if !self.simulate {
+            return;
+        }
+        let job = CookJob {
+            session_id,
+            table_id: table_id.to_string(),
+            item: item.to_string(),
+        };
+        if let Err(err) = self.sender.try_send(job) {
+            error!("[KITCHEN] Queue Error: {}", err);
+        }
+    }
+
+    pub fn queued(&self) -> usize {
+        self.sender.len()
+    }
+
+    pub fn stats(&self) -> KitchenStats {
+        KitchenStats { simulate: self.simulate, queued: self.queued(), slots: self.slots.load(Ordering::SeqCst), running: self.running.load(Ordering::SeqCst) }
+    }
+
+    // Pick up the items of the sessions left open by a previous run. Without the simulation there is
+    // no queue to requeue them on, they stay as they are for the kitchen api and are not counted
+    pub fn recover(&self, db: &Storage, policy: Recovery) -> Result<usize, ServerError> {
+        let (mut recovered, mut left): (usize, usize) = (0, 0);
+        for (session_id, order) in db.open_orders()? {
+            let table_id: &str = &order.table.table_id;
+            for item in order.items.iter().filter(|item| item.status == ItemStatus::New || item.status == ItemStatus::Process) {
+                match policy {
+                    Recovery::Requeue if !self.simulate => {
+                        left += 1;
+                        continue;
+                    }
+                    Recovery::Requeue => self.enqueue(session_id, table_id, &item.name),
+                    Recovery::Reject => {
+                        db.advance_session_item(session_id, &item.name, ItemStatus::Rejected, &Actor::system())?;
+                    }
+                }
+                info!("[KITCHEN] Recovered item: {} of table_id: {} left in {}, policy: {}", item.name, table_id, item.status, policy);
+                recovered += 1;
+            }
+        }
+        if left > 0 {
+            info!("[KITCHEN] Simulation off, {} items left in New/Process for the kitchen api", left);
+        }
+        Ok(recovered)
+    }
+
+    // Spawn `slots` cooks on the async runtime, each preparing one item at a time for the cook time
+    // of its dish, `cook_time` being the fallback for items no longer on the menu
+    pub fn start(&self, db: Storage, slots: usize, cook_time: RangeInclusive<u64>) {
+        if !self.simulate {
+            info!("[KITCHEN] Simulation off, items are cooked through the kitchen api");
+            return;
+        }
+        info!("[KITCHEN] {} cooking slots, {:?} secs for items off the menu", slots, cook_time);
+        self.slots.fetch_add(slots, Ordering::SeqCst);
+        for slot in 0..slots {
+            self.running.fetch_add(1, Ordering::SeqCst);
+            let running: Running = Running(self.running.clone());
+            task::spawn(cook(slot, db.clone(), self.receiver.clone(), cook_time.clone(), running));
+        }
+    }
+}
+
+impl Default for Kitchen {
+    fn default() -> Self {
+        Kitchen::new()
+    }
+}
+
+// Move an item on behalf of the kitchen staff, answering the item within its session
+pub fn advance(db: &Storage, order: KitchenOrder, to: ItemStatus, by: &Actor) -> Result<Order, ServerError> {
+    if db.advance_item(&order.table_id, &order.item, to, by)? {
+        return db.query_by_tableid_and_item(order.table_id, order.item);
+    }
+    let current: Order = db.query_by_tableid_and_item(order.table_id.clone(), order.item.clone())?;
+    match current.items.first() {
+        Some(item) => Err(ServerError::InvalidState(format!("Kitchen Failed! The item: {} of table_id: {} cannot go from {} to {}", order.item, order.table_id, item.status, to))),
+        None => Err(ServerError::NotFound(format!("No item: {} of table id: {}", order.item, order.table_id))),
+    }
+}
+
+// Counts a cooking slot as running until its task ends, panics included
+struct Running(Arc<AtomicUsize>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+async fn cook(slot: usize, db: Storage, jobs: Receiver<CookJob>, cook_time: RangeInclusive<u64>, _running: Running) {
+    while let Ok(job) = jobs.recv().await {
+        let (session_id, item) = (job.session_id, job.item.clone());
+        match blocking(&db, move |db| db.advance_session_item(session_id, &item, ItemStatus::Process, &Actor::system())).await {
+            Ok(true) => {}
+            // the item left the queue meanwhile, e.g. deleted by a staff
+            Ok(false) => {
+                info!("[KITCHEN] Slot {} skipped item: {} of table_id: {}, no longer queued", slot, job.item, job.table_id);
+                continue;
+            }
+            Err(err) => {
+                error!("[KITCHEN] Slot {} Cook Error: {}", slot, err);
+                continue;
+            }
+        }
+
+        let item: String = job.item.clone();
+        let secs: u64 = match blocking(&db, move |db| db.menu_item(&item)).await {
+            Ok(dish) => dish.cook_time as u64,
+            Err(err) => {
+                error!("[KITCHEN] Slot {} Menu Error: {}", slot, err);
+                rand::thread_rng().gen_range(cook_time.clone())
+            }
+        };
+        task::sleep(Duration::from_secs(secs)).await;
+
+        let item: String = job.item.clone();
+        if let Err(err) = blocking(&db, move |db| db.advance_session_item(session_id, &item, ItemStatus::Done, &Actor::system())).await {
+            error!("[KITCHEN] Slot {} Serve Error: {}", slot, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::DB;
+    use crate::memory::MemDb;
+    use crate::order_type::{DeleteOrder, ItemPair, MenuItem, Order, PlaceOrder, TableStatus};
+    use chrono::Utc;
+    use std::sync::Arc;
+
+    // A store whose dishes are cooked right away
+    fn memdb(kitchen: Kitchen) -> MemDb {
+        let db: MemDb = MemDb::new(kitchen);
+        for dish in db.menu().unwrap() {
+            db.update_menu_item(MenuItem { cook_time: 0, ..dish }).unwrap();
+        }
+        db
+    }
+
+    fn place(db: &MemDb, table_id: &str, items: &[&str]) {
+        db.place(PlaceOrder {
+            created_at: Utc::now(),
+            table_id: table_id.to_string(),
+            items: items.iter().map(|name| ItemPair { name: name.to_string(), amount: 1 }).collect(),
+        }, &Actor::system())
+        .unwrap();
+    }
+
+    #[test]
+    fn test_kitchen_enqueue_given_no_slot_started_when_placed_then_items_stay_queued() {
+        let kitchen: Kitchen = Kitchen::new();
+        let db: MemDb = memdb(kitchen.clone());
+        place(&db, "1", &["A", "B"]);
+        assert_eq!(2, kitchen.queued());
+    }
+
+    #[test]
+    fn test_kitchen_enqueue_given_manual_kitchen_when_placed_then_nothing_queued() {
+        let kitchen: Kitchen = Kitchen::manual();
+        let db: MemDb = memdb(kitchen.clone());
+        place(&db, "1", &["A", "B"]);
+        assert_eq!(0, kitchen.queued());
+        assert!(!kitchen.stats().simulate);
+    }
+
+    #[test]
+    fn test_kitchen_start_given_queued_items_when_cooked_then_all_items_done_and_table_closed() {
+        let kitchen: Kitchen = Kitchen::new();
+        let db: MemDb = memdb(kitchen.clone());
+        place(&db, "1", &["A", "B", "C"]);
+        place(&db, "2", &["A"]);
+        kitchen.start(Arc::new(db.clone()), 2, 0..=0);
+
+        task::block_on(async {
+            for _ in 0..100 {
+                if kitchen.queued() == 0 && db.check_table_status().unwrap() {
+                    break;
+                }
+                task::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        for table_id in ["1", "2"] {
+            let order: Order = db.query_by_tableid(table_id.to_string()).unwrap();
+            assert_eq!(TableStatus::Close, order.table.status);
+            assert!(order.items.iter().all(|item| item.status == ItemStatus::Done));
+        }
+    }
+
+    #[test]
+    fn test_kitchen_stats_given_slots_started_when_queried_then_slots_running() {
+        let kitchen: Kitchen = Kitchen::new();
+        assert_eq!((0, 0), (kitchen.stats().slots, kitchen.stats().running));
+        kitchen.start(Arc::new(memdb(kitchen.clone())), 3, 0..=0);
+        assert_eq!((3, 3), (kitchen.stats().slots, kitchen.stats().running));
+
+        let manual: Kitchen = Kitchen::manual();
+        manual.start(Arc::new(memdb(manual.clone())), 3, 0..=0);
+        assert_eq!((0, 0), (manual.stats().slots, manual.stats().running));
+    }
+
+    #[test]
+    fn test_kitchen_start_given_item_deleted_while_queued_when_cooked_then_item_skipped() {
+        let kitchen: Kitchen = Kitchen::new();
+        let db: MemDb = memdb(kitchen.clone());
+        place(&db, "1", &["A", "B"]);
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert!(order.items.iter().all(|item| item.status == ItemStatus::New));
+        db.delete(DeleteOrder { deleted_at: Utc::now(), table_id: "1".to_string(), item: "A".to_string() }, &Actor::system()).unwrap();
+        kitchen.start(Arc::new(db.clone()), 1, 0..=0);
+
+        task::block_on(async {
+            for _ in 0..100 {
+                if db.check_table_status().unwrap() {
+                    break;
+                }
+                task::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!(TableStatus::Close, order.table.status);
+        assert_eq!(1, order.items.len());
+        assert_eq!(ItemStatus::Done, order.items[0].status);
+    }
+
+    #[test]
+    fn test_kitchen_advance_given_manual_kitchen_when_staff_drive_items_then_statuses_follow() {
+        let kitchen: Kitchen = Kitchen::manual();
+        let db: MemDb = memdb(kitchen);
+        place(&db, "1", &["A", "B"]);
+        let db: Storage = Arc::new(db);
+        let order = |item: &str| KitchenOrder { table_id: "1".to_string(), item: item.to_string() };
+
+        assert!(matches!(advance(&db, order("A"), ItemStatus::Done, &Actor::system()), Err(ServerError::InvalidState(_))));
+        assert_eq!(ItemStatus::Process, advance(&db, order("A"), ItemStatus::Process, &Actor::system()).unwrap().items[0].status);
+        assert_eq!(ItemStatus::Done, advance(&db, order("A"), ItemStatus::Done, &Actor::system()).unwrap().items[0].status);
+        assert!(matches!(advance(&db, order("A"), ItemStatus::Rejected, &Actor::system()), Err(ServerError::InvalidState(_))));
+        assert!(matches!(advance(&db, order("C"), ItemStatus::Process, &Actor::system()), Err(ServerError::NotFound(_))));
+
+        let res: Order = advance(&db, order("B"), ItemStatus::Rejected, &Actor::system()).unwrap();
+        assert_eq!(ItemStatus::Rejected, res.items[0].status);
+        assert_eq!(TableStatus::Close, res.table.status);
+    }
+
+    #[test]
+    fn test_kitchen_recover_given_items_left_by_previous_run_when_requeued_then_all_cooked() {
+        let previous: Kitchen = Kitchen::new();
+        let db: MemDb = memdb(previous);
+        place(&db, "1", &["A", "B"]);
+        db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap();
+
+        let kitchen: Kitchen = Kitchen::new();
+        let storage: Storage = Arc::new(db.clone());
+        assert_eq!(2, kitchen.recover(&storage, Recovery::Requeue).unwrap());
+        assert_eq!(2, kitchen.queued());
+        kitchen.start(storage, 1, 0..=0);
+
+        task::block_on(async {
+            for _ in 0..100 {
+                if db.check_table_status().unwrap() {
+                    break;
+                }
+                task::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!(TableStatus::Close, order.table.status);
+        assert!(order.items.iter().all(|item| item.status == ItemStatus::Done));
+    }
+
+    #[test]
+    fn test_kitchen_recover_given_manual_kitchen_when_requeued_then_items_left_uncounted() {
+        let db: MemDb = memdb(Kitchen::new());
+        place(&db, "1", &["A", "B"]);
+
+        let kitchen: Kitchen = Kitchen::manual();
+        assert_eq!(0, kitchen.recover(&(Arc::new(db.clone()) as Storage), Recovery::Requeue).unwrap());
+        assert_eq!(0, kitchen.queued());
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert!(order.items.iter().all(|item| item.status == ItemStatus::New));
+    }
+
+    #[test]
+    fn test_kitchen_recover_given_items_left_by_previous_run_when_rejected_then_table_closed() {
+        let db: MemDb = memdb(Kitchen::new());
+        place(&db, "1", &["A", "B"]);
+        place(&db, "2", &["A"]);
+        db.advance_item("1", "A", ItemStatus::Process, &Actor::system()).unwrap();
+        db.advance_item("2", "A", ItemStatus::Process, &Actor::system()).unwrap();
+        db.advance_item("2", "A", ItemStatus::Done, &Actor::system()).unwrap();
+
+        let kitchen: Kitchen = Kitchen::new();
+        let storage: Storage = Arc::new(db.clone());
+        assert_eq!(2, kitchen.recover(&storage, Recovery::Reject).unwrap());
+        assert_eq!(0, kitchen.queued());
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!(TableStatus::Close, order.table.status);
+        assert!(order.items.iter().all(|item| item.status == ItemStatus::Rejected));
+        assert!(db.open_orders().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_kitchen_recovery_given_config_values_when_parsed_then_policy_returned() {
+        assert_eq!(Ok(Recovery::Requeue), "requeue".parse());
+        assert_eq!(Ok(Recovery::Reject), "reject".parse());
+        assert!("drop".parse::<Recovery>().is_err());
+    }
+
+    #[test]
+    fn test_kitchen_start_given_dish_off_the_menu_when_cooked_then_fallback_cook_time_used() {
+        let kitchen: Kitchen = Kitchen::new();
+        let db: MemDb = memdb(kitchen.clone());
+        let slow: MenuItem = MenuItem { cook_time: 60, ..db.menu_item("B").unwrap() };
+        db.update_menu_item(slow).unwrap();
+        place(&db, "1", &["A", "B"]);
+        db.delete_menu_item("A").unwrap();
+        kitchen.start(Arc::new(db.clone()), 2, 0..=0);
+
+        task::block_on(async {
+            for _ in 0..100 {
+                if db.query_by_tableid_and_item("1".to_string(), "A".to_string()).unwrap().items[0].status == ItemStatus::Done {
+                    break;
+                }
+                task::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!(ItemStatus::Done, order.items[0].status);
+        assert_ne!(ItemStatus::Done, order.items[1].status);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::error::ServerError;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use strum_macros::Display;
+use tide::{Middleware, Next, Request};
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    // checking the database and recovering the items left by a previous run
+    Starting,
+    Serving,
+    // a termination signal arrived, the open tables are being served before the exit
+    Draining,
+    Stopped,
+}
+
+struct Inner {
+    stage: Stage,
+    // end of the drain, set once draining
+    deadline: Option<DateTime<Utc>>,
+}
+
+// Stage of the server, kept in the app state and only moving forward:
+// Starting -> Serving -> Draining -> Stopped
+#[derive(Clone)]
+pub struct Lifecycle {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Lifecycle {
+        Lifecycle { inner: Arc::new(Mutex::new(Inner { stage: Stage::Starting, deadline: None })) }
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.inner.lock().unwrap().stage
+    }
+
+    pub fn deadline(&self) -> Option<DateTime<Utc>> {
+        self.inner.lock().unwrap().deadline
+    }
+
+    // Open the routes, false when the server was already signaled to stop
+    pub fn serve(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.stage != Stage::Starting {
+            return false;
+        }
+        inner.stage = Stage::Serving;
+        true
+    }
+
+    // Start draining until `deadline`, false when the drain had already started
+    pub fn drain(&self, deadline: DateTime<Utc>) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.stage == Stage::Draining || inner.stage == Stage::Stopped {
+            return false;
+        }
+        inner.stage = Stage::Draining;
+        inner.deadline = Some(deadline);
+        true
+    }
+
+    pub fn stop(&self) {
+        self.inner.lock().unwrap().stage = Stage::Stopped;
+    }
+
+    // Secs a refused client should wait, the time left to the drain deadline
+    fn retry_after(&self) -> u64 {
+        match self.deadline() {
+            Some(deadline) => (deadline - Utc::now()).num_seconds().max(1) as u64,
+            None => 1,
+        }
+    }
+
+    // Middleware refusing a route unless the server is serving
+    pub fn guard(&self) -> Guard {
+        Guard { lifecycle: self.clone() }
+    }
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle::new()
+    }
+}
+
+// Answers 503 with a Retry-After header while the server is not serving. It guards the routes opening
+// or changing orders and the menu; status queries, reports and the kitchen routes stay open while
+// draining, so that the open tables can still be followed and cooked until they are served
+#[derive(Clone)]
+pub struct Guard {
+    lifecycle: Lifecycle,
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Guard {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        if self.lifecycle.stage() == Stage::Serving {
+            return Ok(next.run(req).await);
+        }
+        ServerError::ShuttingDown(self.lifecycle.retry_after()).to_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_lifecycle_given_each_transition_when_applied_then_stages_only_move_forward() {
+        let lifecycle: Lifecycle = Lifecycle::new();
+        assert_eq!(Stage::Starting, lifecycle.stage());
+        assert!(lifecycle.serve());
+        assert!(!lifecycle.serve());
+        assert_eq!(Stage::Serving, lifecycle.stage());
+
+        let deadline: DateTime<Utc> = Utc::now() + Duration::seconds(30);
+        assert!(lifecycle.drain(deadline));
+        assert!(!lifecycle.drain(Utc::now()));
+        assert_eq!((Stage::Draining, Some(deadline)), (lifecycle.stage(), lifecycle.deadline()));
+        assert!(!lifecycle.serve());
+
+        lifecycle.stop();
+        assert_eq!(Stage::Stopped, lifecycle.stage());
+        assert!(!lifecycle.drain(Utc::now()));
+    }
+
+    #[test]
+    fn test_lifecycle_guard_given_draining_when_requested_then_503_with_retry_after() {
+        let lifecycle: Lifecycle = Lifecycle::new();
+        let mut app = tide::new();
+        app.at("/guarded").with(lifecycle.guard()).get(|_| async { Ok("served") });
+        app.at("/open").get(|_| async { Ok("served") });
+        let call = |path: &str| {
+            let req = tide::http::Request::new(tide::http::Method::Get, format!("http://localhost{}", path).as_str());
+            let res: tide::http::Response = async_std::task::block_on(app.respond(req)).unwrap();
+            (res.status(), res.header("Retry-After").map(|value| value.as_str().to_string()))
+        };
+
+        lifecycle.serve();
+        assert_eq!((tide::StatusCode::Ok, None), call("/guarded"));
+        lifecycle.drain(Utc::now() + Duration::seconds(30));
+        let (status, retry_after) = call("/guarded");
+        assert_eq!(tide::StatusCode::ServiceUnavailable, status);
+        assert!((29..=30).contains(&retry_after.unwrap().parse::<u64>().unwrap()));
+        assert_eq!((tide::StatusCode::Ok, None), call("/open"));
+    }
+}
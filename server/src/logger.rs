@@ -0,0 +1,283 @@
+use crate::error::ServerError;
+use crate::settings;
+use chrono::{SecondsFormat, Utc};
+use log::kv::{Error, Key, Value, Visitor};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value as Json};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+use strum_macros::{Display, EnumString};
+use tide::{Middleware, Next, Request};
+use uuid::Uuid;
+
+// Records of tide's own request logger, replaced by `RequestLog` which adds the request id
+const MUTED: &[&str] = &["tide::log::middleware"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    // `{time} {level} {message} key=value ...`
+    Text,
+    // an object per line, the key/value pairs next to time, level, target and message
+    Json,
+}
+
+// Log file moved aside to `{path}.1`, `{path}.2`... once it grows beyond `max_size` bytes
+struct RollingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RollingFile {
+    fn open(path: &Path, max_size: u64, max_files: usize) -> std::io::Result<RollingFile> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+        let size: u64 = file.metadata()?.len();
+        Ok(RollingFile { path: path.to_path_buf(), max_size, max_files, file, size })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                if self.rotated(index).exists() {
+                    fs::rename(self.rotated(index), self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+// Logger of section [log], installed once at startup for the `log` macros of the server and its crates
+pub struct Logger {
+    level: LevelFilter,
+    format: Format,
+    console: bool,
+    file: Option<Mutex<RollingFile>>,
+}
+
+impl Logger {
+    pub fn new(config: &settings::Log) -> Result<Logger, ServerError> {
+        let level: LevelFilter = config
+            .get_level()
+            .parse()
+            .map_err(|_| ServerError::Validation(format!("Unknown log level: {}", config.get_level())))?;
+        let format: Format = config
+            .get_format()
+            .parse()
+            .map_err(|_| ServerError::Validation(format!("Unknown log format: {}", config.get_format())))?;
+        let file: Option<Mutex<RollingFile>> = if config.get_file().is_empty() {
+            None
+        } else {
+            let file = RollingFile::open(Path::new(&config.get_file()), config.get_max_size() * 1024 * 1024, config.get_max_files())
+                .map_err(|err| ServerError::Validation(format!("Log file Error: {}, {}", config.get_file(), err)))?;
+            Some(Mutex::new(file))
+        };
+        Ok(Logger { level, format, console: config.get_console(), file })
+    }
+
+    fn format(&self, record: &Record) -> String {
+        let mut fields: Fields = Fields(Vec::new());
+        let _ = record.key_values().visit(&mut fields);
+        let time: String = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        match self.format {
+            Format::Text => {
+                let mut line: String = format!("{} {:<5} {}", time, record.level(), record.args());
+                for (key, value) in fields.0 {
+                    line.push_str(&format!(" {}={}", key, value));
+                }
+                line
+            }
+            Format::Json => {
+                let mut object: Map<String, Json> = Map::new();
+                object.insert("time".to_string(), Json::String(time));
+                object.insert("level".to_string(), Json::String(record.level().to_string()));
+                object.insert("target".to_string(), Json::String(record.target().to_string()));
+                object.insert("message".to_string(), Json::String(record.args().to_string()));
+                for (key, value) in fields.0 {
+                    object.insert(key, Json::String(value));
+                }
+                Json::Object(object).to_string()
+            }
+        }
+    }
+}
+
+// Key/value pairs of a record, rendered with their Display
+struct Fields(Vec<(String, String)>);
+
+impl<'kvs> Visitor<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && !MUTED.contains(&metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line: String = self.format(record);
+        if self.console {
+            println!("{}", line);
+        }
+        if let Some(file) = &self.file {
+            if let Err(err) = file.lock().unwrap().write(&format!("{}\n", line)) {
+                eprintln!("[LOG] File Error: {}", err);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().file.flush();
+        }
+    }
+}
+
+// Replaces `tide::log::start()`, records below the configured level are dropped
+pub fn start(config: &settings::Log) -> Result<(), ServerError> {
+    let logger: Logger = Logger::new(config)?;
+    let level: LevelFilter = logger.level;
+    log::set_boxed_logger(Box::new(logger)).map_err(|err| ServerError::Validation(format!("Logger Error: {}", err)))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+// Id of the request, given by the client in `X-Request-Id` or generated, and echoed in the response
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+// Logs each request once answered, with its id, status and duration. The id is left in the request
+// extensions so that the handlers can log it along with their own records
+pub struct RequestLog;
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for RequestLog {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let id: String = match req.header("X-Request-Id").map(|value| value.as_str()) {
+            Some(id) if !id.is_empty() && id.len() <= 64 => id.to_string(),
+            _ => Uuid::new_v4().to_string(),
+        };
+        let (method, path) = (req.method().to_string(), req.url().path().to_string());
+        let peer: String = req.peer_addr().unwrap_or("unknown").to_string();
+        let start: Instant = Instant::now();
+        req.set_ext(RequestId(id.clone()));
+
+        let mut res = next.run(req).await;
+        let status: u16 = res.status().into();
+        let elapsed: u64 = start.elapsed().as_millis() as u64;
+        let level: Level = if status >= 500 { Level::Error } else { Level::Info };
+        tide::log::log!(level, "[REQUEST] {} {} {}", method, path, status, {
+            request_id: id.as_str(),
+            peer: peer.as_str(),
+            duration_ms: elapsed,
+        });
+        res.insert_header("X-Request-Id", id);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+    use log::kv::Source;
+
+    fn logger(format: Format, file: Option<RollingFile>) -> Logger {
+        Logger { level: LevelFilter::Info, format, console: false, file: file.map(Mutex::new) }
+    }
+
+    fn record<'a>(args: std::fmt::Arguments<'a>, kvs: &'a dyn Source) -> Record<'a> {
+        Record::builder().args(args).level(Level::Info).target("server::main").key_values(kvs).build()
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir: PathBuf = std::env::temp_dir().join(format!("restaurant-{}-{}", name, Uuid::new_v4()));
+        dir.join("server.log")
+    }
+
+    #[test]
+    fn test_logger_new_given_config_provided_when_init_then_level_and_format_parsed() {
+        let logger: Logger = Logger::new(&Settings::new().log).unwrap();
+        assert!(logger.level >= LevelFilter::Error);
+        assert!(!logger.enabled(&Metadata::builder().level(Level::Info).target("tide::log::middleware").build()));
+    }
+
+    #[test]
+    fn test_logger_format_given_key_values_when_formatted_then_fields_rendered() {
+        let kvs: &[(&str, &str)] = &[("request_id", "abc"), ("table_id", "4")];
+        let text: String = logger(Format::Text, None).format(&record(format_args!("[ORDER] Placed"), &kvs));
+        assert!(text.ends_with("INFO  [ORDER] Placed request_id=abc table_id=4"));
+
+        let json: Json = serde_json::from_str(&logger(Format::Json, None).format(&record(format_args!("[ORDER] Placed"), &kvs))).unwrap();
+        assert_eq!("INFO", json["level"]);
+        assert_eq!("server::main", json["target"]);
+        assert_eq!("[ORDER] Placed", json["message"]);
+        assert_eq!("abc", json["request_id"]);
+        assert_eq!("4", json["table_id"]);
+    }
+
+    #[test]
+    fn test_logger_rolling_file_given_max_size_exceeded_when_written_then_rotated() {
+        let path: PathBuf = scratch("rotate");
+        let mut file: RollingFile = RollingFile::open(&path, 10, 2).unwrap();
+        for line in &["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write(line).unwrap();
+        }
+
+        assert_eq!("fourth\n", fs::read_to_string(&path).unwrap());
+        assert_eq!("third\n", fs::read_to_string(file.rotated(1)).unwrap());
+        assert_eq!("second\n", fs::read_to_string(file.rotated(2)).unwrap());
+        assert!(!file.rotated(3).exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_logger_log_given_file_when_logged_then_line_appended() {
+        let path: PathBuf = scratch("append");
+        let logger: Logger = logger(Format::Json, Some(RollingFile::open(&path, 1024, 1).unwrap()));
+        let kvs: &[(&str, &str)] = &[];
+        logger.log(&record(format_args!("[KITCHEN] 4 cooking slots"), &kvs));
+        logger.log(&Record::builder().args(format_args!("dropped")).level(Level::Debug).build());
+        logger.flush();
+
+        let content: String = fs::read_to_string(&path).unwrap();
+        assert_eq!(1, content.lines().count());
+        assert!(content.contains("4 cooking slots"));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
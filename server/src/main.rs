@@ -1,236 +1,556 @@
+mod auth;
 mod cmd;
 mod db;
+mod error;
+mod health;
+mod kitchen;
+mod lifecycle;
+mod logger;
+mod memory;
+mod menu;
+mod metrics;
+mod migration;
 mod order_type;
+mod session;
 mod settings;
+mod shutdown;
+mod sqlite;
+mod throttle;
+mod validation;
 
-use cmd::Dbio;
-use ctrlc;
-use db::DB;
-use lazy_static::lazy_static;
+use auth::{Accounts, Identity, Role};
+use chrono::{DateTime, Utc};
+use db::{blocking, Storage};
+use error::ServerError;
+use kitchen::{Kitchen, Recovery};
+use lifecycle::Lifecycle;
+use logger::{RequestId, RequestLog};
+use metrics::{Metrics, Operation};
+use order_type::Actor;
 use order_type::DeleteOrder;
+use order_type::ItemStatus;
+use order_type::KitchenOrder;
+use order_type::Login;
+use order_type::MenuItem;
 use order_type::PlaceOrder;
 use order_type::UpdateOrder;
-use settings::Settings;
-use sha256::digest_bytes;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use settings::{Args, Settings, SettingsError};
+use shutdown::{Outcome, Shutdown};
 use std::process;
-use std::sync::Mutex;
-use std::{thread, time};
+use std::thread;
+use throttle::{Group, Throttle};
+use tide::log::{error, info, warn};
+use tide::Body;
+use validation::Rules;
 
-#[macro_use]
-extern crate postgres;
 #[macro_use]
 extern crate postgres_derive;
 
-struct Signal {
-    terminate: bool,
+#[derive(Clone)]
+struct State {
+    accounts: Accounts,
+    db: Storage,
+    started_at: DateTime<Utc>,
+    kitchen: Kitchen,
+    lifecycle: Lifecycle,
+    metrics: Metrics,
+    rules: Rules,
+    shutdown: Shutdown,
 }
 
-impl Signal {
-    fn new(signal: bool) -> Signal {
-        Signal { terminate: signal }
-    }
+#[async_std::main]
+async fn main() -> tide::Result<()> {
+    let mut host: String = "".to_string();
+    /* the command line comes first, its flags overriding every other layer of the settings */
+    let args: Args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => settings_error(err),
+    };
+    let config: Settings = match Settings::load(&args, &std::env::vars().collect()) {
+        Ok(config) => config,
+        Err(err) => settings_error(err),
+    };
+    host.push_str(&config.server.get_ip());
+    host.push(':');
+    host.push_str(&config.server.get_port());
 
-    fn set(&mut self, signal: bool) {
-        self.terminate = signal;
+    /* `server hash-password` reads a password from stdin and prints its hash for section [auth] */
+    if args.command.as_deref() == Some("hash-password") {
+        let mut password: String = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        match auth::hash_password(password.trim_end_matches(&['\r', '\n'][..])) {
+            Ok(hash) => println!("{}", hash),
+            Err(err) => panic!("[AUTH] {}", err),
+        }
+        return Ok(());
+    }
+    if let Err(err) = logger::start(&config.log) {
+        panic!("[LOG] {}", err);
+    }
+    info!("[SETTINGS] Profile: {}", config.profile);
+    let accounts: Accounts = match Accounts::new(&config.auth) {
+        Ok(accounts) => accounts,
+        Err(err) => panic!("[AUTH] {}", err),
+    };
+    if accounts.is_empty() {
+        warn!("[AUTH] No account in section [auth], every authorized request will be refused until [[auth.accounts]] are configured");
+    }
+    if config.auth.get_token_secret().is_empty() {
+        warn!("[AUTH] No token_secret in section [auth], tokens are signed with a random key and will not survive a restart");
     }
 
-    fn get(&self) -> bool {
-        self.terminate
+    let throttle: Throttle = Throttle::new(&config.rate_limit, accounts.clone());
+    if !throttle.is_enabled() {
+        warn!("[RATE_LIMIT] Disabled in section [rate_limit], clients are not throttled");
     }
-}
 
-lazy_static! {
-    static ref SIGNAL: Mutex<Signal> = Mutex::new(Signal::new(false));
-}
+    let lifecycle: Lifecycle = Lifecycle::new();
+    let kitchen: Kitchen = if config.kitchen.get_simulate() { Kitchen::new() } else { Kitchen::manual() };
+    let command: Storage = match db::open(&config, kitchen.clone()) {
+        Ok(command) => command,
+        Err(err) => panic!("[DATABASE] {}", err),
+    };
 
-#[async_std::main]
-async fn main() -> tide::Result<()> {
-    let mut server = tide::new();
-    let mut host: String = "".to_string();
-    let config: Settings = Settings::new();
-    host.push_str(&config.server.get_ip());
-    host.push_str(":");
-    host.push_str(&config.server.get_port());
-    let command: Dbio = Dbio::new();
+    /* `server migrate` brings the schema up to date and exits */
+    match args.command.as_deref() {
+        Some("migrate") => {
+            match command.migrate() {
+                Ok(applied) if applied.is_empty() => info!("[MIGRATE] Nothing to apply"),
+                Ok(applied) => info!("[MIGRATE] Applied: {}", applied.join(", ")),
+                Err(err) => panic!("[MIGRATE] {}", err),
+            }
+            return Ok(());
+        }
+        Some(arg) => panic!("[SERVER] Unknown command: {}, expected `migrate` or `hash-password`", arg),
+        None => {}
+    }
 
     /* Check DB status first */
     match command.init() {
-        Ok(()) => println!("[DATABASE] DB status OK"),
+        Ok(()) => info!("[DATABASE] DB status OK"),
         Err(err) => {
-            println!("[DATBASE] DB Error: {}", err);
+            error!("[DATABASE] DB Error: {}", err);
             panic!("[DATABASE] Please check db connection status.");
         }
     };
 
-    ctrlc::set_handler(error_handler).expect("Error setting Ctrl-C handler");
-    tide::log::start();
+    /* Pick up the items a previous run left behind */
+    let recovery: Recovery = match config.kitchen.get_recovery().parse() {
+        Ok(recovery) => recovery,
+        Err(_) => panic!("[KITCHEN] Unknown recovery policy: {}", config.kitchen.get_recovery()),
+    };
+    match kitchen.recover(&command, recovery) {
+        Ok(recovered) => info!("[KITCHEN] {} items recovered", recovered),
+        Err(err) => panic!("[KITCHEN] Recovery Error: {}", err),
+    }
+    kitchen.start(command.clone(), config.kitchen.get_slots(), config.kitchen.get_cook_time());
+
+    let shutdown: Shutdown = match Shutdown::new(&config.shutdown, lifecycle.clone()) {
+        Ok(shutdown) => shutdown,
+        Err(err) => panic!("[TERMINATION] {}", err),
+    };
+    let (terminator, coordinator) = (command.clone(), shutdown.clone());
+    ctrlc::set_handler(move || error_handler(&terminator, &coordinator)).expect("Error setting Ctrl-C handler");
 
-    /* simple api processing here */
+    let metrics: Metrics = Metrics::new();
+    let mut server = tide::with_state(State {
+        accounts: accounts.clone(),
+        db: command,
+        started_at: Utc::now(),
+        kitchen,
+        lifecycle: lifecycle.clone(),
+        metrics: metrics.clone(),
+        rules: Rules::new(&config.validation),
+        shutdown,
+    });
+    server.with(RequestLog);
+    server.with(metrics);
+
+    /* simple api processing here, each route open to the roles given */
+    let anyone: &[Role] = Role::ALL;
+    let floor: &[Role] = &[Role::Staff, Role::Manager];
+    server.at("/healthz").get(healthz);
+    server.at("/readyz").get(readyz);
+    server.at("/metrics").with(throttle.limit(Group::Status)).get(query_metrics);
+    server.at("/api/auth/login").with(throttle.limit(Group::Auth)).post(login);
+    server.at("/api/auth/refresh").with(throttle.limit(Group::Auth)).with(accounts.allow(anyone)).post(refresh);
+    server.at("/api/auth/logout").with(throttle.limit(Group::Auth)).with(accounts.allow(anyone)).post(logout);
     server
         .at("/api/status/order/:tableid")
+        .with(throttle.limit(Group::Status))
+        .with(accounts.allow(anyone))
         .get(query_by_tableid);
     server
         .at("/api/status/order/:tableid/:item")
+        .with(throttle.limit(Group::Status))
+        .with(accounts.allow(anyone))
         .get(query_by_tableid_and_item);
-    server.at("/api/place/order").post(add_by_tableid_and_item);
+    server.at("/api/status/pool").with(throttle.limit(Group::Status)).with(accounts.allow(anyone)).get(query_pool_stats);
+    server.at("/api/status/kitchen").with(throttle.limit(Group::Status)).with(accounts.allow(anyone)).get(query_kitchen_stats);
+    server
+        .at("/api/place/order")
+        .with(throttle.limit(Group::Orders))
+        .with(lifecycle.guard())
+        .with(accounts.allow(floor))
+        .post(add_by_tableid_and_item);
     server
         .at("/api/delete/order")
+        .with(throttle.limit(Group::Orders))
+        .with(lifecycle.guard())
+        .with(accounts.allow(floor))
         .delete(remove_by_tableid_and_item);
     server
         .at("/api/update/order")
+        .with(throttle.limit(Group::Orders))
+        .with(lifecycle.guard())
+        .with(accounts.allow(floor))
         .patch(update_by_tableid_and_item);
+    server.at("/api/menu").with(throttle.limit(Group::Status)).with(accounts.allow(anyone)).get(query_menu);
+    server
+        .at("/api/menu")
+        .with(throttle.limit(Group::Manager))
+        .with(lifecycle.guard())
+        .with(accounts.allow(&[Role::Manager]))
+        .post(add_menu_item);
+    server
+        .at("/api/menu")
+        .with(throttle.limit(Group::Manager))
+        .with(lifecycle.guard())
+        .with(accounts.allow(&[Role::Manager]))
+        .patch(update_menu_item);
+    server.at("/api/menu/:name").with(throttle.limit(Group::Status)).with(accounts.allow(anyone)).get(query_menu_item);
+    server
+        .at("/api/menu/:name")
+        .with(throttle.limit(Group::Manager))
+        .with(lifecycle.guard())
+        .with(accounts.allow(&[Role::Manager]))
+        .delete(remove_menu_item);
+    server.at("/api/kitchen/start").with(throttle.limit(Group::Kitchen)).with(accounts.allow(&[Role::Kitchen])).patch(start_item);
+    server.at("/api/kitchen/finish").with(throttle.limit(Group::Kitchen)).with(accounts.allow(&[Role::Kitchen])).patch(finish_item);
+    server.at("/api/kitchen/reject").with(throttle.limit(Group::Kitchen)).with(accounts.allow(&[Role::Kitchen])).patch(reject_item);
+    server
+        .at("/api/manager/void")
+        .with(throttle.limit(Group::Manager))
+        .with(lifecycle.guard())
+        .with(accounts.allow(&[Role::Manager]))
+        .delete(void_item);
+    server.at("/api/manager/shutdown").with(throttle.limit(Group::Manager)).with(accounts.allow(&[Role::Manager])).get(query_shutdown);
+    server.at("/api/manager/report").with(throttle.limit(Group::Manager)).with(accounts.allow(&[Role::Manager])).get(query_report);
+    server
+        .at("/api/manager/history/:tableid")
+        .with(throttle.limit(Group::Manager))
+        .with(accounts.allow(&[Role::Manager]))
+        .get(query_history);
+    server
+        .at("/api/manager/history/:tableid/:item")
+        .with(throttle.limit(Group::Manager))
+        .with(accounts.allow(&[Role::Manager]))
+        .get(query_history_of_item);
+    /* a signal received while starting leaves the server draining, the routes stay refused */
+    lifecycle.serve();
     server.listen(host).await?;
 
     Ok(())
 }
 
-fn error_handler() {
-    println!("[TERMINATION] Received signal to terminate the server!");
-    /* Waiting incomplete requests to be done */
-    SIGNAL.lock().unwrap().set(true);
-    let command: Dbio = Dbio::new();
-    let mut all_done: bool = false;
+fn settings_error(err: SettingsError) -> ! {
+    eprintln!("[SETTINGS] {}", err);
+    eprintln!("[SETTINGS] Usage: server [migrate | hash-password] [--profile development|test|production] [--config FILE] [--config-dir DIR] [--section.key VALUE]...");
+    process::exit(2);
+}
 
-    loop {
-        match command.check_table_status() {
-            Ok(done) => {
-                all_done = done;
+fn error_handler(command: &Storage, shutdown: &Shutdown) {
+    if !shutdown.begin() {
+        warn!("[TERMINATION] Received a second signal, exiting without waiting for the tables!");
+        log::logger().flush();
+        process::exit(130);
+    }
+    warn!("[TERMINATION] Received signal to terminate the server!");
+    /* Waiting incomplete requests to be done, the handler returns so that a second signal gets through */
+    let (command, shutdown) = (command.clone(), shutdown.clone());
+    thread::spawn(move || {
+        let code: i32 = match shutdown.drain(&command) {
+            Outcome::Drained => {
+                info!("[TERMINATION] Fully served! Server terminated.");
+                0
             }
-            _ => {}
-        }
-        if all_done {
-            break;
-        } else {
-            println!("[TERMINATION] Waiting for tables to be fully served");
-            thread::sleep(time::Duration::from_millis(1000));
-        }
+            Outcome::TimedOut => {
+                warn!("[TERMINATION] Drain timed out! Server terminated with {} tables left open.", shutdown.status().open_tables);
+                1
+            }
+        };
+        log::logger().flush();
+        process::exit(code);
+    });
+}
+
+fn respond<T: Serialize>(result: Result<T, ServerError>) -> tide::Result {
+    match result {
+        Ok(value) => Ok(Body::from_json(&value)?.into()),
+        Err(err) => err.to_response(),
     }
+}
 
-    println!("[TERMINATION] Fully served! Server terminated.");
-    process::exit(0);
+// The account behind the request, as left by the `Authorize` middleware of its route
+fn actor(req: &tide::Request<State>) -> Actor {
+    match req.ext::<Identity>() {
+        Some(identity) => identity.actor(),
+        None => Actor::system(),
+    }
 }
 
-fn is_auth(req: &tide::Request<()>) -> bool {
-    (match req.header("X-Auth-Username") {
-        Some(name) => {
-            let username = b"restaurant";
-            if name.as_str() == digest_bytes(username) {
-                // println!("X-Auth-Username: {}", name.as_str());
-                true
-            } else {
-                false
-            }
+// Id of the request, as left by the `RequestLog` middleware
+fn request_id(req: &tide::Request<State>) -> String {
+    match req.ext::<RequestId>() {
+        Some(RequestId(id)) => id.clone(),
+        None => "-".to_string(),
+    }
+}
+
+// Logs and counts an order operation with the request, table and staff behind it, then passes its result on
+fn track_order<T>(req: &tide::Request<State>, operation: Operation, table_id: &str, result: Result<T, ServerError>) -> Result<T, ServerError> {
+    req.state().metrics.order(operation, &result);
+    let action: &str = operation.action();
+    let actor: Actor = actor(req);
+    let (request_id, device) = (request_id(req), actor.device.unwrap_or_else(|| "-".to_string()));
+    match &result {
+        Ok(_) => info!("[ORDER] {} of table_id: {}", action, table_id, {
+            request_id: request_id,
+            table_id: table_id,
+            staff: actor.staff,
+            device: device,
+        }),
+        Err(err) => warn!("[ORDER] {} of table_id: {} Failed! {}", action, table_id, err, {
+            request_id: request_id,
+            table_id: table_id,
+            staff: actor.staff,
+            device: device,
+        }),
+    }
+    result
+}
+
+async fn parse_body<T: DeserializeOwned>(req: &mut tide::Request<State>) -> Result<T, ServerError> {
+    req.body_json()
+        .await
+        .map_err(|err| ServerError::Validation(format!("Malformed request: {}", err)))
+}
+
+async fn login(mut req: tide::Request<State>) -> tide::Result {
+    let login: Login = match parse_body(&mut req).await {
+        Ok(login) => login,
+        Err(err) => return err.to_response(),
+    };
+    respond(req.state().accounts.login(&login).await)
+}
+
+async fn refresh(req: tide::Request<State>) -> tide::Result {
+    match req.ext::<Identity>() {
+        Some(Identity { name, claims: Some(claims), .. }) => {
+            info!("[AUTH] {} refreshed the token of {}", name, claims.device, { request_id: request_id(&req) });
+            respond(req.state().accounts.refresh(claims))
         }
-        None => false,
-    }) && (match req.header("X-Auth-Password") {
-        Some(pwd) => {
-            let password = b"paidy";
-            if pwd.as_str() == digest_bytes(password) {
-                // println!("X-Auth-Password: {}", pwd.as_str());
-                true
-            } else {
-                false
-            }
+        _ => ServerError::Unauthorized("Refresh Failed! No bearer token".to_string()).to_response(),
+    }
+}
+
+async fn logout(req: tide::Request<State>) -> tide::Result {
+    match req.ext::<Identity>() {
+        Some(Identity { name, claims: Some(claims), .. }) => {
+            info!("[AUTH] {} logged out of {}", name, claims.device, { request_id: request_id(&req) });
+            req.state().accounts.logout(claims);
+            respond(Ok(claims))
         }
-        None => false,
-    })
+        _ => ServerError::Unauthorized("Logout Failed! No bearer token".to_string()).to_response(),
+    }
 }
 
-async fn query_by_tableid(req: tide::Request<()>) -> tide::Result {
-    let terminated: bool = SIGNAL.lock().unwrap().get();
-    if !terminated {
-        let mut collection = req.url().as_str().split('/');
-        let mut res: String = "".to_string();
-        let command: Dbio = Dbio::new();
-        let table_id = collection.nth_back(0).unwrap();
+async fn query_by_tableid(req: tide::Request<State>) -> tide::Result {
+    let table_id: String = req.param("tableid")?.to_string();
 
-        match command.query_by_tableid(table_id.to_string()) {
-            Ok(result) => res = result,
-            _ => {}
-        };
+    respond(blocking(&req.state().db, move |db| db.query_by_tableid(table_id)).await)
+}
+
+async fn query_by_tableid_and_item(req: tide::Request<State>) -> tide::Result {
+    let table_id: String = req.param("tableid")?.to_string();
+    let item: String = req.param("item")?.to_string();
+
+    respond(blocking(&req.state().db, move |db| db.query_by_tableid_and_item(table_id, item)).await)
+}
 
-        Ok(res.into())
-    } else {
-        Ok("Server is Closing. No More Services".into())
+async fn query_pool_stats(req: tide::Request<State>) -> tide::Result {
+    match req.state().db.pool_stats() {
+        Some(stats) => Ok(Body::from_json(&stats)?.into()),
+        None => ServerError::NotFound("No connection pool for the configured backend".to_string()).to_response(),
     }
 }
 
-async fn query_by_tableid_and_item(req: tide::Request<()>) -> tide::Result {
-    let terminated: bool = SIGNAL.lock().unwrap().get();
-    if !terminated {
-        let mut collection = req.url().as_str().split('/');
-        let mut res: String = "".to_string();
-        let command: Dbio = Dbio::new();
-        let item = collection.nth_back(0).unwrap();
-        let table_id = collection.nth_back(0).unwrap();
+async fn query_metrics(req: tide::Request<State>) -> tide::Result {
+    let (metrics, kitchen): (Metrics, Kitchen) = (req.state().metrics.clone(), req.state().kitchen.clone());
+    match blocking(&req.state().db, move |db| metrics.render(db, &kitchen)).await {
+        Ok(text) => Ok(tide::Response::builder(tide::StatusCode::Ok)
+            .content_type("text/plain; version=0.0.4")
+            .body(text)
+            .build()),
+        Err(err) => err.to_response(),
+    }
+}
 
-        match command.query_by_tableid_and_item(table_id.to_string(), item.to_string()) {
-            Ok(result) => res = result,
-            _ => {}
-        };
+async fn healthz(req: tide::Request<State>) -> tide::Result {
+    Ok(Body::from_json(&health::liveness(req.state().started_at))?.into())
+}
 
-        Ok(res.into())
-    } else {
-        Ok("Server is Closing. No More Services".into())
-    }
-}
-
-async fn add_by_tableid_and_item(mut req: tide::Request<()>) -> tide::Result {
-    let terminated: bool = SIGNAL.lock().unwrap().get();
-    if !terminated {
-        if is_auth(&req) {
-            let order: PlaceOrder = req.body_json().await?;
-            let command: Dbio = Dbio::new();
-            let mut res: String = "".to_string();
-            match command.place(order) {
-                Ok(result) => res = result,
-                _ => {}
-            };
-            Ok(res.into())
-        } else {
-            Ok("Un-authorized place order".into())
-        }
-    } else {
-        Ok("Server is Closing. No More Services".into())
-    }
-}
-
-async fn remove_by_tableid_and_item(mut req: tide::Request<()>) -> tide::Result {
-    let terminated: bool = SIGNAL.lock().unwrap().get();
-    if !terminated {
-        if is_auth(&req) {
-            let order: DeleteOrder = req.body_json().await?;
-            let command: Dbio = Dbio::new();
-            let mut res: String = "".to_string();
-            match command.delete(order) {
-                Ok(result) => res = result,
-                _ => {}
-            };
-            Ok(res.into())
-        } else {
-            Ok("Un-authorized delete order".into())
-        }
-    } else {
-        Ok("Server is Closing. No More Services".into())
-    }
-}
-
-async fn update_by_tableid_and_item(mut req: tide::Request<()>) -> tide::Result {
-    let terminated: bool = SIGNAL.lock().unwrap().get();
-    if !terminated {
-        if is_auth(&req) {
-            let order: UpdateOrder = req.body_json().await?;
-            let command: Dbio = Dbio::new();
-            let mut res: String = "".to_string();
-            match command.update(order) {
-                Ok(result) => res = result,
-                _ => {}
-            };
-            Ok(res.into())
-        } else {
-            Ok("Un-authorized update order".into())
-        }
-    } else {
-        Ok("Server is Closing. No More Services".into())
+// 503 while any check fails, so that the load balancer stops routing to the instance
+async fn readyz(req: tide::Request<State>) -> tide::Result {
+    let (kitchen, stage) = (req.state().kitchen.clone(), req.state().lifecycle.stage());
+    let readiness = blocking(&req.state().db, move |db| health::readiness(db, &kitchen, stage)).await;
+    let status = if readiness.is_ready() { tide::StatusCode::Ok } else { tide::StatusCode::ServiceUnavailable };
+    Ok(tide::Response::builder(status).body(Body::from_json(&readiness)?).build())
+}
+
+async fn query_shutdown(req: tide::Request<State>) -> tide::Result {
+    Ok(Body::from_json(&req.state().shutdown.status())?.into())
+}
+
+async fn query_kitchen_stats(req: tide::Request<State>) -> tide::Result {
+    Ok(Body::from_json(&req.state().kitchen.stats())?.into())
+}
+
+async fn add_by_tableid_and_item(mut req: tide::Request<State>) -> tide::Result {
+    let order: PlaceOrder = match parse_body(&mut req).await {
+        Ok(order) => order,
+        Err(err) => return err.to_response(),
+    };
+    if let Err(err) = req.state().rules.check_place(&order) {
+        return err.to_response();
+    }
+    let table_id: String = order.table_id.clone();
+    let by: Actor = actor(&req);
+    let result = blocking(&req.state().db, move |db| db.place(order, &by)).await;
+    respond(track_order(&req, Operation::Place, &table_id, result))
+}
+
+async fn remove_by_tableid_and_item(mut req: tide::Request<State>) -> tide::Result {
+    let order: DeleteOrder = match parse_body(&mut req).await {
+        Ok(order) => order,
+        Err(err) => return err.to_response(),
+    };
+    if let Err(err) = req.state().rules.check_delete(&order) {
+        return err.to_response();
+    }
+    let table_id: String = order.table_id.clone();
+    let by: Actor = actor(&req);
+    let result = blocking(&req.state().db, move |db| db.delete(order, &by)).await;
+    respond(track_order(&req, Operation::Delete, &table_id, result))
+}
+
+async fn update_by_tableid_and_item(mut req: tide::Request<State>) -> tide::Result {
+    let order: UpdateOrder = match parse_body(&mut req).await {
+        Ok(order) => order,
+        Err(err) => return err.to_response(),
+    };
+    if let Err(err) = req.state().rules.check_update(&order) {
+        return err.to_response();
+    }
+    let table_id: String = order.table_id.clone();
+    let by: Actor = actor(&req);
+    let result = blocking(&req.state().db, move |db| db.update(order, &by)).await;
+    respond(track_order(&req, Operation::Update, &table_id, result))
+}
+
+async fn void_item(mut req: tide::Request<State>) -> tide::Result {
+    let order: DeleteOrder = match parse_body(&mut req).await {
+        Ok(order) => order,
+        Err(err) => return err.to_response(),
+    };
+    if let Err(err) = req.state().rules.check_delete(&order) {
+        return err.to_response();
+    }
+    let table_id: String = order.table_id.clone();
+    let by: Actor = actor(&req);
+    let result = blocking(&req.state().db, move |db| db.void_item(order, &by)).await;
+    respond(track_order(&req, Operation::Void, &table_id, result))
+}
+
+async fn query_history(req: tide::Request<State>) -> tide::Result {
+    let table_id: String = req.param("tableid")?.to_string();
+
+    respond(blocking(&req.state().db, move |db| db.history(&table_id, None)).await)
+}
+
+async fn query_history_of_item(req: tide::Request<State>) -> tide::Result {
+    let table_id: String = req.param("tableid")?.to_string();
+    let item: String = req.param("item")?.to_string();
+
+    respond(blocking(&req.state().db, move |db| db.history(&table_id, Some(&item))).await)
+}
+
+async fn query_report(req: tide::Request<State>) -> tide::Result {
+    respond(blocking(&req.state().db, |db| db.report()).await)
+}
+
+async fn query_menu(req: tide::Request<State>) -> tide::Result {
+    respond(blocking(&req.state().db, |db| db.menu()).await)
+}
+
+async fn query_menu_item(req: tide::Request<State>) -> tide::Result {
+    let name: String = req.param("name")?.to_string();
+
+    respond(blocking(&req.state().db, move |db| db.menu_item(&name)).await)
+}
+
+async fn add_menu_item(mut req: tide::Request<State>) -> tide::Result {
+    let item: MenuItem = match parse_body(&mut req).await {
+        Ok(item) => item,
+        Err(err) => return err.to_response(),
+    };
+    if let Err(err) = req.state().rules.check_menu_item(&item) {
+        return err.to_response();
+    }
+    respond(blocking(&req.state().db, move |db| db.add_menu_item(item)).await)
+}
+
+async fn update_menu_item(mut req: tide::Request<State>) -> tide::Result {
+    let item: MenuItem = match parse_body(&mut req).await {
+        Ok(item) => item,
+        Err(err) => return err.to_response(),
+    };
+    if let Err(err) = req.state().rules.check_menu_item(&item) {
+        return err.to_response();
+    }
+    respond(blocking(&req.state().db, move |db| db.update_menu_item(item)).await)
+}
+
+async fn remove_menu_item(req: tide::Request<State>) -> tide::Result {
+    let name: String = req.param("name")?.to_string();
+
+    respond(blocking(&req.state().db, move |db| db.delete_menu_item(&name)).await)
+}
+
+async fn advance_item(mut req: tide::Request<State>, operation: Operation, to: ItemStatus) -> tide::Result {
+    let order: KitchenOrder = match parse_body(&mut req).await {
+        Ok(order) => order,
+        Err(err) => return err.to_response(),
+    };
+    if let Err(err) = req.state().rules.check_kitchen(&order) {
+        return err.to_response();
     }
+    let table_id: String = order.table_id.clone();
+    let by: Actor = actor(&req);
+    let result = blocking(&req.state().db, move |db| kitchen::advance(db, order, to, &by)).await;
+    respond(track_order(&req, operation, &table_id, result))
+}
+
+async fn start_item(req: tide::Request<State>) -> tide::Result {
+    advance_item(req, Operation::Start, ItemStatus::Process).await
+}
+
+async fn finish_item(req: tide::Request<State>) -> tide::Result {
+    advance_item(req, Operation::Finish, ItemStatus::Done).await
+}
+
+async fn reject_item(req: tide::Request<State>) -> tide::Result {
+    advance_item(req, Operation::Reject, ItemStatus::Rejected).await
 }
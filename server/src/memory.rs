@@ -0,0 +1,521 @@
+use crate::db::{self, DB};
+use crate::error::ServerError;
+use crate::kitchen::Kitchen;
+use crate::menu;
+use crate::order_type::{
+    Actor, DeleteOrder, History, HistoryEntry, Item, ItemStatus, MenuItem, Order, PlaceOrder,
+    Report, StatusCount, TableSession, TableStatus, UpdateOrder,
+};
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tide::log::info;
+
+struct TableRow {
+    // taken in order of insertion, the latest session of a table being the one with the highest id
+    session_id: i64,
+    opened_at: DateTime<Utc>,
+    closed_at: Option<DateTime<Utc>>,
+    table_id: String,
+    status: TableStatus,
+    by: Actor,
+}
+
+#[derive(Clone)]
+struct ItemRow {
+    session_id: i64,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    table_id: String,
+    item: String,
+    amount: i32,
+    status: ItemStatus,
+    // the latest action on the item
+    by: Actor,
+}
+
+#[derive(Default)]
+struct Store {
+    next_session: i64,
+    tablet: Vec<TableRow>,
+    items: Vec<ItemRow>,
+    item_history: Vec<ItemRow>,
+    menu: Vec<MenuItem>,
+}
+
+// In-memory counterpart of table 'tablet', 'items', 'item_history' and 'menu', sharing the order rules of Dbio
+#[derive(Clone)]
+pub struct MemDb {
+    store: Arc<Mutex<Store>>,
+    kitchen: Kitchen,
+}
+
+impl TableRow {
+    fn to_session(&self) -> TableSession {
+        TableSession {
+            table_id: self.table_id.clone(),
+            opened_at: self.opened_at,
+            closed_at: self.closed_at,
+            status: self.status,
+            staff: Some(self.by.staff.clone()),
+            device: self.by.device.clone(),
+        }
+    }
+}
+
+impl ItemRow {
+    fn to_item(&self) -> Item {
+        Item {
+            name: self.item.clone(),
+            amount: self.amount,
+            status: self.status,
+        }
+    }
+
+    fn to_entry(&self) -> HistoryEntry {
+        HistoryEntry {
+            opened_at: self.created_at,
+            updated_at: self.updated_at,
+            item: self.item.clone(),
+            amount: self.amount,
+            status: self.status,
+            staff: Some(self.by.staff.clone()),
+            device: self.by.device.clone(),
+        }
+    }
+}
+
+impl Store {
+    fn on_menu(&self, name: &str) -> bool {
+        self.menu.iter().any(|dish| dish.name == name && dish.active)
+    }
+
+    fn latest_session(&self, table_id: &str) -> Option<&TableRow> {
+        self.tablet
+            .iter()
+            .filter(|row| row.table_id == table_id)
+            .max_by_key(|row| row.session_id)
+    }
+
+    fn open_session(&mut self, opened_at: DateTime<Utc>, table_id: &str, by: &Actor) -> i64 {
+        self.next_session += 1;
+        self.tablet.push(TableRow {
+            session_id: self.next_session,
+            opened_at,
+            closed_at: None,
+            table_id: table_id.to_string(),
+            status: TableStatus::Open,
+            by: by.clone(),
+        });
+        self.next_session
+    }
+
+    // Insert a new item as 'New', to be sent to the kitchen queue once the store is unlocked
+    fn queue_item(&mut self, row: ItemRow) {
+        self.items.push(row.clone());
+        self.item_history.push(row);
+    }
+
+    fn session_items(&self, session_id: i64, item: Option<&str>) -> Vec<Item> {
+        self.items
+            .iter()
+            .filter(|row| row.session_id == session_id)
+            .filter(|row| item.is_none_or(|name| row.item == name))
+            .map(ItemRow::to_item)
+            .collect()
+    }
+
+    fn find_item_mut(&mut self, session_id: i64, item: &str) -> Option<&mut ItemRow> {
+        self.items
+            .iter_mut()
+            .find(|row| row.session_id == session_id && row.item == item)
+    }
+
+    // Move an item of a session, answering whether it reached `to`
+    fn advance_item(&mut self, session_id: i64, item: &str, to: ItemStatus, by: &Actor) -> bool {
+        let updated = match self.find_item_mut(session_id, item) {
+            Some(row) if row.status == to => return true,
+            Some(row) if row.status.can_advance(to) => {
+                row.updated_at = Utc::now();
+                row.status = to;
+                row.by = by.clone();
+                row.clone()
+            }
+            _ => return false,
+        };
+        self.item_history.push(updated);
+        self.update_table_status(session_id);
+        true
+    }
+
+    fn update_table_status(&mut self, session_id: i64) {
+        let open = self.items.iter().any(|row| {
+            row.session_id == session_id && (row.status == ItemStatus::New || row.status == ItemStatus::Process)
+        });
+
+        if let Some(session) = self.tablet.iter_mut().find(|row| row.session_id == session_id) {
+            if open {
+                session.status = TableStatus::Open;
+            } else {
+                session.closed_at = Some(Utc::now());
+                session.status = TableStatus::Close;
+            }
+        }
+    }
+}
+
+impl MemDb {
+    pub fn new(kitchen: Kitchen) -> MemDb {
+        let store = Store { menu: menu::default_menu(), ..Store::default() };
+        MemDb { store: Arc::new(Mutex::new(store)), kitchen }
+    }
+
+    fn store(&self) -> Result<MutexGuard<'_, Store>, ServerError> {
+        self.store
+            .lock()
+            .map_err(|_| ServerError::Storage("In-memory store is poisoned".to_string()))
+    }
+}
+
+impl DB for MemDb {
+    fn init(&self) -> Result<(), ServerError> {
+        self.store().map(|_| ())
+    }
+
+    // Nothing to migrate, the store only has to be usable
+    fn pending_migrations(&self) -> Result<Vec<String>, ServerError> {
+        self.store().map(|_| Vec::new())
+    }
+
+    fn place(&self, order: PlaceOrder, by: &Actor) -> Result<Order, ServerError> {
+        let mut store = self.store()?;
+
+        menu::check_order(&order.items, |name| Ok(store.on_menu(name)))?;
+        if let Some(session) = store.latest_session(&order.table_id) {
+            if session.status == TableStatus::Open {
+                return Err(ServerError::Conflict(format!("Duplicated! There has been an order of table_id: {}, being served in the queue", session.table_id)));
+            }
+        }
+
+        let session_id: i64 = store.open_session(order.created_at, &order.table_id, by);
+        for elem in &order.items {
+            let row = ItemRow {
+                session_id,
+                created_at: order.created_at,
+                updated_at: order.created_at,
+                table_id: order.table_id.clone(),
+                item: elem.name.clone(),
+                amount: elem.amount,
+                status: ItemStatus::New,
+                by: by.clone(),
+            };
+            store.queue_item(row);
+        }
+
+        let session = store.latest_session(&order.table_id).unwrap().to_session();
+        let items = store.session_items(session_id, None);
+        drop(store);
+
+        for elem in order.items {
+            self.kitchen.enqueue(session_id, &session.table_id, &elem.name);
+        }
+        Ok(Order { queried_at: Utc::now(), table: session, items })
+    }
+
+    fn update(&self, order: UpdateOrder, by: &Actor) -> Result<Order, ServerError> {
+        let mut store = self.store()?;
+
+        menu::check_order(&order.items, |name| Ok(store.on_menu(name)))?;
+        let (session_id, session) = match store.latest_session(&order.table_id) {
+            Some(session) => (session.session_id, session.to_session()),
+            None => return Err(ServerError::NotFound(format!("Update Order Failed! Non-existent table_id: {}", order.table_id))),
+        };
+        if session.status == TableStatus::Close {
+            return Err(ServerError::InvalidState(format!("Update Order Failed! table_id: {} was done. Please launch a new order", session.table_id)));
+        }
+
+        let updated_at: DateTime<Utc> = order.updated_at;
+        let mut queued: Vec<String> = Vec::new();
+        for elem in order.items {
+            let existing = store
+                .find_item_mut(session_id, &elem.name)
+                .map(|row| {
+                    // only items still in 'New' state are allowed to be updated
+                    if row.status == ItemStatus::New {
+                        row.updated_at = updated_at;
+                        row.amount = elem.amount;
+                        row.by = by.clone();
+                    }
+                    row.clone()
+                });
+            match existing {
+                Some(row) => {
+                    if row.status == ItemStatus::New {
+                        store.item_history.push(row);
+                    }
+                }
+                None => {
+                    let row = ItemRow {
+                        session_id,
+                        created_at: session.opened_at,
+                        updated_at,
+                        table_id: session.table_id.clone(),
+                        item: elem.name.clone(),
+                        amount: elem.amount,
+                        status: ItemStatus::New,
+                        by: by.clone(),
+                    };
+                    store.queue_item(row);
+                    queued.push(elem.name);
+                }
+            }
+        }
+
+        let items = store.session_items(session_id, None);
+        drop(store);
+
+        for item in queued {
+            self.kitchen.enqueue(session_id, &session.table_id, &item);
+        }
+        Ok(Order { queried_at: Utc::now(), table: session, items })
+    }
+
+    fn delete(&self, order: DeleteOrder, by: &Actor) -> Result<Item, ServerError> {
+        let mut store = self.store()?;
+
+        let session_id = store.latest_session(&order.table_id).map(|session| session.session_id);
+        let row = match session_id.and_then(|session_id| store.find_item_mut(session_id, &order.item)) {
+            Some(row) => row.clone(),
+            None => return Err(ServerError::NotFound(format!("Delete Order Failed! No item: {} of table_id: {}", order.item, order.table_id))),
+        };
+
+        match row.status {
+            ItemStatus::New => {
+                store.items.retain(|elem| !(elem.session_id == row.session_id && elem.item == row.item));
+                store.item_history.push(ItemRow {
+                    updated_at: order.deleted_at,
+                    status: ItemStatus::Deleted,
+                    by: by.clone(),
+                    ..row.clone()
+                });
+                store.update_table_status(row.session_id);
+                Ok(Item { name: row.item, amount: row.amount, status: ItemStatus::Deleted })
+            }
+            ItemStatus::Process => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} is cooking", row.item, row.table_id))),
+            ItemStatus::Done => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was done", row.item, row.table_id))),
+            ItemStatus::Deleted => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was deleted", row.item, row.table_id))),
+            ItemStatus::Rejected => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was rejected by the kitchen", row.item, row.table_id))),
+        }
+    }
+
+    fn void_item(&self, order: DeleteOrder, by: &Actor) -> Result<Item, ServerError> {
+        let mut store = self.store()?;
+
+        let session_id = store.latest_session(&order.table_id).map(|session| session.session_id);
+        let row = match session_id.and_then(|session_id| store.find_item_mut(session_id, &order.item)) {
+            Some(row) => row.clone(),
+            None => return Err(ServerError::NotFound(format!("Void Order Failed! No item: {} of table_id: {}", order.item, order.table_id))),
+        };
+
+        match row.status {
+            ItemStatus::Done => {
+                store.items.retain(|elem| !(elem.session_id == row.session_id && elem.item == row.item));
+                store.item_history.push(ItemRow {
+                    updated_at: order.deleted_at,
+                    status: ItemStatus::Deleted,
+                    by: by.clone(),
+                    ..row.clone()
+                });
+                Ok(Item { name: row.item, amount: row.amount, status: ItemStatus::Deleted })
+            }
+            status => Err(ServerError::InvalidState(format!("Void Order Failed! The item: {} of table_id: {} is {}, only served items are voided", row.item, row.table_id, status))),
+        }
+    }
+
+    fn query_by_tableid(&self, table_id: String) -> Result<Order, ServerError> {
+        let store = self.store()?;
+
+        match store.latest_session(&table_id) {
+            Some(session) => {
+                let items = store.session_items(session.session_id, None);
+                let session = session.to_session();
+                Ok(Order { queried_at: Utc::now(), table: session, items })
+            }
+            None => Err(ServerError::NotFound(format!("No Order of table id: {}", table_id))),
+        }
+    }
+
+    fn query_by_tableid_and_item(&self, table_id: String, item: String) -> Result<Order, ServerError> {
+        let store = self.store()?;
+
+        match store.latest_session(&table_id) {
+            Some(session) => {
+                let items = store.session_items(session.session_id, Some(&item));
+                let session = session.to_session();
+                if items.is_empty() {
+                    Err(ServerError::NotFound(format!("No item: {} of table id: {}", item, table_id)))
+                } else {
+                    Ok(Order { queried_at: Utc::now(), table: session, items })
+                }
+            }
+            None => Err(ServerError::NotFound(format!("No Order of table id: {}", table_id))),
+        }
+    }
+
+    fn advance_item(&self, table_id: &str, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError> {
+        let mut store = self.store()?;
+
+        match store.latest_session(table_id) {
+            Some(session) => {
+                let session_id: i64 = session.session_id;
+                Ok(store.advance_item(session_id, item, to, by))
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn advance_session_item(&self, session_id: i64, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError> {
+        let mut store = self.store()?;
+        Ok(store.advance_item(session_id, item, to, by))
+    }
+
+    fn open_orders(&self) -> Result<Vec<(i64, Order)>, ServerError> {
+        let store = self.store()?;
+
+        Ok(store
+            .tablet
+            .iter()
+            .filter(|row| row.status == TableStatus::Open)
+            .map(|row| (row.session_id, Order { queried_at: Utc::now(), table: row.to_session(), items: store.session_items(row.session_id, None) }))
+            .collect())
+    }
+
+    fn history(&self, table_id: &str, item: Option<&str>) -> Result<History, ServerError> {
+        let store = self.store()?;
+
+        let entries: Vec<HistoryEntry> = store
+            .item_history
+            .iter()
+            .filter(|row| row.table_id == table_id && item.is_none_or(|name| row.item == name))
+            .map(ItemRow::to_entry)
+            .collect();
+        if entries.is_empty() {
+            return Err(ServerError::NotFound(format!("No history of table id: {}", table_id)));
+        }
+        Ok(History { queried_at: Utc::now(), table_id: table_id.to_string(), entries })
+    }
+
+    fn menu(&self) -> Result<Vec<MenuItem>, ServerError> {
+        Ok(self.store()?.menu.clone())
+    }
+
+    fn menu_item(&self, name: &str) -> Result<MenuItem, ServerError> {
+        match self.store()?.menu.iter().find(|dish| dish.name == name) {
+            Some(dish) => Ok(dish.clone()),
+            None => Err(ServerError::NotFound(format!("No menu item: {}", name))),
+        }
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<MenuItem, ServerError> {
+        menu::check_entry(&item)?;
+        let mut store = self.store()?;
+
+        if store.menu.iter().any(|dish| dish.name == item.name) {
+            return Err(ServerError::Conflict(format!("Duplicated! The menu has an item: {} already", item.name)));
+        }
+        store.menu.push(item.clone());
+        Ok(item)
+    }
+
+    fn update_menu_item(&self, item: MenuItem) -> Result<MenuItem, ServerError> {
+        menu::check_entry(&item)?;
+        let mut store = self.store()?;
+
+        match store.menu.iter_mut().find(|dish| dish.name == item.name) {
+            Some(dish) => {
+                *dish = item.clone();
+                Ok(item)
+            }
+            None => Err(ServerError::NotFound(format!("No menu item: {}", item.name))),
+        }
+    }
+
+    fn delete_menu_item(&self, name: &str) -> Result<MenuItem, ServerError> {
+        let mut store = self.store()?;
+
+        match store.menu.iter().position(|dish| dish.name == name) {
+            Some(idx) => Ok(store.menu.remove(idx)),
+            None => Err(ServerError::NotFound(format!("No menu item: {}", name))),
+        }
+    }
+
+    fn report(&self) -> Result<Report, ServerError> {
+        let store = self.store()?;
+
+        let mut items: Vec<StatusCount> = Vec::new();
+        for row in store.items.iter() {
+            match items.iter_mut().find(|count| count.status == row.status) {
+                Some(count) => {
+                    count.items += 1;
+                    count.amount += row.amount as i64;
+                }
+                None => items.push(StatusCount { status: row.status, items: 1, amount: row.amount as i64 }),
+            }
+        }
+        items.sort_by_key(|count| count.status.to_string());
+        Ok(Report {
+            reported_at: Utc::now(),
+            open_tables: store.tablet.iter().filter(|row| row.status == TableStatus::Open).count() as i64,
+            items,
+        })
+    }
+
+    fn cook_times(&self) -> Result<Vec<f64>, ServerError> {
+        let rows = self
+            .store()?
+            .item_history
+            .iter()
+            .map(|row| (format!("{}/{}", row.session_id, row.item), row.status, row.updated_at))
+            .collect();
+        Ok(db::cook_times(rows))
+    }
+
+    fn check_table_status(&self) -> Result<bool, ServerError> {
+        let store = self.store()?;
+        let mut is_empty: bool = true;
+
+        for row in store.tablet.iter().filter(|row| row.status == TableStatus::Open) {
+            is_empty = false;
+            info!("[CHECK_TABLE_STATUS] table_id: {} is still being served, status: {}", row.table_id, row.status);
+        }
+
+        Ok(is_empty)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::order_type::ItemPair;
+
+    fn storage() -> db::Storage {
+        Arc::new(MemDb::new(Kitchen::manual()))
+    }
+
+    db::storage_suite!(storage);
+
+    #[test]
+    fn test_memdb_store_given_item_deleted_and_placed_again_when_inspected_then_rows_keyed_by_session() {
+        let db: MemDb = MemDb::new(Kitchen::manual());
+        let place = || PlaceOrder { created_at: Utc::now(), table_id: "1".to_string(), items: vec![ItemPair { name: "A".to_string(), amount: 1 }] };
+        db.place(place(), &Actor::system()).unwrap();
+        db.advance_item("1", "A", ItemStatus::Rejected, &Actor::system()).unwrap();
+        db.place(place(), &Actor::system()).unwrap();
+
+        let store = db.store().unwrap();
+        let sessions: Vec<i64> = store.tablet.iter().map(|session| session.session_id).collect();
+        assert_eq!(2, sessions.len());
+        assert_ne!(sessions[0], sessions[1]);
+        assert_eq!(sessions, store.items.iter().map(|row| row.session_id).collect::<Vec<i64>>());
+        assert_eq!(vec![sessions[0], sessions[0], sessions[1]], store.item_history.iter().map(|row| row.session_id).collect::<Vec<i64>>());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::error::ServerError;
+use crate::order_type::{ItemPair, MenuItem};
+
+// Dishes 'A' to 'Z' served before the menu existed, seeded into every new store.
+// Keep in line with migrations/0003_menu.sql and migrations/sqlite/0001_initial.sql
+pub fn default_menu() -> Vec<MenuItem> {
+    (1..=26)
+        .map(|n: i32| {
+            let name: String = char::from(b'@' + n as u8).to_string();
+            MenuItem {
+                description: format!("Dish {}", name),
+                name,
+                category: "main".to_string(),
+                cook_time: 5 + (n * 7) % 11,
+                active: true,
+            }
+        })
+        .collect()
+}
+
+pub fn check_entry(item: &MenuItem) -> Result<(), ServerError> {
+    if item.name.trim().is_empty() {
+        return Err(ServerError::Validation("Menu item name should not be empty".to_string()));
+    }
+    if item.cook_time < 0 {
+        return Err(ServerError::Validation(format!("Menu item: {} has a negative cook time: {}", item.name, item.cook_time)));
+    }
+    Ok(())
+}
+
+// Every item of an order has to be an active dish of the menu
+pub fn check_order<F>(items: &[ItemPair], mut on_menu: F) -> Result<(), ServerError>
+where
+    F: FnMut(&str) -> Result<bool, ServerError>,
+{
+    let mut unknown: Vec<&str> = Vec::new();
+    for elem in items {
+        if !on_menu(&elem.name)? {
+            unknown.push(&elem.name);
+        }
+    }
+
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(ServerError::Validation(format!("Not on the menu: {}", unknown.join(", "))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_menu_default_menu_given_legacy_items_when_seeded_then_a_to_z_cooked_in_5_to_15_secs() {
+        let menu: Vec<MenuItem> = default_menu();
+        assert_eq!(26, menu.len());
+        assert_eq!("A", menu[0].name);
+        assert_eq!("Z", menu[25].name);
+        assert!(menu.iter().all(|item| (5..=15).contains(&item.cook_time) && item.active));
+    }
+
+    #[test]
+    fn test_menu_check_entry_given_invalid_fields_when_checked_then_validation_returned() {
+        let mut item: MenuItem = default_menu().remove(0);
+        assert!(check_entry(&item).is_ok());
+        item.cook_time = -1;
+        assert!(matches!(check_entry(&item), Err(ServerError::Validation(_))));
+        item.cook_time = 1;
+        item.name = " ".to_string();
+        assert!(matches!(check_entry(&item), Err(ServerError::Validation(_))));
+    }
+
+    #[test]
+    fn test_menu_check_order_given_items_off_the_menu_when_checked_then_all_listed() {
+        let items: Vec<ItemPair> = ["A", "a", "B", "?"]
+            .iter()
+            .map(|name| ItemPair { name: name.to_string(), amount: 1 })
+            .collect();
+        let res = check_order(&items, |name| Ok(name == "A" || name == "B"));
+        match res {
+            Err(ServerError::Validation(msg)) => assert_eq!("Not on the menu: a, ?", msg),
+            _ => panic!("[TEST::MENU_CHECK_ORDER] Validation error expected"),
+        }
+        assert!(check_order(&items[..1], |_| Ok(true)).is_ok());
+    }
+}
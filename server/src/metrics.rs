@@ -0,0 +1,219 @@
+use crate::db::Storage;
+use crate::error::ServerError;
+use crate::kitchen::Kitchen;
+use crate::order_type::ItemStatus;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use strum_macros::Display;
+use tide::{Middleware, Next, Request};
+
+// Upper bounds of the histogram buckets, in secs
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+const COOK_BUCKETS: &[f64] = &[5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+
+// Order operations counted by outcome
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Operation {
+    Place,
+    Update,
+    Delete,
+    Void,
+    // the kitchen moves of an item
+    Start,
+    Finish,
+    Reject,
+}
+
+impl Operation {
+    pub fn action(&self) -> &'static str {
+        match self {
+            Operation::Place => "Place Order",
+            Operation::Update => "Update Order",
+            Operation::Delete => "Delete Order",
+            Operation::Void => "Void Item",
+            Operation::Start => "Start Item",
+            Operation::Finish => "Finish Item",
+            Operation::Reject => "Reject Item",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    // per bucket, not cumulative, the last one past every bound
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram { bounds, counts: vec![0; bounds.len() + 1], sum: 0.0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let index: usize = self.bounds.iter().position(|bound| value <= *bound).unwrap_or(self.bounds.len());
+        self.counts[index] += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative: u64 = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{}le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+        cumulative += self.counts[self.bounds.len()];
+        let _ = writeln!(out, "{}_bucket{{{}le=\"+Inf\"}} {}", name, labels, cumulative);
+        let labels: &str = labels.trim_end_matches(',');
+        let labels: String = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, cumulative);
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    // (operation, outcome) -> count, the outcome being "ok" or the code of the error answered
+    orders: BTreeMap<(String, String), u64>,
+    // (method, status) -> latency
+    requests: BTreeMap<(String, u16), Histogram>,
+}
+
+// Counters and histograms of the server since its start, rendered in the Prometheus text format by
+// `/metrics` along with the gauges read from the storage and the kitchen at scrape time
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn order<T>(&self, operation: Operation, result: &Result<T, ServerError>) {
+        let outcome: &str = match result {
+            Ok(_) => "ok",
+            Err(err) => err.code(),
+        };
+        let mut registry = self.registry.lock().unwrap();
+        *registry.orders.entry((operation.to_string(), outcome.to_string())).or_insert(0) += 1;
+    }
+
+    fn request(&self, method: &str, status: u16, secs: f64) {
+        let mut registry = self.registry.lock().unwrap();
+        registry
+            .requests
+            .entry((method.to_string(), status))
+            .or_insert_with(|| Histogram::new(LATENCY_BUCKETS))
+            .observe(secs);
+    }
+
+    pub fn render(&self, db: &Storage, kitchen: &Kitchen) -> Result<String, ServerError> {
+        let report = db.report()?;
+        let mut cook: Histogram = Histogram::new(COOK_BUCKETS);
+        for secs in db.cook_times()? {
+            cook.observe(secs);
+        }
+        let mut out: String = String::new();
+        {
+            let registry = self.registry.lock().unwrap();
+            header(&mut out, "restaurant_orders_total", "counter", "Order operations by outcome, the error code when refused");
+            for ((operation, outcome), count) in registry.orders.iter() {
+                let _ = writeln!(out, "restaurant_orders_total{{operation=\"{}\",outcome=\"{}\"}} {}", operation, outcome, count);
+            }
+            header(&mut out, "restaurant_http_request_duration_seconds", "histogram", "Time to answer the requests, by method and status");
+            for ((method, status), histogram) in registry.requests.iter() {
+                histogram.render(&mut out, "restaurant_http_request_duration_seconds", &format!("method=\"{}\",status=\"{}\",", method, status));
+            }
+        }
+        header(&mut out, "restaurant_cook_duration_seconds", "histogram", "Time from placing an item to serving it, from the item history");
+        cook.render(&mut out, "restaurant_cook_duration_seconds", "");
+
+        header(&mut out, "restaurant_open_tables", "gauge", "Tables being served");
+        let _ = writeln!(out, "restaurant_open_tables {}", report.open_tables);
+        header(&mut out, "restaurant_items", "gauge", "Items of the orders in each status");
+        for status in [ItemStatus::New, ItemStatus::Process, ItemStatus::Done, ItemStatus::Deleted, ItemStatus::Rejected] {
+            let items: i64 = report.items.iter().find(|count| count.status == status).map_or(0, |count| count.items);
+            let _ = writeln!(out, "restaurant_items{{status=\"{}\"}} {}", status, items);
+        }
+        header(&mut out, "restaurant_kitchen_queued", "gauge", "Items waiting for a cooking slot");
+        let _ = writeln!(out, "restaurant_kitchen_queued {}", kitchen.stats().queued);
+
+        if let Some(pool) = db.pool_stats() {
+            header(&mut out, "restaurant_db_connections", "gauge", "Connections of the database pool, by state");
+            let _ = writeln!(out, "restaurant_db_connections{{state=\"active\"}} {}", pool.connections - pool.idle_connections);
+            let _ = writeln!(out, "restaurant_db_connections{{state=\"idle\"}} {}", pool.idle_connections);
+            header(&mut out, "restaurant_db_connections_max", "gauge", "Size limit of the database pool");
+            let _ = writeln!(out, "restaurant_db_connections_max {}", pool.max_size);
+        }
+        Ok(out)
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// Times every request for `restaurant_http_request_duration_seconds`
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Metrics {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let method: String = req.method().to_string();
+        let start: Instant = Instant::now();
+        let res = next.run(req).await;
+        self.request(&method, res.status().into(), start.elapsed().as_secs_f64());
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::MemDb;
+    use crate::order_type::{Actor, ItemPair, KitchenOrder, PlaceOrder};
+    use chrono::Utc;
+
+    #[test]
+    fn test_metrics_histogram_given_values_when_rendered_then_buckets_cumulative() {
+        let mut histogram: Histogram = Histogram::new(&[1.0, 5.0]);
+        for value in &[0.5, 3.0, 4.0, 7.0] {
+            histogram.observe(*value);
+        }
+        let mut out: String = String::new();
+        histogram.render(&mut out, "cook", "");
+        assert_eq!("cook_bucket{le=\"1\"} 1\ncook_bucket{le=\"5\"} 3\ncook_bucket{le=\"+Inf\"} 4\ncook_sum 14.5\ncook_count 4\n", out);
+    }
+
+    #[test]
+    fn test_metrics_render_given_orders_and_requests_when_scraped_then_each_family_listed() {
+        let kitchen: Kitchen = Kitchen::manual();
+        let db: Storage = Arc::new(MemDb::new(kitchen.clone()));
+        let metrics: Metrics = Metrics::new();
+        let order = PlaceOrder { created_at: Utc::now(), table_id: "1".to_string(), items: vec![ItemPair { name: "A".to_string(), amount: 1 }] };
+        metrics.order(Operation::Place, &db.place(order, &Actor::system()));
+        let order = PlaceOrder { created_at: Utc::now(), table_id: "1".to_string(), items: vec![ItemPair { name: "B".to_string(), amount: 1 }] };
+        metrics.order(Operation::Place, &db.place(order, &Actor::system()));
+        for to in [ItemStatus::Process, ItemStatus::Done] {
+            let order = KitchenOrder { table_id: "1".to_string(), item: "A".to_string() };
+            crate::kitchen::advance(&db, order, to, &Actor::system()).unwrap();
+        }
+        metrics.request("GET", 200, 0.02);
+
+        let out: String = metrics.render(&db, &kitchen).unwrap();
+        assert!(out.contains("restaurant_orders_total{operation=\"place\",outcome=\"ok\"} 1\n"));
+        assert!(out.contains("restaurant_orders_total{operation=\"place\",outcome=\"conflict\"} 1\n"));
+        assert!(out.contains("restaurant_http_request_duration_seconds_bucket{method=\"GET\",status=\"200\",le=\"0.025\"} 1\n"));
+        assert!(out.contains("restaurant_http_request_duration_seconds_count{method=\"GET\",status=\"200\"} 1\n"));
+        assert!(out.contains("restaurant_cook_duration_seconds_count 1\n"));
+        assert!(out.contains("restaurant_open_tables 0\n"));
+        assert!(out.contains("restaurant_items{status=\"Done\"} 1\n"));
+        assert!(out.contains("restaurant_kitchen_queued 0\n"));
+        assert!(!out.contains("restaurant_db_connections"));
+    }
+}
@@ -0,0 +1,119 @@
+use postgres::{Client, Error, GenericClient};
+use tide::log::info;
+
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+    // false for the statements PostgreSQL < 12 refuses within a transaction block, e.g. ALTER TYPE ... ADD VALUE
+    pub transactional: bool,
+}
+
+// Applied in order of version, never edit a migration once released
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("../migrations/0001_initial.sql"),
+        transactional: true,
+    },
+    Migration {
+        version: 2,
+        name: "item_rejected",
+        sql: include_str!("../migrations/0002_item_rejected.sql"),
+        transactional: false,
+    },
+    Migration {
+        version: 3,
+        name: "menu",
+        sql: include_str!("../migrations/0003_menu.sql"),
+        transactional: true,
+    },
+    Migration {
+        version: 4,
+        name: "actor",
+        sql: include_str!("../migrations/0004_actor.sql"),
+        transactional: true,
+    },
+];
+
+// Arbitrary key of the advisory lock serializing concurrent runners
+const MIGRATION_LOCK: i64 = 0x7265_7374_6175;
+
+// Apply the pending migrations, each in its own transaction unless it is not `transactional`, and
+// return the applied ones
+pub fn migrate(client: &mut Client) -> Result<Vec<&'static Migration>, Error> {
+    client.batch_execute(
+        "CREATE TABLE IF NOT EXISTS SCHEMA_VERSION (
+            version INTEGER PRIMARY KEY,
+            name VARCHAR NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )",
+    )?;
+
+    let mut applied: Vec<&'static Migration> = Vec::new();
+    for migration in MIGRATIONS {
+        if !migration.transactional {
+            if apply_outside_transaction(client, migration)? {
+                applied.push(migration);
+            }
+            continue;
+        }
+        let mut tx = client.transaction()?;
+        tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK])?;
+        let row = tx.query_opt("SELECT version FROM schema_version WHERE version = $1", &[&migration.version])?;
+        if row.is_none() {
+            info!("[MIGRATE] Applying {:04}_{}", migration.version, migration.name);
+            tx.batch_execute(migration.sql)?;
+            tx.execute("INSERT INTO schema_version(version, name) VALUES ($1, $2)", &[&migration.version, &migration.name])?;
+            applied.push(migration);
+        }
+        tx.commit()?;
+    }
+
+    Ok(applied)
+}
+
+// The statements of the migration run one by one under the session-level advisory lock, released
+// whatever the outcome. They have to be safe to run again, a failure leaves the migration unrecorded
+fn apply_outside_transaction(client: &mut Client, migration: &'static Migration) -> Result<bool, Error> {
+    client.execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK])?;
+    let applied = (|| -> Result<bool, Error> {
+        if client.query_opt("SELECT version FROM schema_version WHERE version = $1", &[&migration.version])?.is_some() {
+            return Ok(false);
+        }
+        info!("[MIGRATE] Applying {:04}_{} outside a transaction", migration.version, migration.name);
+        client.batch_execute(migration.sql)?;
+        client.execute("INSERT INTO schema_version(version, name) VALUES ($1, $2)", &[&migration.version, &migration.name])?;
+        Ok(true)
+    })();
+    client.execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK])?;
+    applied
+}
+
+// Version of the latest applied migration, 0 for a database never migrated
+pub fn current_version<C: GenericClient>(client: &mut C) -> Result<i32, Error> {
+    let row = client.query_one("SELECT COALESCE(MAX(version), 0) FROM schema_version", &[])?;
+    Ok(row.get(0))
+}
+
+// Migrations newer than the schema, all of them for a database never migrated
+pub fn pending<C: GenericClient>(client: &mut C) -> Result<Vec<&'static Migration>, Error> {
+    let exists: bool = client.query_one("SELECT to_regclass('schema_version') IS NOT NULL", &[])?.get(0);
+    let version: i32 = if exists { current_version(client)? } else { 0 };
+    Ok(MIGRATIONS.iter().filter(|migration| migration.version > version).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_migration_migrations_given_embedded_list_when_listed_then_versions_increase_from_one() {
+        for (idx, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(idx as i32 + 1, migration.version);
+            assert!(!migration.sql.trim().is_empty());
+            assert_eq!(migration.sql.contains("ADD VALUE"), !migration.transactional);
+        }
+    }
+}
@@ -1,10 +1,8 @@
 use chrono::{DateTime, Utc};
 use num_enum::IntoPrimitive;
 use num_enum::TryFromPrimitive;
-use postgres_types;
 use serde::{Deserialize, Serialize};
-use std::string::ToString;
-use strum_macros::Display;
+use strum_macros::{Display, EnumString};
 
 #[derive(Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
 #[repr(i8)]
@@ -16,7 +14,7 @@ pub enum ReqType {
     StatusItem = 4, // show specific item for a specified table
 }
 
-#[derive(Display, Debug, ToSql, FromSql, PartialEq)]
+#[derive(Display, EnumString, Debug, Clone, Copy, ToSql, FromSql, Serialize, Deserialize, PartialEq)]
 #[postgres(name = "tablestatus")]
 pub enum TableStatus {
     #[postgres(name = "Open")]
@@ -25,7 +23,7 @@ pub enum TableStatus {
     Close,
 }
 
-#[derive(Display, Debug, ToSql, FromSql, PartialEq)]
+#[derive(Display, EnumString, Debug, Clone, Copy, ToSql, FromSql, Serialize, Deserialize, PartialEq)]
 #[postgres(name = "itemstatus")]
 pub enum ItemStatus {
     #[postgres(name = "New")]
@@ -36,6 +34,21 @@ pub enum ItemStatus {
     Done,
     #[postgres(name = "Deleted")]
     Deleted,
+    #[postgres(name = "Rejected")]
+    Rejected,
+}
+
+impl ItemStatus {
+    // Transitions driven by the kitchen: New -> Process -> Done, or Rejected before being served
+    pub fn can_advance(&self, to: ItemStatus) -> bool {
+        matches!(
+            (self, to),
+            (ItemStatus::New, ItemStatus::Process)
+                | (ItemStatus::Process, ItemStatus::Done)
+                | (ItemStatus::New, ItemStatus::Rejected)
+                | (ItemStatus::Process, ItemStatus::Rejected)
+        )
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,6 +64,12 @@ pub struct PlaceOrder {
     pub items: Vec<ItemPair>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KitchenOrder {
+    pub table_id: String,
+    pub item: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteOrder {
     pub deleted_at: DateTime<Utc>,
@@ -65,60 +84,116 @@ pub struct UpdateOrder {
     pub items: Vec<ItemPair>,
 }
 
-impl PlaceOrder {
-    pub fn disp(&self) -> String {
-        let mut res = "".to_owned();
-        res.push_str("{ created_at: ");
-        res.push_str(&self.created_at.to_string());
-        res.push_str(", table_id: ");
-        res.push_str(&self.table_id);
-        res.push_str(", items: [ ");
-        for i in 0..self.items.len() {
-            res.push_str(&self.items[i].name);
-            res.push_str(":");
-            res.push_str(&self.items[i].amount.to_string());
-            if i != self.items.len() - 1 {
-                res.push_str(", ");
-            }
-        }
-        res.push_str(" ]");
-        res.push_str(" }");
-        res
-    }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Item {
+    pub name: String,
+    pub amount: i32,
+    pub status: ItemStatus,
 }
 
-impl UpdateOrder {
-    pub fn disp(&self) -> String {
-        let mut res = "".to_owned();
-        res.push_str("{ updated_at: ");
-        res.push_str(&self.updated_at.to_string());
-        res.push_str(", table_id: ");
-        res.push_str(&self.table_id);
-        res.push_str(", items: [ ");
-        for i in 0..self.items.len() {
-            res.push_str(&self.items[i].name);
-            res.push_str(":");
-            res.push_str(&self.items[i].amount.to_string());
-            if i != self.items.len() - 1 {
-                res.push_str(", ");
-            }
-        }
-        res.push_str(" ]");
-        res.push_str(" }");
-        res
-    }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableSession {
+    pub table_id: String,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub status: TableStatus,
+    // who placed the order opening the session, unknown for sessions recorded before
+    pub staff: Option<String>,
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Order {
+    pub queried_at: DateTime<Utc>,
+    pub table: TableSession,
+    pub items: Vec<Item>,
+}
+
+// Who performed an action: the account and, for requests sent with a bearer token, its device
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Actor {
+    pub staff: String,
+    pub device: Option<String>,
 }
 
-impl DeleteOrder {
-    pub fn disp(&self) -> String {
-        let mut res = "".to_owned();
-        res.push_str("{ deleted_at: ");
-        res.push_str(&self.deleted_at.to_string());
-        res.push_str(", table_id:");
-        res.push_str(&self.table_id);
-        res.push_str(", items: ");
-        res.push_str(&self.item);
-        res.push_str(" }");
-        res
+impl Actor {
+    // Actions of the server itself, e.g. the simulated kitchen and the startup recovery
+    pub fn system() -> Actor {
+        Actor { staff: "system".to_string(), device: None }
     }
 }
+
+// A row of item_history: an item as left by an action, along with who performed it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub opened_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub item: String,
+    pub amount: i32,
+    pub status: ItemStatus,
+    pub staff: Option<String>,
+    pub device: Option<String>,
+}
+
+// Every action on the items of a table, over all its sessions, oldest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct History {
+    pub queried_at: DateTime<Utc>,
+    pub table_id: String,
+    pub entries: Vec<HistoryEntry>,
+}
+
+// A dish of the menu, `cook_time` in seconds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MenuItem {
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    pub cook_time: i32,
+    pub active: bool,
+}
+
+// Items of every session in a given status, along with their total amount
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusCount {
+    pub status: ItemStatus,
+    pub items: i64,
+    pub amount: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    pub reported_at: DateTime<Utc>,
+    pub open_tables: i64,
+    pub items: Vec<StatusCount>,
+}
+
+// Sign-in of a tablet or screen, `username` and `password` as SHA-256 digests like the X-Auth headers
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Login {
+    pub username: String,
+    pub password: String,
+    pub device: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Token {
+    pub token: String,
+    pub token_type: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// A rule broken by a field of a request, e.g. `items[1].amount`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Violation {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+}
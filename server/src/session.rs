@@ -0,0 +1,174 @@
+use crate::auth::Role;
+use crate::error::ServerError;
+use crate::order_type::Token;
+use crate::settings;
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+// JWT header of every token, only HMAC-SHA256 signatures are issued and accepted
+const HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+// Claims of a bearer token, `iat` and `exp` in unix secs
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Claims {
+    pub sub: String,
+    pub role: Role,
+    pub device: String,
+    pub jti: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+// Signs and checks the bearer tokens handed out by `/api/auth/login`
+#[derive(Clone)]
+pub struct Sessions {
+    secret: Arc<Vec<u8>>,
+    ttl: i64,
+    // jti of the tokens refreshed or logged out, kept until they would have expired anyway
+    revoked: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl Sessions {
+    pub fn new(config: &settings::Auth) -> Sessions {
+        let secret: Vec<u8> = if config.get_token_secret().is_empty() {
+            let mut secret: Vec<u8> = vec![0; 32];
+            rand::thread_rng().fill_bytes(&mut secret);
+            secret
+        } else {
+            config.get_token_secret().into_bytes()
+        };
+        Sessions {
+            secret: Arc::new(secret),
+            ttl: config.get_token_ttl(),
+            revoked: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn issue(&self, name: &str, role: Role, device: &str) -> Result<Token, ServerError> {
+        let issued_at = Utc::now();
+        let expires_at = issued_at + Duration::seconds(self.ttl);
+        let claims: Claims = Claims {
+            sub: name.to_string(),
+            role,
+            device: device.to_string(),
+            jti: Uuid::new_v4().to_string(),
+            iat: issued_at.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+        let payload: String = serde_json::to_string(&claims).map_err(|err| ServerError::Validation(format!("Token Error: {}", err)))?;
+        let unsigned: String = format!("{}.{}", encode(HEADER), encode(payload));
+        let signature: String = encode(self.mac(&unsigned).finalize().into_bytes());
+        Ok(Token {
+            token: format!("{}.{}", unsigned, signature),
+            token_type: "Bearer".to_string(),
+            expires_at,
+        })
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Claims, ServerError> {
+        let invalid = || ServerError::Unauthorized("Invalid token".to_string());
+        let (unsigned, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let (header, payload) = unsigned.split_once('.').ok_or_else(invalid)?;
+        if header != encode(HEADER) {
+            return Err(invalid());
+        }
+        let signature: Vec<u8> = decode(signature).ok_or_else(invalid)?;
+        self.mac(unsigned).verify(&signature).map_err(|_| invalid())?;
+        let claims: Claims = decode(payload)
+            .and_then(|payload| serde_json::from_slice(&payload).ok())
+            .ok_or_else(invalid)?;
+
+        if claims.exp <= Utc::now().timestamp() {
+            return Err(ServerError::Unauthorized("Token expired".to_string()));
+        }
+        if self.revoked.lock().unwrap().contains_key(&claims.jti) {
+            return Err(ServerError::Unauthorized("Token revoked".to_string()));
+        }
+        Ok(claims)
+    }
+
+    pub fn revoke(&self, claims: &Claims) {
+        let now: i64 = Utc::now().timestamp();
+        let mut revoked = self.revoked.lock().unwrap();
+        revoked.retain(|_, exp| *exp > now);
+        revoked.insert(claims.jti.clone(), claims.exp);
+    }
+
+    fn mac(&self, unsigned: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(unsigned.as_bytes());
+        mac
+    }
+}
+
+fn encode<T: AsRef<[u8]>>(input: T) -> String {
+    base64::encode_config(input, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(input: &str) -> Option<Vec<u8>> {
+    base64::decode_config(input, base64::URL_SAFE_NO_PAD).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+
+    fn sessions(ttl: i64) -> Sessions {
+        Sessions {
+            secret: Arc::new(b"secret".to_vec()),
+            ttl,
+            revoked: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    #[test]
+    fn test_session_issue_given_account_when_verified_then_claims_obtained() {
+        let sessions: Sessions = sessions(60);
+        let token: Token = sessions.issue("restaurant", Role::Staff, "tablet-1").unwrap();
+        assert_eq!("Bearer", token.token_type);
+        assert_eq!(3, token.token.split('.').count());
+
+        let claims: Claims = sessions.verify(&token.token).unwrap();
+        assert_eq!("restaurant", claims.sub);
+        assert_eq!(Role::Staff, claims.role);
+        assert_eq!("tablet-1", claims.device);
+        assert_eq!(token.expires_at.timestamp(), claims.exp);
+    }
+
+    #[test]
+    fn test_session_verify_given_tampered_or_foreign_token_when_verified_then_unauthorized() {
+        let sessions: Sessions = sessions(60);
+        let token: String = sessions.issue("restaurant", Role::Staff, "tablet-1").unwrap().token;
+        let parts: Vec<&str> = token.split('.').collect();
+        let forged: String = encode(r#"{"sub":"restaurant","role":"manager","device":"tablet-1","jti":"x","iat":0,"exp":9999999999}"#);
+
+        assert!(matches!(sessions.verify(&format!("{}.{}.{}", parts[0], forged, parts[2])), Err(ServerError::Unauthorized(_))));
+        assert!(matches!(sessions.verify("not-a-token"), Err(ServerError::Unauthorized(_))));
+        assert!(matches!(Sessions::new(&Settings::new().auth).verify(&token), Err(ServerError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn test_session_verify_given_expired_token_when_verified_then_unauthorized() {
+        let sessions: Sessions = sessions(0);
+        let token: Token = sessions.issue("restaurant", Role::Staff, "tablet-1").unwrap();
+        assert!(matches!(sessions.verify(&token.token), Err(ServerError::Unauthorized(msg)) if msg == "Token expired"));
+    }
+
+    #[test]
+    fn test_session_revoke_given_token_revoked_when_verified_then_unauthorized() {
+        let sessions: Sessions = sessions(60);
+        let first: Token = sessions.issue("restaurant", Role::Staff, "tablet-1").unwrap();
+        let second: Token = sessions.issue("restaurant", Role::Staff, "tablet-1").unwrap();
+        sessions.revoke(&sessions.verify(&first.token).unwrap());
+
+        assert!(matches!(sessions.verify(&first.token), Err(ServerError::Unauthorized(msg)) if msg == "Token revoked"));
+        assert!(sessions.verify(&second.token).is_ok());
+    }
+}
@@ -1,6 +1,8 @@
-use config::{Config, File};
+use config::{Config, ConfigError};
 use serde::Deserialize;
-use std::path::PathBuf;
+pub use settings_layers::{Args, Env, SettingsError};
+use settings_layers::Layers;
+use std::ops::RangeInclusive;
 
 #[derive(Debug, Deserialize)]
 pub struct Server {
@@ -10,17 +12,105 @@ pub struct Server {
 
 #[derive(Debug, Deserialize)]
 pub struct Database {
-    prefix: String,
+    backend: String,
+    sqlite_path: String,
+    user: String,
     password: String,
+    password_file: String,
+    password_env: String,
+    // value of the variable named by password_env, out of the environment given to Settings::load
+    #[serde(skip)]
+    password_from_env: Option<String>,
     ip: String,
     port: String,
     db_name: String,
+    sslmode: String,
+    application_name: String,
+    connect_timeout: u64,
+    pool_max_size: u32,
+    pool_min_idle: u32,
+    pool_connection_timeout: u64,
+    pool_idle_timeout: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Kitchen {
+    simulate: bool,
+    recovery: String,
+    slots: usize,
+    cook_time_min: u64,
+    cook_time_max: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Validation {
+    max_items: usize,
+    max_amount: i32,
+    max_name_len: usize,
+    timestamp_tolerance: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Account {
+    username: String,
+    password_hash: String,
+    role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Auth {
+    token_secret: String,
+    token_ttl: i64,
+    accounts: Vec<Account>,
+}
+
+// Token bucket of a route group, `burst` requests at once then `rate` requests per sec
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Limit {
+    rate: f64,
+    burst: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RateLimit {
+    enabled: bool,
+    auth: Limit,
+    status: Limit,
+    orders: Limit,
+    kitchen: Limit,
+    manager: Limit,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Log {
+    level: String,
+    format: String,
+    console: bool,
+    file: String,
+    max_size: u64,
+    max_files: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Shutdown {
+    drain_timeout: u64,
+    on_timeout: String,
+    poll_interval: u64,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     pub server: Server,
     pub database: Database,
+    pub kitchen: Kitchen,
+    pub validation: Validation,
+    pub auth: Auth,
+    pub rate_limit: RateLimit,
+    pub log: Log,
+    pub shutdown: Shutdown,
+    // development, test or production, its file overriding config/default.toml
+    #[serde(default)]
+    pub profile: String,
 }
 
 impl Server {
@@ -33,12 +123,27 @@ impl Server {
 }
 
 impl Database {
-    pub fn get_prefix(&self) -> String {
-        self.prefix.clone()
+    pub fn get_backend(&self) -> String {
+        self.backend.clone()
+    }
+    pub fn get_sqlite_path(&self) -> String {
+        self.sqlite_path.clone()
+    }
+    pub fn get_user(&self) -> String {
+        self.user.clone()
     }
     pub fn get_password(&self) -> String {
         self.password.clone()
     }
+    pub fn get_password_file(&self) -> String {
+        self.password_file.clone()
+    }
+    pub fn get_password_env(&self) -> String {
+        self.password_env.clone()
+    }
+    pub fn get_password_from_env(&self) -> Option<String> {
+        self.password_from_env.clone()
+    }
     pub fn get_ip(&self) -> String {
         self.ip.clone()
     }
@@ -48,80 +153,242 @@ impl Database {
     pub fn get_db_name(&self) -> String {
         self.db_name.clone()
     }
+    pub fn get_sslmode(&self) -> String {
+        self.sslmode.clone()
+    }
+    pub fn get_application_name(&self) -> String {
+        self.application_name.clone()
+    }
+    pub fn get_connect_timeout(&self) -> u64 {
+        self.connect_timeout
+    }
+    pub fn get_pool_max_size(&self) -> u32 {
+        self.pool_max_size
+    }
+    pub fn get_pool_min_idle(&self) -> u32 {
+        self.pool_min_idle
+    }
+    pub fn get_pool_connection_timeout(&self) -> u64 {
+        self.pool_connection_timeout
+    }
+    pub fn get_pool_idle_timeout(&self) -> u64 {
+        self.pool_idle_timeout
+    }
+}
+
+impl Kitchen {
+    pub fn get_simulate(&self) -> bool {
+        self.simulate
+    }
+    pub fn get_recovery(&self) -> String {
+        self.recovery.clone()
+    }
+    pub fn get_slots(&self) -> usize {
+        self.slots
+    }
+    pub fn get_cook_time(&self) -> RangeInclusive<u64> {
+        self.cook_time_min..=self.cook_time_max
+    }
+}
+
+impl Validation {
+    pub fn get_max_items(&self) -> usize {
+        self.max_items
+    }
+    pub fn get_max_amount(&self) -> i32 {
+        self.max_amount
+    }
+    pub fn get_max_name_len(&self) -> usize {
+        self.max_name_len
+    }
+    pub fn get_timestamp_tolerance(&self) -> i64 {
+        self.timestamp_tolerance
+    }
+}
+
+impl Account {
+    pub fn get_username(&self) -> String {
+        self.username.clone()
+    }
+    pub fn get_password_hash(&self) -> String {
+        self.password_hash.clone()
+    }
+    pub fn get_role(&self) -> String {
+        self.role.clone()
+    }
+}
+
+impl Auth {
+    pub fn get_token_secret(&self) -> String {
+        self.token_secret.clone()
+    }
+    pub fn get_token_ttl(&self) -> i64 {
+        self.token_ttl
+    }
+    pub fn get_accounts(&self) -> Vec<Account> {
+        self.accounts.clone()
+    }
+}
+
+impl Limit {
+    pub fn get_rate(&self) -> f64 {
+        self.rate
+    }
+    pub fn get_burst(&self) -> u32 {
+        self.burst
+    }
+}
+
+impl RateLimit {
+    pub fn get_enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn get_auth(&self) -> Limit {
+        self.auth
+    }
+    pub fn get_status(&self) -> Limit {
+        self.status
+    }
+    pub fn get_orders(&self) -> Limit {
+        self.orders
+    }
+    pub fn get_kitchen(&self) -> Limit {
+        self.kitchen
+    }
+    pub fn get_manager(&self) -> Limit {
+        self.manager
+    }
+
+    // A bucket refilling at a rate of 0 or below would lock its clients out for good
+    fn check(&self) -> Result<(), SettingsError> {
+        let groups = vec![("auth", self.auth), ("status", self.status), ("orders", self.orders), ("kitchen", self.kitchen), ("manager", self.manager)];
+        for (group, limit) in groups {
+            if limit.rate.is_nan() || limit.rate <= 0.0 {
+                return Err(SettingsError::Invalid(format!("rate_limit.{}.rate must be above 0, got {}", group, limit.rate)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Log {
+    pub fn get_level(&self) -> String {
+        self.level.clone()
+    }
+    pub fn get_format(&self) -> String {
+        self.format.clone()
+    }
+    pub fn get_console(&self) -> bool {
+        self.console
+    }
+    pub fn get_file(&self) -> String {
+        self.file.clone()
+    }
+    pub fn get_max_size(&self) -> u64 {
+        self.max_size
+    }
+    pub fn get_max_files(&self) -> usize {
+        self.max_files
+    }
+}
+
+impl Shutdown {
+    pub fn get_drain_timeout(&self) -> u64 {
+        self.drain_timeout
+    }
+    pub fn get_on_timeout(&self) -> String {
+        self.on_timeout.clone()
+    }
+    pub fn get_poll_interval(&self) -> u64 {
+        self.poll_interval
+    }
 }
 
 impl Settings {
+    // Settings of the test profile without command line flags, as the tests run
+    #[cfg(test)]
     pub fn new() -> Self {
-        let mut config: Config = Config::default();
-        let (mut ip, mut port) = ("".to_string(), "".to_string());
-        let (mut prefix, mut password, mut db_ip, mut db_port, mut db_name) = (
-            "".to_string(),
-            "".to_string(),
-            "".to_string(),
-            "".to_string(),
-            "".to_string(),
-        );
-
-        let relative_path: PathBuf;
-        let mut absolute_path = std::env::current_dir().unwrap();
-        let mut path: &str = "";
-
-        //println!("absolute path: {:#?}", absolute_path);
-        if absolute_path.ends_with("server") {
-            relative_path = PathBuf::from("config\\production.toml");
-        } else {
-            relative_path = PathBuf::from("server\\config\\production.toml");
-        };
-        absolute_path.push(relative_path);
-
-        match absolute_path.to_str() {
-            Some(field) => path = field,
-            None => {}
-        };
-        match config.merge(File::with_name(path)) {
-            Ok(_) => {}
-            Err(err) => println!("[SETTINGS] Config Error: {}", err),
-        }
-        match config.get::<String>("server.ip") {
-            Ok(field) => ip = field,
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        }
-        match config.get::<String>("server.port") {
-            Ok(field) => port = field,
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        }
-        match config.get::<String>("database.prefix") {
-            Ok(field) => prefix = field,
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        }
-        match config.get::<String>("database.password") {
-            Ok(field) => password = field,
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        }
-        match config.get::<String>("database.ip") {
-            Ok(field) => db_ip = field,
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        }
-        match config.get::<String>("database.port") {
-            Ok(field) => db_port = field,
-            Err(err) => println!("[SETTINGS] Error: {}", err),
-        }
-        match config.get::<String>("database.db_name") {
-            Ok(field) => db_name = field,
-            Err(err) => println!("[SETTINGS] Error: {}", err),
+        let args: Args = Args { profile: Some("test".to_string()), ..Args::default() };
+        match Settings::load(&args, &Env::new()) {
+            Ok(settings) => settings,
+            Err(err) => panic!("[SETTINGS] {}", err),
         }
+    }
 
-        Settings {
-            server: Server { ip: ip, port: port },
-            database: Database {
-                prefix: prefix,
-                password: password,
-                ip: db_ip,
-                port: db_port,
-                db_name: db_name,
-            },
-        }
+    // Merge the layers of settings_layers::Layers over the defaults below, then check the result
+    pub fn load(args: &Args, env: &Env) -> Result<Settings, SettingsError> {
+        let (config, profile) = LAYERS.load(args, env, defaults)?;
+
+        required(&config)?;
+        let mut settings: Settings = config.try_into().map_err(|err| SettingsError::Invalid(err.to_string()))?;
+        settings.rate_limit.check()?;
+        settings.profile = profile;
+        settings.database.password_from_env = env.get(&settings.database.password_env).cloned();
+        settings.kitchen.cook_time_max = settings.kitchen.cook_time_max.max(settings.kitchen.cook_time_min);
+        settings.shutdown.poll_interval = settings.shutdown.poll_interval.max(1);
+        Ok(settings)
+    }
+}
+
+const LAYERS: Layers = Layers { prefix: "RESTAURANT", crate_dir: "server" };
+
+// Keys without a sensible default, the connection keys only needed by the postgres backend
+const REQUIRED: &[&str] = &["server.ip", "server.port"];
+const REQUIRED_POSTGRES: &[&str] = &["database.user", "database.ip", "database.port", "database.db_name"];
+
+fn required(config: &Config) -> Result<(), SettingsError> {
+    let postgres: bool = config.get::<String>("database.backend").is_ok_and(|backend| backend == "postgres");
+    let keys: Vec<&str> = REQUIRED.iter().chain(if postgres { REQUIRED_POSTGRES } else { &[] }).copied().collect();
+    LAYERS.required(config, &keys)
+}
+
+fn defaults(config: &mut Config) -> Result<(), ConfigError> {
+    config
+        .set_default("database.backend", "postgres")?
+        .set_default("database.sqlite_path", "restaurant.db")?
+        .set_default("database.user", "")?
+        .set_default("database.password", "")?
+        .set_default("database.password_file", "")?
+        .set_default("database.password_env", "")?
+        .set_default("database.ip", "")?
+        .set_default("database.port", "")?
+        .set_default("database.db_name", "")?
+        .set_default("database.sslmode", "prefer")?
+        .set_default("database.application_name", "restaurant-server")?
+        .set_default("database.connect_timeout", 5)?
+        .set_default("database.pool_max_size", 16)?
+        .set_default("database.pool_min_idle", 2)?
+        .set_default("database.pool_connection_timeout", 5)?
+        .set_default("database.pool_idle_timeout", 300)?
+        .set_default("kitchen.simulate", true)?
+        .set_default("kitchen.recovery", "requeue")?
+        .set_default("kitchen.slots", 4)?
+        .set_default("kitchen.cook_time_min", 5)?
+        .set_default("kitchen.cook_time_max", 15)?
+        .set_default("validation.max_items", 26)?
+        .set_default("validation.max_amount", 99)?
+        .set_default("validation.max_name_len", 32)?
+        .set_default("validation.timestamp_tolerance", 86400)?
+        .set_default("auth.token_secret", "")?
+        .set_default("auth.token_ttl", 3600)?
+        .set_default("auth.accounts", Vec::<String>::new())?
+        .set_default("rate_limit.enabled", true)?;
+    for (group, rate, burst) in [("auth", 0.2, 5), ("status", 5.0, 20), ("orders", 1.0, 10), ("kitchen", 5.0, 20), ("manager", 2.0, 10)] {
+        config
+            .set_default(&format!("rate_limit.{}.rate", group), rate)?
+            .set_default(&format!("rate_limit.{}.burst", group), burst)?;
     }
+    config
+        .set_default("log.level", "info")?
+        .set_default("log.format", "text")?
+        .set_default("log.console", true)?
+        .set_default("log.file", "")?
+        .set_default("log.max_size", 10)?
+        .set_default("log.max_files", 5)?
+        .set_default("shutdown.drain_timeout", 60)?
+        .set_default("shutdown.on_timeout", "cancel")?
+        .set_default("shutdown.poll_interval", 1000)?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -130,11 +397,78 @@ mod test {
     #[test]
     fn test_settings_new_given_config_provided_when_init_then_fields_obtained() {
         let config: Settings = Settings::new();
-        assert!(config.server.get_ip().len() > 0);
-        assert!(config.server.get_port().len() > 0);
-        assert_eq!("postgresql://postgres", config.database.get_prefix());
-        assert!(config.database.get_ip().len() > 0);
-        assert!(config.database.get_port().len() > 0);
+        assert!(!config.server.get_ip().is_empty());
+        assert!(!config.server.get_port().is_empty());
+        assert_eq!("memory", config.database.get_backend());
+        assert!(!config.database.get_sqlite_path().is_empty());
+        assert_eq!("postgres", config.database.get_user());
+        assert!(config.database.get_password_file().is_empty() && config.database.get_password_env().is_empty());
+        assert!(!config.database.get_ip().is_empty());
+        assert!(!config.database.get_port().is_empty());
         assert_eq!("restaurant", config.database.get_db_name());
+        assert_eq!("prefer", config.database.get_sslmode());
+        assert_eq!("restaurant-server", config.database.get_application_name());
+        assert!(config.database.get_connect_timeout() > 0);
+        assert!(config.database.get_pool_max_size() >= config.database.get_pool_min_idle());
+        assert!(config.database.get_pool_connection_timeout() > 0);
+        assert!(config.kitchen.get_slots() > 0);
+        assert!(!config.kitchen.get_recovery().is_empty());
+        assert!(!config.kitchen.get_cook_time().is_empty());
+        assert!(config.validation.get_max_items() > 0);
+        assert!(config.validation.get_max_amount() > 0);
+        assert!(config.validation.get_max_name_len() > 0);
+        assert!(config.auth.get_token_ttl() > 0);
+        assert!(config.auth.get_accounts().iter().all(|account| !account.get_password_hash().is_empty()));
+        let orders: Limit = config.rate_limit.get_orders();
+        assert!(orders.get_rate() > 0.0 && orders.get_burst() > 0);
+        assert!(!config.log.get_level().is_empty());
+        assert!(config.log.get_max_size() > 0);
+        assert!(!config.shutdown.get_on_timeout().is_empty());
+        assert!(config.shutdown.get_poll_interval() > 0);
+        assert_eq!("test", config.profile);
+    }
+
+    fn args(args: &[&str]) -> Result<Args, SettingsError> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_settings_load_given_profile_and_flags_when_loaded_then_later_layers_win() {
+        let config: Settings = Settings::load(&args(&["--profile", "test", "--server.port", "9090", "--rate_limit.orders.burst=3"]).unwrap(), &Env::new()).unwrap();
+        assert_eq!("test", config.profile);
+        assert_eq!("memory", config.database.get_backend());
+        assert_eq!("9090", config.server.get_port());
+        assert_eq!(3, config.rate_limit.get_orders().get_burst());
+        assert!(config.rate_limit.get_orders().get_rate() > 0.0);
+
+        let env: Env = vec![("RESTAURANT_SHUTDOWN__POLL_INTERVAL".to_string(), "250".to_string()), ("RESTAURANT_PROFILE".to_string(), "test".to_string())]
+            .into_iter()
+            .collect();
+        let config: Settings = Settings::load(&Args::default(), &env).unwrap();
+        assert_eq!(("test", 250), (config.profile.as_str(), config.shutdown.get_poll_interval()));
+        let config: Settings = Settings::load(&args(&["--shutdown.poll_interval", "500"]).unwrap(), &env).unwrap();
+        assert_eq!(500, config.shutdown.get_poll_interval());
+    }
+
+    #[test]
+    fn test_settings_load_given_unknown_profile_or_missing_key_when_loaded_then_typed_error() {
+        let err = Settings::load(&args(&["--profile", "staging"]).unwrap(), &Env::new()).unwrap_err();
+        assert_eq!(SettingsError::Profile("staging".to_string()), err);
+
+        let err = Settings::load(&args(&["--config", "missing.toml"]).unwrap(), &Env::new()).unwrap_err();
+        assert!(matches!(err, SettingsError::File(_)));
+
+        let err = Settings::load(&args(&["--database.db_name", ""]).unwrap(), &Env::new()).unwrap_err();
+        assert_eq!(SettingsError::Missing("database.db_name".to_string(), "RESTAURANT_DATABASE__DB_NAME".to_string()), err);
+        assert!(err.to_string().contains("RESTAURANT_DATABASE__DB_NAME"));
+        assert!(Settings::load(&args(&["--database.backend", "memory", "--database.db_name", ""]).unwrap(), &Env::new()).is_ok());
+
+        let err = Settings::load(&args(&["--profile", "test", "--rate_limit.orders.rate", "0"]).unwrap(), &Env::new()).unwrap_err();
+        assert!(matches!(err, SettingsError::Invalid(ref msg) if msg.contains("rate_limit.orders.rate")));
+        assert!(Settings::load(&args(&["--profile", "test", "--rate_limit.auth.rate", "-1"]).unwrap(), &Env::new()).is_err());
+
+        let mut config: Config = Config::default();
+        defaults(&mut config).unwrap();
+        assert_eq!(Err(SettingsError::Missing("server.ip".to_string(), "RESTAURANT_SERVER__IP".to_string())), required(&config));
     }
 }
@@ -0,0 +1,280 @@
+use crate::db::Storage;
+use crate::error::ServerError;
+use crate::lifecycle::{Lifecycle, Stage};
+use crate::order_type::{Actor, ItemStatus, Report};
+use crate::settings;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use strum_macros::{Display, EnumString};
+use tide::log::{error, info, warn};
+
+// What happens at the drain deadline to the items still unserved
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    // reject the items never started, those being cooked are left to the recovery of the next run
+    Cancel,
+    // leave every item as it is, for the recovery of the next run
+    Persist,
+    // reject every item left, so that all the tables get closed
+    Force,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, Serialize)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Drained,
+    // the deadline passed before, the policy was applied to the items left
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Progress {
+    started_at: Option<DateTime<Utc>>,
+    outcome: Option<Outcome>,
+    open_tables: i64,
+    pending_items: i64,
+    cancelled_items: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShutdownStatus {
+    pub stage: Stage,
+    pub outcome: Option<Outcome>,
+    pub policy: Policy,
+    pub drain_timeout: u64,
+    pub started_at: Option<DateTime<Utc>>,
+    pub deadline: Option<DateTime<Utc>>,
+    pub open_tables: i64,
+    // items still 'New' or 'Process'
+    pub pending_items: i64,
+    pub cancelled_items: usize,
+}
+
+// Coordinates the shutdown of section [shutdown]: the open tables get `drain_timeout` secs to be
+// served, then the policy settles the items left so that the server exits in bounded time
+#[derive(Clone)]
+pub struct Shutdown {
+    policy: Policy,
+    drain_timeout: Duration,
+    poll_interval: Duration,
+    lifecycle: Lifecycle,
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl Shutdown {
+    pub fn new(config: &settings::Shutdown, lifecycle: Lifecycle) -> Result<Shutdown, ServerError> {
+        let policy: Policy = config
+            .get_on_timeout()
+            .parse()
+            .map_err(|_| ServerError::Validation(format!("Unknown shutdown policy: {}", config.get_on_timeout())))?;
+        Ok(Shutdown {
+            policy,
+            drain_timeout: Duration::from_secs(config.get_drain_timeout()),
+            poll_interval: Duration::from_millis(config.get_poll_interval()),
+            lifecycle,
+            progress: Arc::new(Mutex::new(Progress::default())),
+        })
+    }
+
+    pub fn status(&self) -> ShutdownStatus {
+        let progress: Progress = self.progress.lock().unwrap().clone();
+        ShutdownStatus {
+            stage: self.lifecycle.stage(),
+            outcome: progress.outcome,
+            policy: self.policy,
+            drain_timeout: self.drain_timeout.as_secs(),
+            started_at: progress.started_at,
+            deadline: self.lifecycle.deadline(),
+            open_tables: progress.open_tables,
+            pending_items: progress.pending_items,
+            cancelled_items: progress.cancelled_items,
+        }
+    }
+
+    // Start draining, false when it had already started, i.e. on a second signal
+    pub fn begin(&self) -> bool {
+        let now: DateTime<Utc> = Utc::now();
+        let timeout: chrono::Duration = chrono::Duration::from_std(self.drain_timeout).unwrap_or_else(|_| chrono::Duration::zero());
+        if !self.lifecycle.drain(now + timeout) {
+            return false;
+        }
+        self.progress.lock().unwrap().started_at = Some(now);
+        true
+    }
+
+    // Record what is left to serve, true once every table is closed
+    fn progress(&self, db: &Storage) -> Result<bool, ServerError> {
+        let report: Report = db.report()?;
+        let pending_items: i64 = report
+            .items
+            .iter()
+            .filter(|count| count.status == ItemStatus::New || count.status == ItemStatus::Process)
+            .map(|count| count.items)
+            .sum();
+        {
+            let mut progress = self.progress.lock().unwrap();
+            progress.open_tables = report.open_tables;
+            progress.pending_items = pending_items;
+        }
+        db.check_table_status()
+    }
+
+    fn finish(&self, outcome: Outcome) -> Outcome {
+        self.progress.lock().unwrap().outcome = Some(outcome);
+        self.lifecycle.stop();
+        outcome
+    }
+
+    // Wait for the open tables to be served until the deadline, then settle the items left with the
+    // policy. The server is stopped afterwards, only left to exit
+    pub fn drain(&self, db: &Storage) -> Outcome {
+        let deadline: Instant = Instant::now() + self.drain_timeout;
+        loop {
+            match self.progress(db) {
+                Ok(true) => return self.finish(Outcome::Drained),
+                Ok(false) => {}
+                Err(err) => error!("[TERMINATION] {}", err),
+            }
+            let now: Instant = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            let status: ShutdownStatus = self.status();
+            info!("[TERMINATION] Waiting for {} tables, {} items to be served, {} secs left",
+                  status.open_tables, status.pending_items, (deadline - now).as_secs());
+            thread::sleep(self.poll_interval.min(deadline - now));
+        }
+
+        let status: ShutdownStatus = self.status();
+        warn!("[TERMINATION] Drain deadline passed with {} tables open, policy: {}", status.open_tables, self.policy);
+        match settle(db, self.policy) {
+            Ok(cancelled) => {
+                self.progress.lock().unwrap().cancelled_items = cancelled;
+                info!("[TERMINATION] {} items cancelled", cancelled);
+            }
+            Err(err) => error!("[TERMINATION] Settle Error: {}", err),
+        }
+        if let Err(err) = self.progress(db) {
+            error!("[TERMINATION] {}", err);
+        }
+        self.finish(Outcome::TimedOut)
+    }
+}
+
+// Reject the items the policy gives up on, answering how many
+pub fn settle(db: &Storage, policy: Policy) -> Result<usize, ServerError> {
+    let given_up = |status: ItemStatus| match policy {
+        Policy::Cancel => status == ItemStatus::New,
+        Policy::Persist => false,
+        Policy::Force => status == ItemStatus::New || status == ItemStatus::Process,
+    };
+    let mut cancelled: usize = 0;
+    for (session_id, order) in db.open_orders()? {
+        for item in order.items.iter().filter(|item| given_up(item.status)) {
+            if db.advance_session_item(session_id, &item.name, ItemStatus::Rejected, &Actor::system())? {
+                cancelled += 1;
+            }
+        }
+    }
+    Ok(cancelled)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::kitchen::{self, Kitchen};
+    use crate::memory::MemDb;
+    use crate::order_type::{ItemPair, KitchenOrder, Order, PlaceOrder, TableStatus};
+    use crate::settings::Settings;
+
+    // A store of a manual kitchen with table 1 ordering A and B, A being cooked
+    fn db() -> Storage {
+        let db: Storage = Arc::new(MemDb::new(Kitchen::manual()));
+        let items: Vec<ItemPair> = ["A", "B"].iter().map(|name| ItemPair { name: name.to_string(), amount: 1 }).collect();
+        db.place(PlaceOrder { created_at: Utc::now(), table_id: "1".to_string(), items }, &Actor::system()).unwrap();
+        let order = KitchenOrder { table_id: "1".to_string(), item: "A".to_string() };
+        kitchen::advance(&db, order, ItemStatus::Process, &Actor::system()).unwrap();
+        db
+    }
+
+    fn shutdown(drain_timeout: u64, on_timeout: &str) -> Shutdown {
+        let config: settings::Shutdown = serde_json::from_str(&format!(
+            r#"{{"drain_timeout":{},"on_timeout":"{}","poll_interval":10}}"#,
+            drain_timeout, on_timeout
+        ))
+        .unwrap();
+        Shutdown::new(&config, Lifecycle::new()).unwrap()
+    }
+
+    fn statuses(db: &Storage) -> (TableStatus, Vec<ItemStatus>) {
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        (order.table.status, order.items.iter().map(|item| item.status).collect())
+    }
+
+    #[test]
+    fn test_shutdown_new_given_config_provided_when_init_then_no_drain_started() {
+        let shutdown: Shutdown = Shutdown::new(&Settings::new().shutdown, Lifecycle::new()).unwrap();
+        assert_eq!(Stage::Starting, shutdown.status().stage);
+        assert!(shutdown.status().deadline.is_none() && shutdown.status().outcome.is_none());
+
+        let config: settings::Shutdown = serde_json::from_str(r#"{"drain_timeout":1,"on_timeout":"wait","poll_interval":10}"#).unwrap();
+        assert!(Shutdown::new(&config, Lifecycle::new()).is_err());
+    }
+
+    #[test]
+    fn test_shutdown_begin_given_drain_started_when_signaled_again_then_false() {
+        let shutdown: Shutdown = shutdown(60, "cancel");
+        assert!(shutdown.begin());
+        assert!(!shutdown.begin());
+        let status: ShutdownStatus = shutdown.status();
+        assert_eq!(Stage::Draining, status.stage);
+        assert_eq!(60, (status.deadline.unwrap() - status.started_at.unwrap()).num_seconds());
+    }
+
+    #[test]
+    fn test_shutdown_drain_given_no_open_table_when_drained_then_done_at_once() {
+        let db: Storage = Arc::new(MemDb::new(Kitchen::manual()));
+        let shutdown: Shutdown = shutdown(60, "cancel");
+        shutdown.begin();
+        assert_eq!(Outcome::Drained, shutdown.drain(&db));
+        assert_eq!((Stage::Stopped, Some(Outcome::Drained)), (shutdown.status().stage, shutdown.status().outcome));
+    }
+
+    #[test]
+    fn test_shutdown_drain_given_persist_policy_when_timed_out_then_items_left_as_they_are() {
+        let db: Storage = db();
+        let shutdown: Shutdown = shutdown(0, "persist");
+        shutdown.begin();
+        assert_eq!(Outcome::TimedOut, shutdown.drain(&db));
+
+        let status: ShutdownStatus = shutdown.status();
+        assert_eq!(Stage::Stopped, status.stage);
+        assert_eq!((1, 2, 0), (status.open_tables, status.pending_items, status.cancelled_items));
+        assert_eq!((TableStatus::Open, vec![ItemStatus::Process, ItemStatus::New]), statuses(&db));
+    }
+
+    #[test]
+    fn test_shutdown_drain_given_cancel_policy_when_timed_out_then_new_items_rejected() {
+        let db: Storage = db();
+        let shutdown: Shutdown = shutdown(0, "cancel");
+        assert_eq!(Outcome::TimedOut, shutdown.drain(&db));
+
+        let status: ShutdownStatus = shutdown.status();
+        assert_eq!((1, 1, 1), (status.open_tables, status.pending_items, status.cancelled_items));
+        assert_eq!((TableStatus::Open, vec![ItemStatus::Process, ItemStatus::Rejected]), statuses(&db));
+    }
+
+    #[test]
+    fn test_shutdown_settle_given_force_policy_when_settled_then_tables_closed() {
+        let db: Storage = db();
+        assert_eq!(2, settle(&db, Policy::Force).unwrap());
+        assert_eq!((TableStatus::Close, vec![ItemStatus::Rejected, ItemStatus::Rejected]), statuses(&db));
+        assert!(db.check_table_status().unwrap());
+    }
+}
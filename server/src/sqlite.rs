@@ -0,0 +1,694 @@
+use crate::db::{self, DB};
+use crate::error::ServerError;
+use crate::kitchen::Kitchen;
+use crate::menu;
+use crate::migration::Migration;
+use crate::order_type::{
+    Actor, DeleteOrder, History, HistoryEntry, Item, ItemPair, ItemStatus, MenuItem, Order, PlaceOrder,
+    Report, StatusCount, TableSession, TableStatus, UpdateOrder,
+};
+use chrono::{DateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tide::log::info;
+
+// Enums 'tablestatus'/'itemstatus' are stored as text columns guarded by CHECK constraints
+macro_rules! text_enum {
+    ($name:ty) => {
+        impl ToSql for $name {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.to_string()))
+            }
+        }
+
+        impl FromSql for $name {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                value
+                    .as_str()?
+                    .parse()
+                    .map_err(|err| FromSqlError::Other(Box::new(err)))
+            }
+        }
+    };
+}
+
+text_enum!(TableStatus);
+text_enum!(ItemStatus);
+
+// Applied in order of version like migration::MIGRATIONS, never edit a migration once released
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("../migrations/sqlite/0001_initial.sql"),
+        transactional: true,
+    },
+];
+
+#[derive(Clone)]
+pub struct SqliteDb {
+    conn: Arc<Mutex<Connection>>,
+    kitchen: Kitchen,
+}
+
+impl SqliteDb {
+    pub fn open(path: &str, kitchen: Kitchen) -> Result<SqliteDb, ServerError> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        Ok(SqliteDb {
+            conn: Arc::new(Mutex::new(conn)),
+            kitchen,
+        })
+    }
+
+    fn conn(&self) -> Result<MutexGuard<'_, Connection>, ServerError> {
+        self.conn
+            .lock()
+            .map_err(|_| ServerError::Storage("SQLite connection is poisoned".to_string()))
+    }
+}
+
+impl DB for SqliteDb {
+    fn init(&self) -> Result<(), ServerError> {
+        self.migrate().map(|_| ())
+    }
+
+    fn migrate(&self) -> Result<Vec<String>, ServerError> {
+        let mut conn = self.conn()?;
+        let applied = migrate(&mut conn)?;
+        info!("[MIGRATE] SQLite schema at version {}", current_version(&conn)?);
+
+        Ok(applied
+            .iter()
+            .map(|migration| format!("{:04}_{}", migration.version, migration.name))
+            .collect())
+    }
+
+    fn pending_migrations(&self) -> Result<Vec<String>, ServerError> {
+        let conn = self.conn()?;
+
+        Ok(pending(&conn)?
+            .iter()
+            .map(|migration| format!("{:04}_{}", migration.version, migration.name))
+            .collect())
+    }
+
+    fn place(&self, order: PlaceOrder, by: &Actor) -> Result<Order, ServerError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        menu::check_order(&order.items, |name| on_menu(&tx, name))?;
+        if let Some((_, session)) = latest_session(&tx, &order.table_id)? {
+            if session.status == TableStatus::Open {
+                return Err(ServerError::Conflict(format!("Duplicated! There has been an order of table_id: {}, being served in the queue", session.table_id)));
+            }
+        }
+
+        // insert new order into table 'tablet', a second open session of the table hits index 'tablet_open_session'
+        match tx.execute("INSERT INTO tablet(opened_at, table_id, status, staff, device) VALUES (?1, ?2, ?3, ?4, ?5)",
+                         params![order.created_at, order.table_id, TableStatus::Open, by.staff, by.device]) {
+            Ok(_) => {}
+            // only the unique index means the table is served, other constraints are storage errors
+            Err(rusqlite::Error::SqliteFailure(failure, _)) if failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE => {
+                return Err(ServerError::Conflict(format!("Duplicated! There has been an order of table_id: {}, being served in the queue", order.table_id)));
+            }
+            Err(err) => return Err(err.into()),
+        }
+        let session_id: i64 = tx.last_insert_rowid();
+        // insert new items into table items/item_history
+        for elem in &order.items {
+            queue_item(&tx, session_id, order.created_at, order.created_at, &order.table_id, elem, by)?;
+        }
+
+        let session = TableSession {
+            table_id: order.table_id.clone(),
+            opened_at: order.created_at,
+            closed_at: None,
+            status: TableStatus::Open,
+            staff: Some(by.staff.clone()),
+            device: by.device.clone(),
+        };
+        let items = session_items(&tx, session_id, None)?;
+        tx.commit()?;
+
+        for elem in order.items {
+            self.kitchen.enqueue(session_id, &session.table_id, &elem.name);
+        }
+        Ok(Order { queried_at: Utc::now(), table: session, items })
+    }
+
+    fn update(&self, order: UpdateOrder, by: &Actor) -> Result<Order, ServerError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        menu::check_order(&order.items, |name| on_menu(&tx, name))?;
+        let (session_id, session) = match latest_session(&tx, &order.table_id)? {
+            Some(session) => session,
+            None => return Err(ServerError::NotFound(format!("Update Order Failed! Non-existent table_id: {}", order.table_id))),
+        };
+        if session.status == TableStatus::Close {
+            return Err(ServerError::InvalidState(format!("Update Order Failed! table_id: {} was done. Please launch a new order", session.table_id)));
+        }
+
+        let mut queued: Vec<&ItemPair> = Vec::new();
+        for elem in &order.items {
+            let status: Option<ItemStatus> = tx
+                .query_row("SELECT status FROM items WHERE session_id = ?1 AND item = ?2", params![session_id, elem.name], |row| row.get(0))
+                .optional()?;
+            match status {
+                // only items still in 'New' state are allowed to be updated
+                Some(ItemStatus::New) => {
+                    tx.execute("UPDATE items SET updated_at = ?1, amount = ?2 WHERE session_id = ?3 AND item = ?4",
+                               params![order.updated_at, elem.amount, session_id, elem.name])?;
+                    tx.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'New', ?7, ?8)",
+                               params![session_id, session.opened_at, order.updated_at, session.table_id, elem.name, elem.amount, by.staff, by.device])?;
+                }
+                Some(_) => {}
+                None => {
+                    queue_item(&tx, session_id, session.opened_at, order.updated_at, &session.table_id, elem, by)?;
+                    queued.push(elem);
+                }
+            }
+        }
+
+        let items = session_items(&tx, session_id, None)?;
+        tx.commit()?;
+
+        for elem in queued {
+            self.kitchen.enqueue(session_id, &session.table_id, &elem.name);
+        }
+        Ok(Order { queried_at: Utc::now(), table: session, items })
+    }
+
+    fn delete(&self, order: DeleteOrder, by: &Actor) -> Result<Item, ServerError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let row = match latest_session(&tx, &order.table_id)? {
+            Some((session_id, _)) => find_item(&tx, session_id, &order.item)?,
+            None => None,
+        };
+        let (session_id, created_at, amount, status) = match row {
+            Some(row) => row,
+            None => return Err(ServerError::NotFound(format!("Delete Order Failed! No item: {} of table_id: {}", order.item, order.table_id))),
+        };
+
+        match status {
+            ItemStatus::New => {
+                tx.execute("DELETE FROM items WHERE session_id = ?1 AND item = ?2", params![session_id, order.item])?;
+                tx.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'Deleted', ?7, ?8)",
+                           params![session_id, created_at, order.deleted_at, order.table_id, order.item, amount, by.staff, by.device])?;
+                update_table_status(&tx, session_id)?;
+                tx.commit()?;
+                Ok(Item { name: order.item, amount, status: ItemStatus::Deleted })
+            }
+            ItemStatus::Process => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} is cooking", order.item, order.table_id))),
+            ItemStatus::Done => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was done", order.item, order.table_id))),
+            ItemStatus::Deleted => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was deleted", order.item, order.table_id))),
+            ItemStatus::Rejected => Err(ServerError::InvalidState(format!("Delete Order Failed! The item: {} of table_id: {} was rejected by the kitchen", order.item, order.table_id))),
+        }
+    }
+
+    fn void_item(&self, order: DeleteOrder, by: &Actor) -> Result<Item, ServerError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let row = match latest_session(&tx, &order.table_id)? {
+            Some((session_id, _)) => find_item(&tx, session_id, &order.item)?,
+            None => None,
+        };
+        let (session_id, created_at, amount, status) = match row {
+            Some(row) => row,
+            None => return Err(ServerError::NotFound(format!("Void Order Failed! No item: {} of table_id: {}", order.item, order.table_id))),
+        };
+
+        match status {
+            ItemStatus::Done => {
+                tx.execute("DELETE FROM items WHERE session_id = ?1 AND item = ?2", params![session_id, order.item])?;
+                tx.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'Deleted', ?7, ?8)",
+                           params![session_id, created_at, order.deleted_at, order.table_id, order.item, amount, by.staff, by.device])?;
+                tx.commit()?;
+                Ok(Item { name: order.item, amount, status: ItemStatus::Deleted })
+            }
+            status => Err(ServerError::InvalidState(format!("Void Order Failed! The item: {} of table_id: {} is {}, only served items are voided", order.item, order.table_id, status))),
+        }
+    }
+
+    fn query_by_tableid(&self, table_id: String) -> Result<Order, ServerError> {
+        let conn = self.conn()?;
+
+        match latest_session(&conn, &table_id)? {
+            Some((session_id, session)) => {
+                let items = session_items(&conn, session_id, None)?;
+                Ok(Order { queried_at: Utc::now(), table: session, items })
+            }
+            None => Err(ServerError::NotFound(format!("No Order of table id: {}", table_id))),
+        }
+    }
+
+    fn query_by_tableid_and_item(&self, table_id: String, item: String) -> Result<Order, ServerError> {
+        let conn = self.conn()?;
+
+        match latest_session(&conn, &table_id)? {
+            Some((session_id, session)) => {
+                let items = session_items(&conn, session_id, Some(&item))?;
+                if items.is_empty() {
+                    Err(ServerError::NotFound(format!("No item: {} of table id: {}", item, table_id)))
+                } else {
+                    Ok(Order { queried_at: Utc::now(), table: session, items })
+                }
+            }
+            None => Err(ServerError::NotFound(format!("No Order of table id: {}", table_id))),
+        }
+    }
+
+    fn advance_item(&self, table_id: &str, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let advanced: bool = match latest_session(&tx, table_id)? {
+            Some((session_id, _)) => advance_item(&tx, session_id, item, to, by)?,
+            None => false,
+        };
+        tx.commit()?;
+        Ok(advanced)
+    }
+
+    fn advance_session_item(&self, session_id: i64, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let advanced: bool = advance_item(&tx, session_id, item, to, by)?;
+        tx.commit()?;
+        Ok(advanced)
+    }
+
+    fn open_orders(&self) -> Result<Vec<(i64, Order)>, ServerError> {
+        let conn = self.conn()?;
+
+        let mut stmt = conn.prepare("SELECT session_id, opened_at, closed_at, table_id, status, staff, device FROM tablet WHERE status = ?1 ORDER BY session_id")?;
+        let sessions = stmt
+            .query_map(params![TableStatus::Open], |row| Ok((row.get("session_id")?, session_row(row)?)))?
+            .collect::<rusqlite::Result<Vec<(i64, TableSession)>>>()?;
+
+        let mut orders: Vec<(i64, Order)> = Vec::new();
+        for (session_id, session) in sessions {
+            let items = session_items(&conn, session_id, None)?;
+            orders.push((session_id, Order { queried_at: Utc::now(), table: session, items }));
+        }
+        Ok(orders)
+    }
+
+    fn history(&self, table_id: &str, item: Option<&str>) -> Result<History, ServerError> {
+        let conn = self.conn()?;
+
+        let mut stmt = conn.prepare("SELECT created_at, updated_at, item, amount, status, staff, device
+                                     FROM item_history
+                                     WHERE table_id = ?1 AND (?2 IS NULL OR item = ?2)
+                                     ORDER BY history_id")?;
+        let entries = stmt
+            .query_map(params![table_id, item], |row| Ok(HistoryEntry {
+                opened_at: row.get(0)?,
+                updated_at: row.get(1)?,
+                item: row.get(2)?,
+                amount: row.get(3)?,
+                status: row.get(4)?,
+                staff: row.get(5)?,
+                device: row.get(6)?,
+            }))?
+            .collect::<rusqlite::Result<Vec<HistoryEntry>>>()?;
+        if entries.is_empty() {
+            return Err(ServerError::NotFound(format!("No history of table id: {}", table_id)));
+        }
+        Ok(History { queried_at: Utc::now(), table_id: table_id.to_string(), entries })
+    }
+
+    fn menu(&self) -> Result<Vec<MenuItem>, ServerError> {
+        let conn = self.conn()?;
+
+        let mut stmt = conn.prepare("SELECT name, description, category, cook_time, active FROM menu ORDER BY name")?;
+        let menu = stmt
+            .query_map([], menu_row)?
+            .collect::<rusqlite::Result<Vec<MenuItem>>>()?;
+        Ok(menu)
+    }
+
+    fn menu_item(&self, name: &str) -> Result<MenuItem, ServerError> {
+        let conn = self.conn()?;
+
+        match find_menu_item(&conn, name)? {
+            Some(dish) => Ok(dish),
+            None => Err(ServerError::NotFound(format!("No menu item: {}", name))),
+        }
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<MenuItem, ServerError> {
+        menu::check_entry(&item)?;
+        let conn = self.conn()?;
+
+        if find_menu_item(&conn, &item.name)?.is_some() {
+            return Err(ServerError::Conflict(format!("Duplicated! The menu has an item: {} already", item.name)));
+        }
+        conn.execute("INSERT INTO menu(name, description, category, cook_time, active) VALUES (?1, ?2, ?3, ?4, ?5)",
+                     params![item.name, item.description, item.category, item.cook_time, item.active])?;
+        Ok(item)
+    }
+
+    fn update_menu_item(&self, item: MenuItem) -> Result<MenuItem, ServerError> {
+        menu::check_entry(&item)?;
+        let conn = self.conn()?;
+
+        let updated = conn.execute("UPDATE menu SET description = ?2, category = ?3, cook_time = ?4, active = ?5 WHERE name = ?1",
+                                   params![item.name, item.description, item.category, item.cook_time, item.active])?;
+        if updated == 0 {
+            return Err(ServerError::NotFound(format!("No menu item: {}", item.name)));
+        }
+        Ok(item)
+    }
+
+    fn delete_menu_item(&self, name: &str) -> Result<MenuItem, ServerError> {
+        let conn = self.conn()?;
+
+        match find_menu_item(&conn, name)? {
+            Some(dish) => {
+                conn.execute("DELETE FROM menu WHERE name = ?1", params![name])?;
+                Ok(dish)
+            }
+            None => Err(ServerError::NotFound(format!("No menu item: {}", name))),
+        }
+    }
+
+    fn report(&self) -> Result<Report, ServerError> {
+        let conn = self.conn()?;
+
+        let open_tables: i64 = conn.query_row("SELECT COUNT(*) FROM tablet WHERE status = ?1", params![TableStatus::Open], |row| row.get(0))?;
+        let mut stmt = conn.prepare("SELECT status, COUNT(*), SUM(amount) FROM items GROUP BY status ORDER BY status")?;
+        let items = stmt
+            .query_map([], |row| Ok(StatusCount {
+                status: row.get(0)?,
+                items: row.get(1)?,
+                amount: row.get(2)?,
+            }))?
+            .collect::<rusqlite::Result<Vec<StatusCount>>>()?;
+        Ok(Report { reported_at: Utc::now(), open_tables, items })
+    }
+
+    fn cook_times(&self) -> Result<Vec<f64>, ServerError> {
+        let conn = self.conn()?;
+
+        let mut stmt = conn.prepare("SELECT session_id || '/' || item, status, updated_at FROM item_history ORDER BY history_id")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<Vec<(String, ItemStatus, DateTime<Utc>)>>>()?;
+        Ok(db::cook_times(rows))
+    }
+
+    fn check_table_status(&self) -> Result<bool, ServerError> {
+        let conn = self.conn()?;
+        let mut is_empty: bool = true;
+
+        let mut stmt = conn.prepare("SELECT table_id, status FROM tablet WHERE status = ?1")?;
+        let mut rows = stmt.query(params![TableStatus::Open])?;
+        while let Some(row) = rows.next()? {
+            is_empty = false;
+            let table_id: String = row.get(0)?;
+            let status: TableStatus = row.get(1)?;
+            info!("[CHECK_TABLE_STATUS] table_id: {} is still being served, status: {}", table_id, status);
+        }
+
+        Ok(is_empty)
+    }
+}
+
+// The latest session of a table along with its session id
+fn latest_session(conn: &Connection, table_id: &str) -> Result<Option<(i64, TableSession)>, ServerError> {
+    let session = conn
+        .query_row("SELECT session_id, opened_at, closed_at, table_id, status, staff, device
+                    FROM tablet
+                    WHERE session_id = (SELECT MAX(session_id) FROM tablet WHERE table_id = ?1)",
+                   params![table_id],
+                   |row| Ok((row.get("session_id")?, session_row(row)?)))
+        .optional()?;
+    Ok(session)
+}
+
+fn session_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<TableSession> {
+    Ok(TableSession {
+        opened_at: row.get("opened_at")?,
+        closed_at: row.get("closed_at")?,
+        table_id: row.get("table_id")?,
+        status: row.get("status")?,
+        staff: row.get("staff")?,
+        device: row.get("device")?,
+    })
+}
+
+fn session_items(conn: &Connection, session_id: i64, item: Option<&str>) -> Result<Vec<Item>, ServerError> {
+    let mut stmt = conn.prepare("SELECT item, amount, status
+                                 FROM items
+                                 WHERE session_id = ?1 AND (?2 IS NULL OR item = ?2)
+                                 ORDER BY item_id")?;
+    let items = stmt
+        .query_map(params![session_id, item], |row| Ok(Item {
+            name: row.get(0)?,
+            amount: row.get(1)?,
+            status: row.get(2)?,
+        }))?
+        .collect::<rusqlite::Result<Vec<Item>>>()?;
+    Ok(items)
+}
+
+// Session id, created_at, amount and status of an item of a session
+type ItemRow = (i64, DateTime<Utc>, i32, ItemStatus);
+
+fn find_item(conn: &Connection, session_id: i64, item: &str) -> Result<Option<ItemRow>, ServerError> {
+    let row = conn
+        .query_row("SELECT session_id, created_at, amount, status FROM items WHERE session_id = ?1 AND item = ?2",
+                   params![session_id, item],
+                   |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+        .optional()?;
+    Ok(row)
+}
+
+fn menu_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<MenuItem> {
+    Ok(MenuItem {
+        name: row.get(0)?,
+        description: row.get(1)?,
+        category: row.get(2)?,
+        cook_time: row.get(3)?,
+        active: row.get(4)?,
+    })
+}
+
+fn find_menu_item(conn: &Connection, name: &str) -> Result<Option<MenuItem>, ServerError> {
+    let dish = conn
+        .query_row("SELECT name, description, category, cook_time, active FROM menu WHERE name = ?1", params![name], menu_row)
+        .optional()?;
+    Ok(dish)
+}
+
+// Insert a new item as 'New', waiting in the kitchen queue for a free slot
+fn queue_item(conn: &Connection, session_id: i64, opened_at: DateTime<Utc>, updated_at: DateTime<Utc>, table_id: &str, elem: &ItemPair, by: &Actor) -> Result<(), ServerError> {
+    conn.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'New')",
+                 params![session_id, opened_at, updated_at, table_id, elem.name, elem.amount])?;
+    conn.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'New', ?7, ?8)",
+                 params![session_id, opened_at, updated_at, table_id, elem.name, elem.amount, by.staff, by.device])?;
+    Ok(())
+}
+
+fn on_menu(conn: &Connection, name: &str) -> Result<bool, ServerError> {
+    let found: bool = conn.query_row("SELECT EXISTS (SELECT 1 FROM menu WHERE name = ?1 AND active)", params![name], |row| row.get(0))?;
+    Ok(found)
+}
+
+// Apply the pending migrations, each in its own transaction, and return the applied ones
+fn migrate(conn: &mut Connection) -> Result<Vec<&'static Migration>, ServerError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+    )?;
+
+    let version: i32 = current_version(conn)?;
+    let mut applied: Vec<&'static Migration> = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > version) {
+        let tx = conn.transaction()?;
+        info!("[MIGRATE] Applying SQLite {:04}_{}", migration.version, migration.name);
+        tx.execute_batch(migration.sql)?;
+        tx.execute("INSERT INTO schema_version(version, name, applied_at) VALUES (?1, ?2, ?3)", params![migration.version, migration.name, Utc::now()])?;
+        tx.commit()?;
+        applied.push(migration);
+    }
+
+    Ok(applied)
+}
+
+// Version of the latest applied migration, 0 for a file never migrated
+fn current_version(conn: &Connection) -> Result<i32, ServerError> {
+    let version: i32 = conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))?;
+    Ok(version)
+}
+
+// Migrations newer than the schema, all of them for a file never migrated
+fn pending(conn: &Connection) -> Result<Vec<&'static Migration>, ServerError> {
+    let version: i32 = if exists(conn, "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'")? {
+        current_version(conn)?
+    } else {
+        0
+    };
+    Ok(MIGRATIONS.iter().filter(|migration| migration.version > version).collect())
+}
+
+fn exists(conn: &Connection, query: &str) -> Result<bool, ServerError> {
+    let found: bool = conn.query_row(&format!("SELECT EXISTS ({})", query), [], |row| row.get(0))?;
+    Ok(found)
+}
+
+// Move an item of a session, answering whether it reached `to`
+fn advance_item(conn: &Connection, session_id: i64, item: &str, to: ItemStatus, by: &Actor) -> Result<bool, ServerError> {
+    match find_item(conn, session_id, item)? {
+        Some((_, _, _, status)) if status == to => return Ok(true),
+        Some((_, _, _, status)) if status.can_advance(to) => {}
+        _ => return Ok(false),
+    }
+
+    conn.execute("UPDATE items SET updated_at = ?1, status = ?2 WHERE session_id = ?3 AND item = ?4", params![Utc::now(), to, session_id, item])?;
+    conn.execute("INSERT INTO item_history(session_id, created_at, updated_at, table_id, item, amount, status, staff, device)
+                  SELECT session_id, created_at, updated_at, table_id, item, amount, status, ?3, ?4
+                  FROM items WHERE session_id = ?1 AND item = ?2",
+                 params![session_id, item, by.staff, by.device])?;
+    update_table_status(conn, session_id)?;
+    Ok(true)
+}
+
+fn update_table_status(conn: &Connection, session_id: i64) -> Result<(), ServerError> {
+    let open: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM items WHERE session_id = ?1 AND status IN ('New', 'Process'))",
+        params![session_id],
+        |row| row.get(0),
+    )?;
+
+    if open {
+        conn.execute("UPDATE tablet SET status = 'Open' WHERE session_id = ?1", params![session_id])?;
+    } else {
+        conn.execute("UPDATE tablet SET closed_at = ?2, status = 'Close' WHERE session_id = ?1", params![session_id, Utc::now()])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seed(db: &SqliteDb, table: TableStatus, items: Vec<(&str, ItemStatus)>) -> DateTime<Utc> {
+        let opened_at: DateTime<Utc> = Utc::now();
+        let conn = db.conn().unwrap();
+        conn.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES (?1, '1', ?2)", params![opened_at, table]).unwrap();
+        let session_id: i64 = conn.last_insert_rowid();
+        for (item, status) in items {
+            conn.execute("INSERT INTO items(session_id, created_at, updated_at, table_id, item, amount, status) VALUES (?1, ?2, ?2, '1', ?3, 2, ?4)",
+                         params![session_id, opened_at, item, status]).unwrap();
+        }
+        opened_at
+    }
+
+    fn new_db() -> SqliteDb {
+        let db: SqliteDb = SqliteDb::open(":memory:", Kitchen::new()).unwrap();
+        db.init().unwrap();
+        db
+    }
+
+    fn storage() -> db::Storage {
+        let db: SqliteDb = SqliteDb::open(":memory:", Kitchen::manual()).unwrap();
+        db.init().unwrap();
+        Arc::new(db)
+    }
+
+    db::storage_suite!(storage);
+
+    #[test]
+    fn test_sqlitedb_init_given_status_out_of_enum_when_inserted_then_check_constraint_rejects() {
+        let db: SqliteDb = new_db();
+        let conn = db.conn().unwrap();
+        let res = conn.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES (?1, '1', 'Served')", params![Utc::now()]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_sqlitedb_advance_item_given_item_in_process_when_rejected_then_status_accepted_and_table_closed() {
+        let db: SqliteDb = new_db();
+        seed(&db, TableStatus::Open, vec![("A", ItemStatus::Process)]);
+        assert!(db.advance_item("1", "A", ItemStatus::Rejected, &Actor::system()).unwrap());
+        let order: Order = db.query_by_tableid("1".to_string()).unwrap();
+        assert_eq!(ItemStatus::Rejected, order.items[0].status);
+        assert_eq!(TableStatus::Close, order.table.status);
+    }
+
+    #[test]
+    fn test_sqlitedb_pending_migrations_given_schema_initialized_when_checked_then_none_pending() {
+        let db: SqliteDb = SqliteDb::open(":memory:", Kitchen::new()).unwrap();
+        assert_eq!(MIGRATIONS.len(), db.pending_migrations().unwrap().len());
+        assert_eq!(MIGRATIONS.len(), db.migrate().unwrap().len());
+        assert!(db.pending_migrations().unwrap().is_empty());
+        assert!(db.migrate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sqlitedb_menu_given_fresh_file_when_init_twice_then_default_menu_seeded_once() {
+        let db: SqliteDb = new_db();
+        let mut dish: MenuItem = db.menu_item("A").unwrap();
+        dish.cook_time = 0;
+        dish.active = false;
+        db.update_menu_item(dish.clone()).unwrap();
+        db.init().unwrap();
+        assert_eq!(dish, db.menu_item("A").unwrap());
+        assert_eq!(26, db.menu().unwrap().len());
+        assert!(matches!(db.add_menu_item(dish), Err(ServerError::Conflict(_))));
+    }
+
+    #[test]
+    fn test_sqlitedb_init_given_open_session_when_another_opened_for_the_table_then_unique_index_rejects() {
+        let db: SqliteDb = new_db();
+        let conn = db.conn().unwrap();
+        conn.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES (?1, '1', 'Open')", params![Utc::now()]).unwrap();
+        conn.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES (?1, '2', 'Open')", params![Utc::now()]).unwrap();
+        let res = conn.execute("INSERT INTO tablet(opened_at, table_id, status) VALUES (?1, '1', 'Open')", params![Utc::now()]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_sqlitedb_place_given_insert_failing_midway_when_rolled_back_then_nothing_queued() {
+        let kitchen: Kitchen = Kitchen::new();
+        let db: SqliteDb = SqliteDb::open(":memory:", kitchen.clone()).unwrap();
+        db.init().unwrap();
+        db.conn().unwrap().execute_batch(
+            "CREATE TRIGGER items_fail BEFORE INSERT ON items WHEN NEW.item = 'B' BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+        ).unwrap();
+        let order: PlaceOrder = PlaceOrder {
+            created_at: Utc::now(),
+            table_id: "1".to_string(),
+            items: vec![ItemPair { name: "A".to_string(), amount: 1 }, ItemPair { name: "B".to_string(), amount: 1 }],
+        };
+        assert!(db.place(order, &Actor::system()).is_err());
+        assert_eq!(0, kitchen.queued());
+        assert!(db.check_table_status().unwrap());
+    }
+
+    #[test]
+    fn test_sqlitedb_place_given_constraint_other_than_unique_failing_when_place_then_storage_error_returned() {
+        let db: SqliteDb = new_db();
+        db.conn().unwrap().execute_batch(
+            "CREATE TRIGGER tablet_fail BEFORE INSERT ON tablet BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+        ).unwrap();
+        let order: PlaceOrder = PlaceOrder {
+            created_at: Utc::now(),
+            table_id: "1".to_string(),
+            items: vec![ItemPair { name: "A".to_string(), amount: 1 }],
+        };
+        assert!(matches!(db.place(order, &Actor::system()), Err(ServerError::Storage(_))));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::auth::Accounts;
+use crate::error::ServerError;
+use crate::settings;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use strum_macros::Display;
+use tide::log::warn;
+use tide::{Middleware, Next, Request};
+
+// Buckets kept before the full ones, i.e. of the clients gone quiet, get dropped, followed by the least
+// recently used ones if that is not enough
+const MAX_BUCKETS: usize = 10000;
+
+// Routes sharing a rate limit, a client spending its burst on one route of a group waits on all of them
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Group {
+    // login, refresh and logout
+    Auth,
+    // order status and menu queries
+    Status,
+    // place, update and delete orders
+    Orders,
+    Kitchen,
+    // menu changes, voids and reports
+    Manager,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+    // set while the client is refused, so that it gets logged once per flood
+    limited: bool,
+}
+
+impl Bucket {
+    fn new(limit: &settings::Limit, now: Instant) -> Bucket {
+        Bucket { tokens: limit.get_burst() as f64, last: now, limited: false }
+    }
+
+    fn refill(&mut self, limit: &settings::Limit, now: Instant) {
+        let elapsed: f64 = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.get_rate()).min(limit.get_burst() as f64);
+        self.last = now;
+    }
+
+    // Takes a token, or answers the secs until one is there again
+    fn take(&mut self, limit: &settings::Limit, now: Instant) -> Result<(), u64> {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(((1.0 - self.tokens) / limit.get_rate()).ceil().max(1.0) as u64)
+    }
+}
+
+// Rate limits of section [rate_limit], one token bucket per client and route group
+pub struct Throttle {
+    enabled: bool,
+    limiters: Vec<Limiter>,
+}
+
+impl Throttle {
+    pub fn new(config: &settings::RateLimit, accounts: Accounts) -> Throttle {
+        let limiters: Vec<Limiter> = [
+            (Group::Auth, config.get_auth()),
+            (Group::Status, config.get_status()),
+            (Group::Orders, config.get_orders()),
+            (Group::Kitchen, config.get_kitchen()),
+            (Group::Manager, config.get_manager()),
+        ]
+        .iter()
+        .map(|(group, limit)| Limiter {
+            group: *group,
+            limit: *limit,
+            enabled: config.get_enabled(),
+            accounts: accounts.clone(),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        })
+        .collect();
+        Throttle { enabled: config.get_enabled(), limiters }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Middleware counting the requests of a route against the buckets of its group
+    pub fn limit(&self, group: Group) -> Limiter {
+        self.limiters
+            .iter()
+            .find(|limiter| limiter.group == group)
+            .cloned()
+            .expect("Every group has a limiter")
+    }
+}
+
+// Answers 429 with a Retry-After header once a client has spent the burst of the group. Clients are
+// told apart by the account and device of their bearer token, or by their remote address without a
+// valid one. The limit is checked before the credentials so that a flood of requests
+// never reaches the password hashing
+#[derive(Clone)]
+pub struct Limiter {
+    group: Group,
+    limit: settings::Limit,
+    enabled: bool,
+    accounts: Accounts,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl Limiter {
+    fn client<State>(&self, req: &Request<State>) -> String {
+        if let Some(principal) = self.accounts.principal(req) {
+            return principal;
+        }
+        match req.peer_addr() {
+            // the port changes with every connection, the address does not
+            Some(addr) => addr.parse::<SocketAddr>().map(|addr| addr.ip().to_string()).unwrap_or_else(|_| addr.to_string()),
+            None => "unknown".to_string(),
+        }
+    }
+
+    fn check(&self, client: &str, now: Instant) -> Result<(), ServerError> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(client) {
+            let limit: settings::Limit = self.limit;
+            buckets.retain(|_, bucket| {
+                let elapsed: f64 = now.saturating_duration_since(bucket.last).as_secs_f64();
+                bucket.tokens + elapsed * limit.get_rate() < limit.get_burst() as f64
+            });
+            // a flood from many addresses leaves no bucket full, a tenth of them goes at once so that
+            // the next new clients find room
+            if buckets.len() >= MAX_BUCKETS {
+                let mut used: Vec<(Instant, String)> = buckets.iter().map(|(client, bucket)| (bucket.last, client.clone())).collect();
+                let evicted: usize = buckets.len() - MAX_BUCKETS + MAX_BUCKETS / 10;
+                used.select_nth_unstable(evicted - 1);
+                for (_, client) in &used[..evicted] {
+                    buckets.remove(client);
+                }
+            }
+        }
+        let bucket: &mut Bucket = buckets.entry(client.to_string()).or_insert_with(|| Bucket::new(&self.limit, now));
+        match bucket.take(&self.limit, now) {
+            Ok(()) => {
+                bucket.limited = false;
+                Ok(())
+            }
+            Err(secs) => {
+                if !bucket.limited {
+                    warn!("[RATE_LIMIT] {} is over the {} limit, retry after {} secs", client, self.group, secs);
+                    bucket.limited = true;
+                }
+                Err(ServerError::RateLimited(secs))
+            }
+        }
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Limiter {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        if !self.enabled {
+            return Ok(next.run(req).await);
+        }
+        match self.check(&self.client(&req), Instant::now()) {
+            Ok(()) => Ok(next.run(req).await),
+            Err(err) => err.to_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::order_type::{Login, Token};
+    use crate::settings::Settings;
+    use sha256::digest;
+    use std::time::Duration;
+
+    fn limit(rate: f64, burst: u32) -> settings::Limit {
+        serde_json::from_str(&format!(r#"{{"rate":{},"burst":{}}}"#, rate, burst)).unwrap()
+    }
+
+    fn limiter(accounts: Accounts, limit: settings::Limit) -> Limiter {
+        Limiter {
+            group: Group::Orders,
+            limit,
+            enabled: true,
+            accounts,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    #[test]
+    fn test_throttle_bucket_take_given_burst_spent_when_time_passes_then_refilled() {
+        let limit: settings::Limit = limit(0.5, 2);
+        let start: Instant = Instant::now();
+        let mut bucket: Bucket = Bucket::new(&limit, start);
+        assert_eq!(Ok(()), bucket.take(&limit, start));
+        assert_eq!(Ok(()), bucket.take(&limit, start));
+        assert_eq!(Err(2), bucket.take(&limit, start));
+
+        assert_eq!(Err(1), bucket.take(&limit, start + Duration::from_secs(1)));
+        assert_eq!(Ok(()), bucket.take(&limit, start + Duration::from_secs(2)));
+        assert_eq!(Ok(()), bucket.take(&limit, start + Duration::from_secs(60)));
+        assert_eq!(Ok(()), bucket.take(&limit, start + Duration::from_secs(60)));
+        assert!(bucket.take(&limit, start + Duration::from_secs(60)).is_err());
+    }
+
+    #[test]
+    fn test_throttle_limiter_given_burst_spent_when_requested_then_429_with_retry_after() {
+        let accounts: Accounts = Accounts::new(&Settings::new().auth).unwrap();
+        let mut app = tide::new();
+        app.at("/").with(limiter(accounts, limit(0.1, 2))).get(|_| async { Ok("served") });
+        let call = || {
+            let req = tide::http::Request::new(tide::http::Method::Get, "http://localhost/");
+            let res: tide::http::Response = async_std::task::block_on(app.respond(req)).unwrap();
+            (res.status(), res.header("Retry-After").map(|value| value.as_str().to_string()))
+        };
+
+        assert_eq!((tide::StatusCode::Ok, None), call());
+        assert_eq!((tide::StatusCode::Ok, None), call());
+        assert_eq!((tide::StatusCode::TooManyRequests, Some("10".to_string())), call());
+    }
+
+    #[test]
+    fn test_throttle_limiter_given_bearer_tokens_when_requested_then_buckets_kept_per_device() {
+        let accounts: Accounts = Accounts::new(&Settings::new().auth).unwrap();
+        let token = |device: &str| -> Token {
+            async_std::task::block_on(accounts.login(&Login { username: digest("restaurant"), password: digest("paidy"), device: device.to_string() })).unwrap()
+        };
+        let mut app = tide::new();
+        app.at("/").with(limiter(accounts.clone(), limit(0.1, 1))).get(|_| async { Ok("served") });
+        let call = |headers: &[(&str, String)]| {
+            let mut req = tide::http::Request::new(tide::http::Method::Get, "http://localhost/");
+            for (name, value) in headers {
+                req.insert_header(*name, value.as_str());
+            }
+            let res: tide::http::Response = async_std::task::block_on(app.respond(req)).unwrap();
+            res.status()
+        };
+        let bearer = |token: &Token| [("Authorization", format!("Bearer {}", token.token))];
+        let (first, second) = (token("tablet-1"), token("tablet-2"));
+
+        assert_eq!(tide::StatusCode::Ok, call(&bearer(&first)));
+        assert_eq!(tide::StatusCode::TooManyRequests, call(&bearer(&first)));
+        assert_eq!(tide::StatusCode::Ok, call(&bearer(&second)));
+        // unverified usernames count against the remote address, not the bucket of the account
+        assert_eq!(tide::StatusCode::Ok, call(&[("X-Auth-Username", digest("restaurant"))]));
+        assert_eq!(tide::StatusCode::TooManyRequests, call(&[("X-Auth-Username", digest("restaurant"))]));
+        assert_eq!(tide::StatusCode::Ok, call(&bearer(&token("tablet-3"))));
+    }
+
+    #[test]
+    fn test_throttle_limiter_check_given_flood_of_new_clients_when_checked_then_least_recently_used_evicted() {
+        let limiter: Limiter = limiter(Accounts::new(&Settings::new().auth).unwrap(), limit(0.1, 2));
+        let start: Instant = Instant::now();
+        for client in 0..MAX_BUCKETS + 10 {
+            assert!(limiter.check(&client.to_string(), start + Duration::from_micros(client as u64)).is_ok());
+        }
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.len() < MAX_BUCKETS);
+        assert!(!buckets.contains_key("0"));
+        assert!(buckets.contains_key(&(MAX_BUCKETS + 9).to_string()));
+    }
+
+    #[test]
+    fn test_throttle_limit_given_disabled_limiter_when_requested_then_requests_pass() {
+        let config: Settings = Settings::new();
+        let throttle: Throttle = Throttle::new(&config.rate_limit, Accounts::new(&config.auth).unwrap());
+        assert_eq!(Group::Kitchen, throttle.limit(Group::Kitchen).group);
+        assert_eq!(config.rate_limit.get_enabled(), throttle.is_enabled());
+
+        let mut disabled: Limiter = limiter(Accounts::new(&config.auth).unwrap(), limit(0.1, 0));
+        disabled.enabled = false;
+        let mut app = tide::new();
+        app.at("/").with(disabled).get(|_| async { Ok("served") });
+        let req = tide::http::Request::new(tide::http::Method::Get, "http://localhost/");
+        let res: tide::http::Response = async_std::task::block_on(app.respond(req)).unwrap();
+        assert_eq!(tide::StatusCode::Ok, res.status());
+    }
+}
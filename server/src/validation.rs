@@ -0,0 +1,209 @@
+use crate::error::ServerError;
+use crate::order_type::{DeleteOrder, ItemPair, KitchenOrder, MenuItem, PlaceOrder, UpdateOrder, Violation};
+use crate::settings;
+use chrono::{DateTime, Utc};
+
+// Limits on the order and menu payloads, checked before they reach the storage backend
+#[derive(Debug, Clone)]
+pub struct Rules {
+    max_items: usize,
+    max_amount: i32,
+    max_name_len: usize,
+    timestamp_tolerance: i64,
+}
+
+impl Rules {
+    pub fn new(config: &settings::Validation) -> Rules {
+        Rules {
+            max_items: config.get_max_items(),
+            max_amount: config.get_max_amount(),
+            max_name_len: config.get_max_name_len(),
+            timestamp_tolerance: config.get_timestamp_tolerance(),
+        }
+    }
+
+    pub fn check_place(&self, order: &PlaceOrder) -> Result<(), ServerError> {
+        let mut violations: Vec<Violation> = Vec::new();
+        self.timestamp("created_at", order.created_at, &mut violations);
+        self.name("table_id", &order.table_id, &mut violations);
+        self.items(&order.items, &mut violations);
+        report(violations)
+    }
+
+    pub fn check_update(&self, order: &UpdateOrder) -> Result<(), ServerError> {
+        let mut violations: Vec<Violation> = Vec::new();
+        self.timestamp("updated_at", order.updated_at, &mut violations);
+        self.name("table_id", &order.table_id, &mut violations);
+        self.items(&order.items, &mut violations);
+        report(violations)
+    }
+
+    pub fn check_delete(&self, order: &DeleteOrder) -> Result<(), ServerError> {
+        let mut violations: Vec<Violation> = Vec::new();
+        self.timestamp("deleted_at", order.deleted_at, &mut violations);
+        self.name("table_id", &order.table_id, &mut violations);
+        self.name("item", &order.item, &mut violations);
+        report(violations)
+    }
+
+    pub fn check_kitchen(&self, order: &KitchenOrder) -> Result<(), ServerError> {
+        let mut violations: Vec<Violation> = Vec::new();
+        self.name("table_id", &order.table_id, &mut violations);
+        self.name("item", &order.item, &mut violations);
+        report(violations)
+    }
+
+    // Dish names follow the rules of the item names of the orders, or no order could name them
+    pub fn check_menu_item(&self, item: &MenuItem) -> Result<(), ServerError> {
+        let mut violations: Vec<Violation> = Vec::new();
+        self.name("name", &item.name, &mut violations);
+        if item.cook_time < 0 {
+            violations.push(violation("cook_time", "should be at least 0".to_string()));
+        }
+        report(violations)
+    }
+
+    // A tolerance of 0 accepts any timestamp
+    fn timestamp(&self, field: &str, at: DateTime<Utc>, violations: &mut Vec<Violation>) {
+        let drift: i64 = (Utc::now() - at).num_seconds().abs();
+        if self.timestamp_tolerance > 0 && drift > self.timestamp_tolerance {
+            violations.push(violation(field, format!("is {} secs off the server clock, at most {} secs allowed", drift, self.timestamp_tolerance)));
+        }
+    }
+
+    fn name(&self, field: &str, name: &str, violations: &mut Vec<Violation>) {
+        if name.trim().is_empty() {
+            violations.push(violation(field, "should not be blank".to_string()));
+        } else if name.trim() != name || name.chars().any(char::is_control) {
+            violations.push(violation(field, "should not have surrounding spaces or control characters".to_string()));
+        }
+        if name.chars().count() > self.max_name_len {
+            violations.push(violation(field, format!("should be at most {} characters", self.max_name_len)));
+        }
+    }
+
+    fn items(&self, items: &[ItemPair], violations: &mut Vec<Violation>) {
+        if items.is_empty() {
+            violations.push(violation("items", "should not be empty".to_string()));
+        } else if items.len() > self.max_items {
+            violations.push(violation("items", format!("should have at most {} items", self.max_items)));
+        }
+
+        for (idx, elem) in items.iter().enumerate() {
+            self.name(&format!("items[{}].name", idx), &elem.name, violations);
+            if let Some(first) = items[..idx].iter().position(|prev| prev.name == elem.name) {
+                violations.push(violation(&format!("items[{}].name", idx), format!("duplicates items[{}].name", first)));
+            }
+            if elem.amount < 1 {
+                violations.push(violation(&format!("items[{}].amount", idx), "should be at least 1".to_string()));
+            } else if elem.amount > self.max_amount {
+                violations.push(violation(&format!("items[{}].amount", idx), format!("should be at most {}", self.max_amount)));
+            }
+        }
+    }
+}
+
+fn violation(field: &str, message: String) -> Violation {
+    Violation { field: field.to_string(), message }
+}
+
+fn report(violations: Vec<Violation>) -> Result<(), ServerError> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(ServerError::Violations(violations))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+    use chrono::TimeZone;
+
+    fn rules() -> Rules {
+        Rules { max_items: 3, max_amount: 10, max_name_len: 8, timestamp_tolerance: 60 }
+    }
+
+    fn items(pairs: &[(&str, i32)]) -> Vec<ItemPair> {
+        pairs.iter().map(|(name, amount)| ItemPair { name: name.to_string(), amount: *amount }).collect()
+    }
+
+    fn fields(res: Result<(), ServerError>) -> Vec<String> {
+        match res {
+            Err(ServerError::Violations(violations)) => violations.into_iter().map(|violation| violation.field).collect(),
+            Err(err) => panic!("[TEST::VALIDATION] Unexpected error: {}", err),
+            Ok(()) => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_validation_rules_new_given_config_provided_when_init_then_limits_obtained() {
+        let rules: Rules = Rules::new(&Settings::new().validation);
+        assert!(rules.max_items > 0);
+        assert!(rules.max_amount > 0);
+    }
+
+    #[test]
+    fn test_validation_check_place_given_valid_order_when_checked_then_ok() {
+        let order: PlaceOrder = PlaceOrder { created_at: Utc::now(), table_id: "1".to_string(), items: items(&[("A", 1), ("B", 10)]) };
+        assert!(rules().check_place(&order).is_ok());
+    }
+
+    #[test]
+    fn test_validation_check_place_given_every_field_invalid_when_checked_then_all_violations_returned() {
+        let order: PlaceOrder = PlaceOrder {
+            created_at: Utc.timestamp_opt(0, 0).unwrap(),
+            table_id: " ".to_string(),
+            items: items(&[("A", 0), ("Too long name", 1), ("A", 11)]),
+        };
+        assert_eq!(
+            vec!["created_at", "table_id", "items[0].amount", "items[1].name", "items[2].name", "items[2].amount"],
+            fields(rules().check_place(&order))
+        );
+    }
+
+    #[test]
+    fn test_validation_check_update_given_no_items_when_checked_then_items_violation_returned() {
+        let order: UpdateOrder = UpdateOrder { updated_at: Utc::now(), table_id: "1".to_string(), items: Vec::new() };
+        assert_eq!(vec!["items"], fields(rules().check_update(&order)));
+        let order: UpdateOrder = UpdateOrder { updated_at: Utc::now(), table_id: "1".to_string(), items: items(&[("A", 1), ("B", 1), ("C", 1), ("D", 1)]) };
+        assert_eq!(vec!["items"], fields(rules().check_update(&order)));
+    }
+
+    #[test]
+    fn test_validation_check_delete_given_blank_item_and_padded_table_id_when_checked_then_both_returned() {
+        let order: DeleteOrder = DeleteOrder { deleted_at: Utc::now(), table_id: " 1".to_string(), item: "".to_string() };
+        assert_eq!(vec!["table_id", "item"], fields(rules().check_delete(&order)));
+    }
+
+    #[test]
+    fn test_validation_check_delete_given_zero_tolerance_when_checked_then_any_timestamp_accepted() {
+        let rules: Rules = Rules { timestamp_tolerance: 0, ..rules() };
+        let order: DeleteOrder = DeleteOrder { deleted_at: Utc.timestamp_opt(0, 0).unwrap(), table_id: "1".to_string(), item: "A".to_string() };
+        assert!(rules.check_delete(&order).is_ok());
+    }
+
+    #[test]
+    fn test_validation_check_kitchen_given_padded_table_id_and_blank_item_when_checked_then_both_returned() {
+        let order: KitchenOrder = KitchenOrder { table_id: "1 ".to_string(), item: " ".to_string() };
+        assert_eq!(vec!["table_id", "item"], fields(rules().check_kitchen(&order)));
+        let order: KitchenOrder = KitchenOrder { table_id: "1".to_string(), item: "A".to_string() };
+        assert!(rules().check_kitchen(&order).is_ok());
+    }
+
+    #[test]
+    fn test_validation_check_menu_item_given_names_no_order_could_use_when_checked_then_name_violation_returned() {
+        let item = |name: &str, cook_time: i32| MenuItem {
+            name: name.to_string(),
+            description: String::new(),
+            category: "main".to_string(),
+            cook_time,
+            active: true,
+        };
+        assert!(rules().check_menu_item(&item("Soup", 0)).is_ok());
+        assert_eq!(vec!["name"], fields(rules().check_menu_item(&item(" Soup", 5))));
+        assert_eq!(vec!["name"], fields(rules().check_menu_item(&item("So\tup", 5))));
+        assert_eq!(vec!["name", "cook_time"], fields(rules().check_menu_item(&item("Chicken soup", -1))));
+    }
+}
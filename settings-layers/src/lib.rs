@@ -0,0 +1,262 @@
+use config::{Config, ConfigError, File};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_PROFILE: &str = "production";
+pub const PROFILES: &[&str] = &["development", "test", "production"];
+
+// Variables of the environment layer, `std::env::vars()` but for the tests
+pub type Env = HashMap<String, String>;
+
+// How an application layers its settings, the server and the client each keeping their own variables
+pub struct Layers {
+    // `{prefix}_{SECTION}__{KEY}` variables, `{prefix}_PROFILE` for the profile and `{prefix}_CONFIG_DIR`
+    pub prefix: &'static str,
+    // directory of the crate under the workspace root, holding its `config` when run from there
+    pub crate_dir: &'static str,
+}
+
+impl Layers {
+    // Merge the layers, each one overriding the ones before: `defaults`, `config/default.toml`,
+    // `config/{profile}.toml`, the file given by `--config`, the `{prefix}_*` variables of `env` and the
+    // `--section.key value` flags. Answers the merged config and its profile
+    pub fn load<F>(&self, args: &Args, env: &Env, defaults: F) -> Result<(Config, String), SettingsError>
+    where
+        F: FnOnce(&mut Config) -> Result<(), ConfigError>,
+    {
+        let mut config: Config = Config::default();
+        defaults(&mut config).map_err(|err| SettingsError::Invalid(format!("defaults, {}", err)))?;
+
+        let dir: PathBuf = self.config_dir(args, env)?;
+        merge(&mut config, &dir.join("default.toml"))?;
+        let profile: String = match &args.profile {
+            Some(profile) => profile.clone(),
+            None => env.get(&format!("{}_PROFILE", self.prefix)).cloned().unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+        };
+        if !PROFILES.contains(&profile.as_str()) {
+            return Err(SettingsError::Profile(profile));
+        }
+        let profile_path: PathBuf = dir.join(format!("{}.toml", profile));
+        if profile_path.exists() {
+            merge(&mut config, &profile_path)?;
+        }
+        if let Some(path) = &args.config {
+            merge(&mut config, path)?;
+        }
+        for (name, value) in env {
+            // keys of a section only, e.g. RESTAURANT_SERVER__PORT, leaving RESTAURANT_PROFILE aside
+            let key: String = match name.strip_prefix(self.prefix).and_then(|name| name.strip_prefix('_')) {
+                Some(key) if key.contains("__") => key.to_lowercase().replace("__", "."),
+                _ => continue,
+            };
+            config.set(&key, value.as_str()).map_err(|err| SettingsError::Invalid(format!("{}, {}", name, err)))?;
+        }
+        for (key, value) in &args.overrides {
+            config.set(key, value.as_str()).map_err(|err| SettingsError::Invalid(format!("{}, {}", key, err)))?;
+        }
+        Ok((config, profile))
+    }
+
+    // Keys without a sensible default, each to be set to a non empty value
+    pub fn required(&self, config: &Config, keys: &[&str]) -> Result<(), SettingsError> {
+        for key in keys {
+            match config.get::<String>(key) {
+                Ok(value) if !value.is_empty() => {}
+                _ => return Err(SettingsError::Missing(key.to_string(), self.variable(key))),
+            }
+        }
+        Ok(())
+    }
+
+    // The variable of a key, e.g. RESTAURANT_DATABASE__DB_NAME for database.db_name
+    pub fn variable(&self, key: &str) -> String {
+        format!("{}_{}", self.prefix, key.to_uppercase().replace('.', "__"))
+    }
+
+    // The directory given by `--config-dir` or `{prefix}_CONFIG_DIR`, else the first `config` holding a
+    // default.toml under the working directory, run from the crate or the workspace, or next to the executable
+    fn config_dir(&self, args: &Args, env: &Env) -> Result<PathBuf, SettingsError> {
+        let variable: String = format!("{}_CONFIG_DIR", self.prefix);
+        if let Some(dir) = args.config_dir.clone().or_else(|| env.get(&variable).map(PathBuf::from)) {
+            return Ok(dir);
+        }
+
+        let mut candidates: Vec<PathBuf> = Vec::new();
+        if let Ok(cwd) = std::env::current_dir() {
+            candidates.push(cwd.join("config"));
+            candidates.push(cwd.join(self.crate_dir).join("config"));
+        }
+        if let Some(dir) = std::env::current_exe().ok().as_deref().and_then(Path::parent) {
+            candidates.push(dir.join("config"));
+        }
+        match candidates.iter().find(|dir| dir.join("default.toml").exists()) {
+            Some(dir) => Ok(dir.clone()),
+            None => {
+                let searched: Vec<String> = candidates.iter().map(|dir| dir.display().to_string()).collect();
+                Err(SettingsError::ConfigDir(format!("no default.toml in {}, pass --config-dir or set {}", searched.join(", "), variable)))
+            }
+        }
+    }
+}
+
+// Command line of an application: an optional leading argument, then the flags
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Args {
+    // `migrate` or `hash-password` for the server, the number of staffs for the client
+    pub command: Option<String>,
+    pub config: Option<PathBuf>,
+    pub config_dir: Option<PathBuf>,
+    pub profile: Option<String>,
+    // `--section.key value` or `--section.key=value`
+    pub overrides: Vec<(String, String)>,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, SettingsError> {
+        let mut parsed: Args = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let flag: &str = match arg.strip_prefix("--") {
+                Some(flag) => flag,
+                None if parsed.command.is_none() && parsed == Args::default() => {
+                    parsed.command = Some(arg.clone());
+                    continue;
+                }
+                None => return Err(SettingsError::Args(format!("Unexpected argument: {}", arg))),
+            };
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            if name != "config" && name != "config-dir" && name != "profile" && !name.contains('.') {
+                return Err(SettingsError::Args(format!("Unknown flag: --{}, expected --config, --config-dir, --profile or --section.key", name)));
+            }
+            let value: String = match inline.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(SettingsError::Args(format!("Missing value of --{}", name))),
+            };
+            match name {
+                "config" => parsed.config = Some(PathBuf::from(value)),
+                "config-dir" => parsed.config_dir = Some(PathBuf::from(value)),
+                "profile" => parsed.profile = Some(value),
+                key => parsed.overrides.push((key.to_string(), value)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SettingsError {
+    Args(String),
+    // a config file that can't be read or parsed
+    File(String),
+    // no directory holding the config files
+    ConfigDir(String),
+    Profile(String),
+    // the key along with its variable
+    Missing(String, String),
+    Invalid(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Args(msg) => write!(f, "{}", msg),
+            SettingsError::File(msg) => write!(f, "Config file Error: {}", msg),
+            SettingsError::ConfigDir(msg) => write!(f, "Config directory Error: {}", msg),
+            SettingsError::Profile(profile) => write!(f, "Unknown profile: {}, expected one of {}", profile, PROFILES.join(", ")),
+            SettingsError::Missing(key, variable) => write!(f, "Missing key: {}, set it in a config file, as {} or with --{}", key, variable, key),
+            SettingsError::Invalid(msg) => write!(f, "Invalid settings: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+fn merge(config: &mut Config, path: &Path) -> Result<(), SettingsError> {
+    config
+        .merge(File::from(path))
+        .map(|_| ())
+        .map_err(|err| SettingsError::File(format!("{}, {}", path.display(), err)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LAYERS: Layers = Layers { prefix: "RESTAURANT_TEST", crate_dir: "settings-layers" };
+
+    fn args(args: &[&str]) -> Result<Args, SettingsError> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_layers_args_parse_given_command_and_flags_when_parsed_then_layers_obtained() {
+        let parsed: Args = args(&["migrate", "--profile", "test", "--config=/etc/restaurant.toml", "--config-dir", "/etc/restaurant", "--server.port", "9090"]).unwrap();
+        assert_eq!(Some("migrate".to_string()), parsed.command);
+        assert_eq!(Some(PathBuf::from("/etc/restaurant")), parsed.config_dir);
+        assert_eq!(Some("test".to_string()), parsed.profile);
+        assert_eq!(Some(PathBuf::from("/etc/restaurant.toml")), parsed.config);
+        assert_eq!(vec![("server.port".to_string(), "9090".to_string())], parsed.overrides);
+
+        assert!(matches!(args(&["--verbose", "1"]), Err(SettingsError::Args(_))));
+        assert!(matches!(args(&["--server.port"]), Err(SettingsError::Args(_))));
+        assert!(matches!(args(&["--profile", "test", "migrate"]), Err(SettingsError::Args(_))));
+    }
+
+    #[test]
+    fn test_layers_required_given_key_left_empty_when_checked_then_missing_with_its_variable() {
+        let mut config: Config = Config::default();
+        config.set_default("server.ip", "").unwrap();
+        config.set_default("rate_limit.orders.burst", 10).unwrap();
+
+        assert!(LAYERS.required(&config, &["rate_limit.orders.burst"]).is_ok());
+        let err = LAYERS.required(&config, &["rate_limit.orders.burst", "server.ip"]).unwrap_err();
+        assert_eq!(SettingsError::Missing("server.ip".to_string(), "RESTAURANT_TEST_SERVER__IP".to_string()), err);
+        assert!(err.to_string().contains("--server.ip"));
+    }
+
+    #[test]
+    fn test_layers_load_given_variables_of_the_prefix_when_loaded_then_sections_set_under_the_flags() {
+        let env: Env = vec![
+            ("RESTAURANT_TEST_SERVER__PORT", "9090"),
+            ("RESTAURANT_TEST_RATE_LIMIT__ORDERS__BURST", "20"),
+            ("RESTAURANT_TEST_SERVER__IP", "10.0.0.1"),
+            ("RESTAURANT_TEST_PROFILE", "development"),
+            ("RESTAURANT_SERVER__PORT", "7070"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+        let args: Args = Args { config_dir: Some(server_config()), overrides: vec![("server.ip".to_string(), "127.0.0.1".to_string())], ..Args::default() };
+
+        let (config, profile) = LAYERS.load(&args, &env, |_| Ok(())).unwrap();
+        assert_eq!("development", profile);
+        assert_eq!("9090", config.get::<String>("server.port").unwrap());
+        assert_eq!(20, config.get::<u32>("rate_limit.orders.burst").unwrap());
+        assert_eq!("127.0.0.1", config.get::<String>("server.ip").unwrap());
+        assert!(config.get::<String>("profile").is_err());
+    }
+
+    fn server_config() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("server").join("config")
+    }
+
+    #[test]
+    fn test_layers_config_dir_given_flag_variable_or_workspace_when_looked_up_then_first_found_taken() {
+        let env: Env = vec![("RESTAURANT_TEST_CONFIG_DIR".to_string(), "/etc/restaurant".to_string())].into_iter().collect();
+        let flag: Args = Args { config_dir: Some(PathBuf::from("/opt/restaurant")), ..Args::default() };
+        assert_eq!(PathBuf::from("/opt/restaurant"), LAYERS.config_dir(&flag, &env).unwrap());
+        assert_eq!(PathBuf::from("/etc/restaurant"), LAYERS.config_dir(&Args::default(), &env).unwrap());
+
+        // the tests run from settings-layers, the server crate sits next to it
+        let sibling: Layers = Layers { prefix: "RESTAURANT_TEST", crate_dir: "../server" };
+        let dir: PathBuf = sibling.config_dir(&Args::default(), &Env::new()).unwrap();
+        assert_eq!(server_config().canonicalize().unwrap(), dir.canonicalize().unwrap());
+
+        let err = LAYERS.config_dir(&Args::default(), &Env::new()).unwrap_err();
+        assert!(matches!(err, SettingsError::ConfigDir(ref msg) if msg.contains("RESTAURANT_TEST_CONFIG_DIR")));
+    }
+}